@@ -0,0 +1,569 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use sled::{Db, Tree};
+use std::{
+    collections::HashSet,
+    future::Future,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use crate::parsers::{account::EditForm, archive::ArchivePage, paste::Paste, user::User};
+
+use super::{Account, Backend, BackendError, NewPaste, PasteView, Rating, RawPaste};
+
+#[derive(Serialize, Deserialize)]
+struct Entry<T> {
+    fetched_at: i64,
+    /// Upstream no longer had it when refreshed, so it is only kept as a fallback
+    #[serde(default)]
+    missing: bool,
+    value: T,
+}
+
+// Just the age of an entry, without decoding its value
+#[derive(Deserialize)]
+struct Fetched {
+    fetched_at: i64,
+}
+
+enum Lookup<T> {
+    Fresh(T),
+    Stale(Entry<T>),
+    /// Too old to serve, but kept in case upstream fails
+    Expired(Entry<T>),
+    Miss,
+}
+
+/// A value the cache knows how to store and serve as a fallback
+trait Cacheable: Serialize + DeserializeOwned + Send + 'static {
+    fn cacheable(&self) -> bool {
+        true
+    }
+
+    /// Set on copies that are not fresh from upstream
+    fn captured_at(&self) -> Option<i64>;
+
+    fn set_captured_at(&mut self, timestamp: i64);
+}
+
+impl Cacheable for PasteView {
+    fn cacheable(&self) -> bool {
+        matches!(self, PasteView::Paste(_))
+    }
+
+    fn captured_at(&self) -> Option<i64> {
+        match self {
+            PasteView::Paste(paste) => paste.captured_at(),
+            _ => None,
+        }
+    }
+
+    fn set_captured_at(&mut self, timestamp: i64) {
+        if let PasteView::Paste(paste) = self {
+            paste.set_captured_at(timestamp);
+        }
+    }
+}
+
+impl Cacheable for RawPaste {
+    fn captured_at(&self) -> Option<i64> {
+        self.captured_at
+    }
+
+    fn set_captured_at(&mut self, timestamp: i64) {
+        self.captured_at = Some(timestamp);
+    }
+}
+
+/// Caches parsed pastes and raw bodies from another backend in sled.
+///
+/// Entries younger than `ttl` are served as is. Entries that are older but
+/// still within the `stale` window are served immediately while a background
+/// task fetches a fresh copy, so popular pastes never wait on upstream. If
+/// that fetch finds the paste deleted, the entry is only kept as a fallback.
+///
+/// Past that, entries are kept for another `fallback` window. They are only
+/// served when upstream is down or no longer has the paste. Stale and
+/// fallback copies are marked with the time they were captured so the user
+/// knows the copy may be old. With a zero `ttl` and `stale`, every request
+/// goes upstream and the cache only serves as that fallback.
+pub struct CachedBackend {
+    inner: Arc<dyn Backend>,
+    pastes: Tree,
+    raws: Tree,
+    ttl: i64,
+    stale: i64,
+    fallback: i64,
+    refreshing: Arc<Mutex<HashSet<String>>>,
+}
+
+impl CachedBackend {
+    pub fn new(
+        inner: Arc<dyn Backend>,
+        db: &Db,
+        ttl: Duration,
+        stale: Duration,
+        fallback: Duration,
+    ) -> sled::Result<Self> {
+        Ok(Self {
+            inner,
+            pastes: db.open_tree("pastes")?,
+            raws: db.open_tree("raws")?,
+            ttl: ttl.as_secs() as i64,
+            stale: stale.as_secs() as i64,
+            fallback: fallback.as_secs() as i64,
+            refreshing: Arc::new(Mutex::new(HashSet::new())),
+        })
+    }
+
+    /// Forgets every cached copy of a paste its author changed or deleted
+    fn invalidate(&self, id: &str) {
+        for tree in [&self.pastes, &self.raws] {
+            if let Err(e) = tree.remove(id) {
+                eprintln!("Failed to invalidate cache entry '{}': {}", id, e);
+            }
+        }
+    }
+
+    /// Drops every entry past its fallback window and returns how many were removed.
+    ///
+    /// Lookups already drop such entries, this catches pastes nobody asks for again.
+    pub fn prune(&self) -> sled::Result<usize> {
+        let now = Utc::now().timestamp();
+        let lifetime = self.ttl + self.stale + self.fallback;
+
+        let mut removed = 0;
+        for tree in [&self.pastes, &self.raws] {
+            for entry in tree.iter() {
+                let (key, value) = entry?;
+                let expired = serde_json::from_slice::<Fetched>(&value)
+                    .map_or(true, |entry| now - entry.fetched_at >= lifetime);
+
+                if expired {
+                    tree.remove(key)?;
+                    removed += 1;
+                }
+            }
+        }
+
+        Ok(removed)
+    }
+
+    fn lookup<T: DeserializeOwned>(&self, tree: &Tree, key: &str) -> Lookup<T> {
+        let entry = match tree.get(key) {
+            Ok(Some(bytes)) => serde_json::from_slice::<Entry<T>>(&bytes).ok(),
+            Ok(None) => None,
+            Err(e) => {
+                eprintln!("Failed to read cache entry '{}': {}", key, e);
+                None
+            }
+        };
+
+        let Some(entry) = entry else {
+            return Lookup::Miss;
+        };
+
+        let age = Utc::now().timestamp() - entry.fetched_at;
+        if age < self.ttl && !entry.missing {
+            Lookup::Fresh(entry.value)
+        } else if age < self.ttl + self.stale && !entry.missing {
+            Lookup::Stale(entry)
+        } else if age < self.ttl + self.stale + self.fallback {
+            Lookup::Expired(entry)
+        } else {
+            tree.remove(key).ok();
+            Lookup::Miss
+        }
+    }
+
+    async fn get_cached<T, F, Fut>(
+        &self,
+        tree: &Tree,
+        key: &str,
+        fetch: F,
+    ) -> Result<T, BackendError>
+    where
+        T: Cacheable,
+        F: FnOnce(Arc<dyn Backend>, String) -> Fut + Send + 'static,
+        Fut: Future<Output = Result<T, BackendError>> + Send + 'static,
+    {
+        let last_known = match self.lookup(tree, key) {
+            Lookup::Fresh(value) => return Ok(value),
+            Lookup::Stale(Entry {
+                fetched_at,
+                mut value,
+                ..
+            }) => {
+                self.refresh(tree, key, fetch);
+                value.set_captured_at(fetched_at);
+                return Ok(value);
+            }
+            Lookup::Expired(entry) => Some(entry),
+            Lookup::Miss => None,
+        };
+
+        match fetch(self.inner.clone(), key.to_string()).await {
+            // A fallback copy from the wrapped backend, keep ours as is
+            Ok(value) if value.captured_at().is_some() => Ok(value),
+            Ok(value) => {
+                if value.cacheable() {
+                    store(tree, key, &value);
+                } else if last_known.is_some() {
+                    tree.remove(key).ok();
+                }
+                Ok(value)
+            }
+            Err(err) => match last_known {
+                Some(Entry {
+                    fetched_at,
+                    mut value,
+                    ..
+                }) if err.is_not_found() || err.is_unavailable() => {
+                    value.set_captured_at(fetched_at);
+                    Ok(value)
+                }
+                _ => Err(err),
+            },
+        }
+    }
+
+    /// Refetches a stale entry in the background, at most once at a time per key
+    fn refresh<T, F, Fut>(&self, tree: &Tree, key: &str, fetch: F)
+    where
+        T: Cacheable,
+        F: FnOnce(Arc<dyn Backend>, String) -> Fut + Send + 'static,
+        Fut: Future<Output = Result<T, BackendError>> + Send + 'static,
+    {
+        let refresh_key = format!("{}/{}", String::from_utf8_lossy(&tree.name()), key);
+        {
+            let mut refreshing = self.refreshing.lock().unwrap_or_else(|e| e.into_inner());
+            if !refreshing.insert(refresh_key.clone()) {
+                return;
+            }
+        }
+
+        let inner = self.inner.clone();
+        let tree = tree.clone();
+        let key = key.to_string();
+        let refreshing = self.refreshing.clone();
+        let keep_missing = self.fallback > 0;
+
+        tokio::spawn(async move {
+            match fetch(inner, key.clone()).await {
+                Ok(value) if value.captured_at().is_some() => {}
+                Ok(value) if value.cacheable() => store(&tree, &key, &value),
+                Ok(_) => {
+                    tree.remove(&key).ok();
+                }
+                // Deleted pastes are kept around to fall back on, but no longer
+                // served before asking upstream
+                Err(err) if err.is_not_found() && keep_missing => mark_missing(&tree, &key),
+                Err(err) if err.is_not_found() => {
+                    tree.remove(&key).ok();
+                }
+                Err(err) => eprintln!("Failed to refresh cache entry '{}': {}", key, err),
+            }
+
+            refreshing
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .remove(&refresh_key);
+        });
+    }
+}
+
+fn store<T: Serialize>(tree: &Tree, key: &str, value: &T) {
+    let entry = Entry {
+        fetched_at: Utc::now().timestamp(),
+        missing: false,
+        value,
+    };
+
+    match serde_json::to_vec(&entry) {
+        Ok(bytes) => {
+            if let Err(e) = tree.insert(key, bytes) {
+                eprintln!("Failed to write cache entry '{}': {}", key, e);
+            }
+        }
+        Err(e) => eprintln!("Failed to serialize cache entry '{}': {}", key, e),
+    }
+}
+
+fn mark_missing(tree: &Tree, key: &str) {
+    let marked = tree.update_and_fetch(key, |bytes| {
+        let mut entry = serde_json::from_slice::<serde_json::Value>(bytes?).ok()?;
+        entry
+            .as_object_mut()?
+            .insert("missing".to_string(), true.into());
+        serde_json::to_vec(&entry).ok()
+    });
+
+    if let Err(e) = marked {
+        eprintln!("Failed to mark cache entry '{}' as missing: {}", key, e);
+    }
+}
+
+#[async_trait]
+impl Backend for CachedBackend {
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    async fn get_paste(&self, id: &str) -> Result<PasteView, BackendError> {
+        self.get_cached(&self.pastes, id, |inner, id| async move {
+            inner.get_paste(&id).await
+        })
+        .await
+    }
+
+    async fn unlock_paste(&self, id: &str, password: &str) -> Result<Paste, BackendError> {
+        self.inner.unlock_paste(id, password).await
+    }
+
+    async fn get_raw(&self, id: &str) -> Result<RawPaste, BackendError> {
+        self.get_cached(&self.raws, id, |inner, id| async move {
+            inner.get_raw(&id).await
+        })
+        .await
+    }
+
+    async fn create_paste(
+        &self,
+        paste: NewPaste,
+        account: Option<&Account>,
+    ) -> Result<String, BackendError> {
+        self.inner.create_paste(paste, account).await
+    }
+
+    async fn create_comment(
+        &self,
+        parent: &str,
+        comment: NewPaste,
+        account: Option<&Account>,
+    ) -> Result<String, BackendError> {
+        let id = self.inner.create_comment(parent, comment, account).await?;
+
+        // So the parent is shown with its new comment right away
+        self.invalidate(parent);
+        Ok(id)
+    }
+
+    async fn login(&self, username: &str, password: &str) -> Result<Account, BackendError> {
+        self.inner.login(username, password).await
+    }
+
+    async fn get_edit_form(&self, id: &str, account: &Account) -> Result<EditForm, BackendError> {
+        self.inner.get_edit_form(id, account).await
+    }
+
+    async fn edit_paste(
+        &self,
+        id: &str,
+        paste: NewPaste,
+        account: &Account,
+    ) -> Result<(), BackendError> {
+        self.inner.edit_paste(id, paste, account).await?;
+        self.invalidate(id);
+        Ok(())
+    }
+
+    async fn delete_paste(&self, id: &str, account: &Account) -> Result<(), BackendError> {
+        self.inner.delete_paste(id, account).await?;
+        self.invalidate(id);
+        Ok(())
+    }
+
+    async fn rate_paste(
+        &self,
+        id: &str,
+        rating: Rating,
+        account: Option<&Account>,
+    ) -> Result<(), BackendError> {
+        self.inner.rate_paste(id, rating, account).await?;
+        self.invalidate(id);
+        Ok(())
+    }
+
+    async fn report_paste(
+        &self,
+        id: &str,
+        reason: &str,
+        account: Option<&Account>,
+    ) -> Result<(), BackendError> {
+        self.inner.report_paste(id, reason, account).await
+    }
+
+    async fn get_user(&self, username: &str) -> Result<User, BackendError> {
+        self.inner.get_user(username).await
+    }
+
+    async fn get_archive(&self, format: Option<&str>) -> Result<ArchivePage, BackendError> {
+        self.inner.get_archive(format).await
+    }
+
+    async fn get_icon(&self, path: &str) -> Result<Vec<u8>, BackendError> {
+        self.inner.get_icon(path).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::mock::{MockBackend, OWNER};
+    use super::*;
+
+    fn setup(ttl: u64, stale: u64, fallback: u64) -> (Arc<MockBackend>, CachedBackend) {
+        let mock = Arc::new(MockBackend::default());
+        let db = sled::Config::default()
+            .temporary(true)
+            .open()
+            .expect("Temporary database");
+        let cached = CachedBackend::new(
+            mock.clone(),
+            &db,
+            Duration::from_secs(ttl),
+            Duration::from_secs(stale),
+            Duration::from_secs(fallback),
+        )
+        .expect("Cache trees");
+
+        (mock, cached)
+    }
+
+    #[tokio::test]
+    async fn test_fresh_entries_are_served_from_cache() {
+        let (mock, cached) = setup(60, 60, 0);
+
+        assert_eq!(
+            cached.get_raw("abc").await.ok().map(|raw| raw.text),
+            Some("abc #1".to_string())
+        );
+        assert_eq!(
+            cached.get_raw("abc").await.ok().map(|raw| raw.text),
+            Some("abc #1".to_string())
+        );
+        assert_eq!(mock.calls(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_stale_entries_are_refreshed_in_background() {
+        let (mock, cached) = setup(0, 60, 0);
+
+        let fetched = cached.get_raw("abc").await.expect("First fetch");
+        assert_eq!(fetched.text, "abc #1");
+        assert!(fetched.captured_at.is_none());
+
+        let stale = cached.get_raw("abc").await.expect("Stale copy");
+        assert_eq!(stale.text, "abc #1");
+        assert!(stale.captured_at.is_some());
+
+        for _ in 0..100 {
+            if mock.calls() == 2 {
+                break;
+            }
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(mock.calls(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_stale_copies_deleted_upstream_are_only_a_fallback() {
+        let (mock, cached) = setup(0, 60, 60);
+
+        cached.get_raw("abc").await.expect("First fetch");
+        mock.set_deleted(true);
+        cached.get_raw("abc").await.expect("Stale copy");
+
+        for _ in 0..100 {
+            if mock.calls() == 2 && cached.refreshing.lock().unwrap().is_empty() {
+                break;
+            }
+            tokio::task::yield_now().await;
+        }
+
+        // Upstream is asked again before the copy is served, as a fallback
+        let fallback = cached.get_raw("abc").await.expect("Fallback copy");
+        assert_eq!(mock.calls(), 3);
+        assert_eq!(fallback.text, "abc #1");
+        assert!(fallback.captured_at.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_errors_are_not_cached() {
+        let (mock, cached) = setup(60, 60, 0);
+
+        mock.set_failing(true);
+        assert!(cached.get_raw("abc").await.is_err());
+
+        mock.set_failing(false);
+        assert_eq!(
+            cached.get_raw("abc").await.ok().map(|raw| raw.text),
+            Some("abc #2".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_last_known_copy_is_served_when_upstream_fails() {
+        let (mock, cached) = setup(0, 0, 60);
+
+        let fetched = cached.get_raw("abc").await.expect("First fetch");
+        assert!(fetched.captured_at.is_none());
+
+        mock.set_failing(true);
+        let fallback = cached.get_raw("abc").await.expect("Fallback copy");
+        assert_eq!(fallback.text, "abc #1");
+        assert!(fallback.captured_at.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_edits_drop_cached_copies() {
+        let (mock, cached) = setup(60, 60, 60);
+        let account = Account {
+            username: OWNER.to_string(),
+            cookies: Default::default(),
+        };
+
+        assert!(cached.get_raw("abc").await.is_ok());
+        cached
+            .delete_paste("abc", &account)
+            .await
+            .expect("Owner deletes");
+
+        mock.set_failing(true);
+        assert!(cached.get_raw("abc").await.is_err());
+        assert_eq!(mock.calls(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_no_fallback_without_fallback_window() {
+        let (mock, cached) = setup(0, 0, 0);
+
+        assert!(cached.get_raw("abc").await.is_ok());
+
+        mock.set_failing(true);
+        assert!(cached.get_raw("abc").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_prune_drops_entries_past_fallback() {
+        let (_, cached) = setup(60, 0, 0);
+
+        assert!(cached.get_raw("abc").await.is_ok());
+        assert_eq!(cached.prune().expect("Prune"), 0);
+
+        let entry = Entry {
+            fetched_at: 0,
+            missing: false,
+            value: RawPaste::from("old".to_string()),
+        };
+        cached
+            .raws
+            .insert("old", serde_json::to_vec(&entry).unwrap())
+            .unwrap();
+
+        assert_eq!(cached.prune().expect("Prune"), 1);
+        assert!(cached.raws.get("old").unwrap().is_none());
+        assert!(cached.raws.get("abc").unwrap().is_some());
+    }
+}
@@ -0,0 +1,180 @@
+use async_trait::async_trait;
+use std::sync::Arc;
+
+use crate::{
+    parsers::{account::EditForm, archive::ArchivePage, paste::Paste, user::User},
+    singleflight::SingleFlight,
+};
+
+use super::{Account, Backend, BackendError, NewPaste, PasteView, Rating, RawPaste};
+
+/// Shares a single upstream fetch between concurrent requests for the same resource.
+///
+/// When a paste goes viral many visitors ask for it at once; only the first
+/// request goes upstream and the rest receive a copy of its result, errors
+/// included. Writes and password unlocks are never coalesced.
+pub struct CoalescedBackend {
+    inner: Arc<dyn Backend>,
+    pastes: SingleFlight<Result<PasteView, BackendError>>,
+    raws: SingleFlight<Result<RawPaste, BackendError>>,
+    users: SingleFlight<Result<User, BackendError>>,
+    archives: SingleFlight<Result<ArchivePage, BackendError>>,
+    icons: SingleFlight<Result<Vec<u8>, BackendError>>,
+}
+
+impl CoalescedBackend {
+    pub fn new(inner: Arc<dyn Backend>) -> Self {
+        Self {
+            inner,
+            pastes: SingleFlight::new(),
+            raws: SingleFlight::new(),
+            users: SingleFlight::new(),
+            archives: SingleFlight::new(),
+            icons: SingleFlight::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl Backend for CoalescedBackend {
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    async fn get_paste(&self, id: &str) -> Result<PasteView, BackendError> {
+        self.pastes.run(id, || self.inner.get_paste(id)).await
+    }
+
+    async fn unlock_paste(&self, id: &str, password: &str) -> Result<Paste, BackendError> {
+        self.inner.unlock_paste(id, password).await
+    }
+
+    async fn get_raw(&self, id: &str) -> Result<RawPaste, BackendError> {
+        self.raws.run(id, || self.inner.get_raw(id)).await
+    }
+
+    async fn create_paste(
+        &self,
+        paste: NewPaste,
+        account: Option<&Account>,
+    ) -> Result<String, BackendError> {
+        self.inner.create_paste(paste, account).await
+    }
+
+    async fn create_comment(
+        &self,
+        parent: &str,
+        comment: NewPaste,
+        account: Option<&Account>,
+    ) -> Result<String, BackendError> {
+        self.inner.create_comment(parent, comment, account).await
+    }
+
+    async fn login(&self, username: &str, password: &str) -> Result<Account, BackendError> {
+        self.inner.login(username, password).await
+    }
+
+    async fn get_edit_form(&self, id: &str, account: &Account) -> Result<EditForm, BackendError> {
+        self.inner.get_edit_form(id, account).await
+    }
+
+    async fn edit_paste(
+        &self,
+        id: &str,
+        paste: NewPaste,
+        account: &Account,
+    ) -> Result<(), BackendError> {
+        self.inner.edit_paste(id, paste, account).await
+    }
+
+    async fn delete_paste(&self, id: &str, account: &Account) -> Result<(), BackendError> {
+        self.inner.delete_paste(id, account).await
+    }
+
+    async fn rate_paste(
+        &self,
+        id: &str,
+        rating: Rating,
+        account: Option<&Account>,
+    ) -> Result<(), BackendError> {
+        self.inner.rate_paste(id, rating, account).await
+    }
+
+    async fn report_paste(
+        &self,
+        id: &str,
+        reason: &str,
+        account: Option<&Account>,
+    ) -> Result<(), BackendError> {
+        self.inner.report_paste(id, reason, account).await
+    }
+
+    async fn get_user(&self, username: &str) -> Result<User, BackendError> {
+        self.users
+            .run(username, || self.inner.get_user(username))
+            .await
+    }
+
+    async fn get_archive(&self, format: Option<&str>) -> Result<ArchivePage, BackendError> {
+        self.archives
+            .run(format.unwrap_or_default(), || {
+                self.inner.get_archive(format)
+            })
+            .await
+    }
+
+    async fn get_icon(&self, path: &str) -> Result<Vec<u8>, BackendError> {
+        self.icons.run(path, || self.inner.get_icon(path)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::mock::MockBackend;
+    use super::*;
+
+    #[tokio::test]
+    async fn test_concurrent_fetches_are_coalesced() {
+        let mock = Arc::new(MockBackend::default());
+        let backend = CoalescedBackend::new(mock.clone());
+
+        let (a, b, c) = tokio::join!(
+            backend.get_raw("abc"),
+            backend.get_raw("abc"),
+            backend.get_raw("abc")
+        );
+
+        assert_eq!(a.ok().map(|raw| raw.text), Some("abc #1".to_string()));
+        assert_eq!(b.ok().map(|raw| raw.text), Some("abc #1".to_string()));
+        assert_eq!(c.ok().map(|raw| raw.text), Some("abc #1".to_string()));
+        assert_eq!(mock.calls(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_different_keys_are_not_coalesced() {
+        let mock = Arc::new(MockBackend::default());
+        let backend = CoalescedBackend::new(mock.clone());
+
+        let (a, b) = tokio::join!(backend.get_raw("abc"), backend.get_raw("def"));
+
+        assert!(a.is_ok() && b.is_ok());
+        assert_eq!(mock.calls(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_errors_are_shared_but_not_remembered() {
+        let mock = Arc::new(MockBackend::default());
+        let backend = CoalescedBackend::new(mock.clone());
+
+        mock.set_failing(true);
+        let (a, b) = tokio::join!(backend.get_raw("abc"), backend.get_raw("abc"));
+        assert!(a.is_err() && b.is_err());
+        assert_eq!(mock.calls(), 1);
+
+        mock.set_failing(false);
+        assert_eq!(
+            backend.get_raw("abc").await.ok().map(|raw| raw.text),
+            Some("abc #2".to_string())
+        );
+    }
+}
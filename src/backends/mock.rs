@@ -0,0 +1,212 @@
+use async_trait::async_trait;
+use serde_json::json;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use crate::{
+    client::ClientError,
+    parsers::{account::EditForm, archive::ArchivePage, paste::Paste, user::User},
+};
+
+use super::{Account, Backend, BackendError, NewPaste, PasteView, Rating, RawPaste};
+
+/// Account that owns every mock paste
+pub const OWNER: &str = "alice";
+
+/// Test backend that serves numbered raw pastes, accepts new pastes and counts upstream calls.
+/// Every paste belongs to the account named `OWNER` and has one comment, which
+/// has a reply of its own.
+#[derive(Default)]
+pub struct MockBackend {
+    pub calls: AtomicUsize,
+    pub failing: AtomicBool,
+    pub deleted: AtomicBool,
+}
+
+fn check_owner(account: &Account) -> Result<(), BackendError> {
+    if account.username != OWNER {
+        return Err(BackendError::Rejected(
+            "Only the author of this paste can change it".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+impl MockBackend {
+    pub fn calls(&self) -> usize {
+        self.calls.load(Ordering::SeqCst)
+    }
+
+    pub fn set_failing(&self, failing: bool) {
+        self.failing.store(failing, Ordering::SeqCst);
+    }
+
+    pub fn set_deleted(&self, deleted: bool) {
+        self.deleted.store(deleted, Ordering::SeqCst);
+    }
+}
+
+#[async_trait]
+impl Backend for MockBackend {
+    fn name(&self) -> &'static str {
+        "mock"
+    }
+
+    async fn get_paste(&self, id: &str) -> Result<PasteView, BackendError> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+
+        let author = json!({
+            "username": OWNER,
+            "registered": true,
+            "pro": false,
+            "icon_url": "",
+        });
+        let container = |id: &str| {
+            json!({
+                "category": null,
+                "size": 0,
+                "likes": null,
+                "dislikes": null,
+                "id": id,
+                "format": "text",
+                "format_name": "None",
+                "content": id,
+            })
+        };
+        let paste = json!({
+            "id": id,
+            "title": null,
+            "tags": [],
+            "container": container(id),
+            "author": author,
+            "date": 0,
+            "edit_date": null,
+            "views": 0,
+            "rating": 0.0,
+            "expire": "Never",
+            "comment_for": null,
+            "unlisted": false,
+            "num_comments": 1,
+            "comments": [{
+                "author": author,
+                "date": 0,
+                "edit_date": null,
+                "container": container(&format!("{id}-r")),
+                "num_comments": 1,
+            }],
+            "locked": false,
+        });
+
+        serde_json::from_value(paste)
+            .map(|paste| PasteView::Paste(Box::new(paste)))
+            .map_err(|e| BackendError::Parser(e.to_string()))
+    }
+
+    async fn unlock_paste(&self, _id: &str, _password: &str) -> Result<Paste, BackendError> {
+        unimplemented!()
+    }
+
+    async fn get_raw(&self, id: &str) -> Result<RawPaste, BackendError> {
+        let call = self.calls.fetch_add(1, Ordering::SeqCst) + 1;
+        tokio::task::yield_now().await;
+
+        if self.failing.load(Ordering::SeqCst) {
+            Err(ClientError::from(ureq::Error::ConnectionFailed).into())
+        } else if self.deleted.load(Ordering::SeqCst) {
+            Err(ClientError::from(ureq::Error::StatusCode(404)).into())
+        } else {
+            Ok(format!("{id} #{call}").into())
+        }
+    }
+
+    async fn create_paste(
+        &self,
+        paste: NewPaste,
+        _account: Option<&Account>,
+    ) -> Result<String, BackendError> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        Ok(format!("new{}", paste.text.len()))
+    }
+
+    async fn create_comment(
+        &self,
+        parent: &str,
+        comment: NewPaste,
+        _account: Option<&Account>,
+    ) -> Result<String, BackendError> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        Ok(format!("{parent}re{}", comment.text.len()))
+    }
+
+    async fn login(&self, username: &str, password: &str) -> Result<Account, BackendError> {
+        if password != "hunter2" {
+            return Err(BackendError::Rejected(
+                "Incorrect username or password".to_string(),
+            ));
+        }
+
+        Ok(Account {
+            username: username.to_string(),
+            cookies: Default::default(),
+        })
+    }
+
+    async fn get_edit_form(&self, id: &str, account: &Account) -> Result<EditForm, BackendError> {
+        check_owner(account)?;
+        Ok(EditForm {
+            text: format!("{id} text"),
+            title: id.to_string(),
+            tags: String::new(),
+            category: 0,
+            format: 1,
+            expiration: None,
+            exposure: 1,
+        })
+    }
+
+    async fn edit_paste(
+        &self,
+        _id: &str,
+        _paste: NewPaste,
+        account: &Account,
+    ) -> Result<(), BackendError> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        check_owner(account)
+    }
+
+    async fn delete_paste(&self, _id: &str, account: &Account) -> Result<(), BackendError> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        check_owner(account)
+    }
+
+    async fn rate_paste(
+        &self,
+        _id: &str,
+        _rating: Rating,
+        _account: Option<&Account>,
+    ) -> Result<(), BackendError> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+
+    async fn report_paste(
+        &self,
+        _id: &str,
+        _reason: &str,
+        _account: Option<&Account>,
+    ) -> Result<(), BackendError> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+
+    async fn get_user(&self, _username: &str) -> Result<User, BackendError> {
+        unimplemented!()
+    }
+
+    async fn get_archive(&self, _format: Option<&str>) -> Result<ArchivePage, BackendError> {
+        unimplemented!()
+    }
+
+    async fn get_icon(&self, _path: &str) -> Result<Vec<u8>, BackendError> {
+        unimplemented!()
+    }
+}
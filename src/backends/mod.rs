@@ -0,0 +1,203 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::{fmt, sync::Arc};
+
+use crate::{
+    client::{Client, ClientError, Cookies},
+    parsers::{account::EditForm, archive::ArchivePage, paste::Paste, user::User},
+};
+
+pub mod cached;
+pub mod coalesced;
+#[cfg(test)]
+pub(crate) mod mock;
+pub mod pastebin;
+pub mod revisions;
+pub mod snapshot;
+pub mod threaded;
+
+/// Result of looking up a paste on an upstream service
+#[derive(Serialize, Deserialize, Clone)]
+pub enum PasteView {
+    Paste(Box<Paste>),
+    Locked { burn: bool },
+    Burn,
+}
+
+/// Plain text body of a paste
+#[derive(Serialize, Deserialize, Clone)]
+pub struct RawPaste {
+    pub text: String,
+    /// When this copy was fetched, set only when it is served from cache
+    /// rather than fresh from upstream
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub captured_at: Option<i64>,
+}
+
+impl From<String> for RawPaste {
+    fn from(text: String) -> Self {
+        Self {
+            text,
+            captured_at: None,
+        }
+    }
+}
+
+/// A paste about to be created on an upstream service
+pub struct NewPaste {
+    pub text: String,
+    pub category: u8,
+    pub tags: String,
+    pub format: u16,
+    pub expiration: String,
+    pub exposure: u8,
+    pub password: String,
+    pub title: String,
+}
+
+/// A visitor's vote on a paste
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Rating {
+    Like,
+    Dislike,
+}
+
+/// An upstream account a visitor signed in to, kept server-side
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Account {
+    pub username: String,
+    pub cookies: Cookies,
+}
+
+#[derive(Debug, Clone)]
+pub enum BackendError {
+    Client(ClientError),
+    Parser(String),
+    /// Upstream refused the request, e.g. for wrong credentials
+    Rejected(String),
+}
+
+impl BackendError {
+    /// Whether upstream reported that the requested resource does not exist
+    pub fn is_not_found(&self) -> bool {
+        match self {
+            BackendError::Client(err) => err.status_code() == Some(404),
+            BackendError::Parser(_) | BackendError::Rejected(_) => false,
+        }
+    }
+
+    /// Whether upstream could not be reached or failed to answer
+    pub fn is_unavailable(&self) -> bool {
+        match self {
+            BackendError::Client(ClientError::CircuitOpen { .. }) => true,
+            BackendError::Client(err) => err.is_upstream_failure(),
+            BackendError::Parser(_) | BackendError::Rejected(_) => false,
+        }
+    }
+}
+
+impl From<ClientError> for BackendError {
+    fn from(value: ClientError) -> Self {
+        BackendError::Client(value)
+    }
+}
+
+impl fmt::Display for BackendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BackendError::Client(err) => write!(f, "{}", err),
+            BackendError::Parser(msg) | BackendError::Rejected(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+/// An upstream paste service that Pasted can act as a frontend for
+#[async_trait]
+pub trait Backend: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    async fn get_paste(&self, id: &str) -> Result<PasteView, BackendError>;
+
+    async fn unlock_paste(&self, id: &str, password: &str) -> Result<Paste, BackendError>;
+
+    async fn get_raw(&self, id: &str) -> Result<RawPaste, BackendError>;
+
+    /// Creates a paste, as a guest unless `account` is given, and returns its ID
+    async fn create_paste(
+        &self,
+        paste: NewPaste,
+        account: Option<&Account>,
+    ) -> Result<String, BackendError>;
+
+    /// Comments on the paste `parent`, as a guest unless `account` is given,
+    /// and returns the comment's ID
+    async fn create_comment(
+        &self,
+        parent: &str,
+        comment: NewPaste,
+        account: Option<&Account>,
+    ) -> Result<String, BackendError>;
+
+    /// Signs in to an upstream account
+    async fn login(&self, username: &str, password: &str) -> Result<Account, BackendError>;
+
+    /// The current form values of a paste, as shown to its author `account`
+    async fn get_edit_form(&self, id: &str, account: &Account) -> Result<EditForm, BackendError>;
+
+    /// Replaces the content and options of a paste owned by `account`
+    async fn edit_paste(
+        &self,
+        id: &str,
+        paste: NewPaste,
+        account: &Account,
+    ) -> Result<(), BackendError>;
+
+    /// Deletes a paste owned by `account`
+    async fn delete_paste(&self, id: &str, account: &Account) -> Result<(), BackendError>;
+
+    /// Likes or dislikes a paste, as a guest unless `account` is given
+    async fn rate_paste(
+        &self,
+        id: &str,
+        rating: Rating,
+        account: Option<&Account>,
+    ) -> Result<(), BackendError>;
+
+    /// Reports a paste to upstream's moderators, as a guest unless `account` is given
+    async fn report_paste(
+        &self,
+        id: &str,
+        reason: &str,
+        account: Option<&Account>,
+    ) -> Result<(), BackendError>;
+
+    async fn get_user(&self, username: &str) -> Result<User, BackendError>;
+
+    async fn get_archive(&self, format: Option<&str>) -> Result<ArchivePage, BackendError>;
+
+    async fn get_icon(&self, path: &str) -> Result<Vec<u8>, BackendError>;
+}
+
+/// Builds the backend configured for this instance by name
+pub fn from_name(name: &str, client: Client, base_url: &str) -> Option<Arc<dyn Backend>> {
+    match name {
+        "pastebin" => Some(Arc::new(pastebin::PastebinBackend::new(
+            client,
+            base_url.to_string(),
+        ))),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::constants::URL;
+
+    use super::*;
+
+    #[test]
+    fn test_from_name() {
+        assert!(from_name("pastebin", Client::default(), URL).is_some());
+        assert!(from_name("unknown", Client::default(), URL).is_none());
+    }
+}
@@ -0,0 +1,430 @@
+use async_trait::async_trait;
+use axum::http::Response;
+use scraper::Html;
+
+use crate::{
+    client::{CSRF_FIELD, Client, ClientError, Cookies},
+    parsers::{
+        FromHtml as _,
+        account::{self, EditForm},
+        archive::ArchivePage,
+        paste::{self, Paste},
+        user::User,
+    },
+};
+
+use super::{Account, Backend, BackendError, NewPaste, PasteView, Rating, RawPaste};
+
+/// Scrapes pastebin.com, or any site serving the same pages from `base_url`
+pub struct PastebinBackend {
+    client: Client,
+    base_url: String,
+}
+
+impl PastebinBackend {
+    pub fn new(client: Client, base_url: String) -> Self {
+        Self { client, base_url }
+    }
+
+    /// Fetches the edit page of a paste, which upstream only shows its author,
+    /// and returns its form along with the page's CSRF token
+    async fn get_edit_page(
+        &self,
+        id: &str,
+        cookies: &mut Cookies,
+    ) -> Result<(EditForm, String), BackendError> {
+        let url = format!("{}/edit/{id}", self.base_url);
+        let response = self.client.get_response_as(&url, cookies).await?;
+        cookies.update(response.headers());
+
+        let status = response.status();
+        if status.as_u16() == 404 || status.is_server_error() {
+            return Err(ClientError::from(ureq::Error::StatusCode(status.as_u16())).into());
+        }
+
+        // Expired sessions are sent to the login page, other accounts elsewhere
+        if redirects_to_login(&response) {
+            return Err(login_required(true));
+        }
+
+        let dom = Html::parse_document(&String::from_utf8_lossy(response.body()));
+        match EditForm::from_html(&dom, &self.base_url) {
+            Ok(form) if status.is_success() => {
+                Ok((form, paste::get_csrftoken(&dom).unwrap_or_default()))
+            }
+            _ => Err(BackendError::Rejected(
+                "Only the author of this paste can change it".to_string(),
+            )),
+        }
+    }
+
+    /// Posts a form made on a visitor's behalf, which upstream answers with a
+    /// redirect once it was accepted
+    async fn submit_as(
+        &self,
+        url: &str,
+        form: Vec<(String, String)>,
+        cookies: &Cookies,
+        signed_in: bool,
+    ) -> Result<(), BackendError> {
+        let response = self.client.post_response_as(url, form, cookies).await?;
+        if redirects_to_login(&response) {
+            return Err(login_required(signed_in));
+        }
+        if response.status().is_redirection() {
+            return Ok(());
+        }
+
+        let dom = Html::parse_document(&String::from_utf8_lossy(response.body()));
+        Err(BackendError::Rejected(
+            account::get_form_error(&dom)
+                .unwrap_or_else(|| format!("Pastebin refused the request ({})", response.status())),
+        ))
+    }
+
+    /// Sends a form from the page `page` for a visitor, with a session of its
+    /// own unless they signed in to `account`
+    async fn act_as(
+        &self,
+        page: &str,
+        url: &str,
+        mut form: Vec<(String, String)>,
+        account: Option<&Account>,
+    ) -> Result<(), BackendError> {
+        let mut cookies = account
+            .map(|account| account.cookies.clone())
+            .unwrap_or_default();
+        let csrf = self
+            .client
+            .get_csrf_token_as(page, &mut cookies)
+            .await?
+            .ok_or_else(|| login_required(account.is_some()))?;
+
+        form.insert(0, (CSRF_FIELD.to_string(), csrf));
+        self.submit_as(url, form, &cookies, account.is_some()).await
+    }
+
+    fn parse_paste(&self, dom: &Html) -> Result<Paste, BackendError> {
+        Paste::from_html(dom, &self.base_url)
+            .map_err(|e| BackendError::Parser(format!("Failed to parse paste: {}", e)))
+    }
+}
+
+/// Whether upstream answered by sending the visitor to its login page
+fn redirects_to_login(response: &Response<Vec<u8>>) -> bool {
+    response.status().is_redirection()
+        && response
+            .headers()
+            .get("Location")
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|location| location.contains("/login"))
+}
+
+fn login_required(signed_in: bool) -> BackendError {
+    BackendError::Rejected(if signed_in {
+        "The Pastebin session expired, log in again".to_string()
+    } else {
+        "Pastebin only accepts this from signed-in accounts, log in first".to_string()
+    })
+}
+
+/// The ID of a paste or comment upstream created, taken from the redirect to it
+fn created_id(response: &Response<Vec<u8>>) -> Result<String, BackendError> {
+    let location = response
+        .headers()
+        .get("Location")
+        .ok_or_else(|| {
+            match account::get_form_error(&Html::parse_document(&String::from_utf8_lossy(
+                response.body(),
+            ))) {
+                Some(message) => BackendError::Rejected(message),
+                None => BackendError::Parser("Missing Location header".to_string()),
+            }
+        })?
+        .to_str()
+        .map_err(|e| BackendError::Parser(format!("Invalid Location header encoding: {}", e)))?;
+
+    location
+        .split('/')
+        .next_back()
+        .map(|id| id.to_owned())
+        .ok_or_else(|| BackendError::Parser("Invalid Location header".to_string()))
+}
+
+/// The fields of upstream's paste form, shared by pastes, their edits and comments
+fn paste_form(csrf: String, paste: NewPaste) -> Vec<(String, String)> {
+    vec![
+        (CSRF_FIELD.to_string(), csrf),
+        ("PostForm[text]".to_string(), paste.text),
+        (
+            "PostForm[category_id]".to_string(),
+            paste.category.to_string(),
+        ),
+        ("PostForm[tag]".to_string(), paste.tags),
+        ("PostForm[format]".to_string(), paste.format.to_string()),
+        (
+            "PostForm[expiration]".to_string(),
+            paste.expiration.to_string(),
+        ),
+        ("PostForm[status]".to_string(), paste.exposure.to_string()),
+        (
+            "PostForm[is_password_enabled]".to_string(),
+            (if paste.password.is_empty() { "0" } else { "1" }).to_string(),
+        ),
+        ("PostForm[password]".to_string(), paste.password),
+        (
+            "PostForm[is_burn]".to_string(),
+            (if paste.expiration == "B" { "1" } else { "0" }).to_string(),
+        ),
+        ("PostForm[name]".to_string(), paste.title),
+    ]
+}
+
+#[async_trait]
+impl Backend for PastebinBackend {
+    fn name(&self) -> &'static str {
+        "pastebin"
+    }
+
+    async fn get_paste(&self, id: &str) -> Result<PasteView, BackendError> {
+        let dom = self
+            .client
+            .get_html(&format!("{}/{id}", self.base_url))
+            .await?;
+
+        if paste::is_locked(&dom) {
+            Ok(PasteView::Locked {
+                burn: paste::is_burn(&dom),
+            })
+        } else if paste::is_burn(&dom) {
+            Ok(PasteView::Burn)
+        } else {
+            self.parse_paste(&dom)
+                .map(|paste| PasteView::Paste(Box::new(paste)))
+        }
+    }
+
+    async fn unlock_paste(&self, id: &str, password: &str) -> Result<Paste, BackendError> {
+        let url = format!("{}/{id}", self.base_url);
+        let csrf = paste::get_csrftoken(&self.client.get_html(&url).await?).unwrap_or_default();
+
+        let form = vec![
+            (CSRF_FIELD.to_string(), csrf),
+            (
+                "PostPasswordVerificationForm[password]".to_string(),
+                password.to_string(),
+            ),
+            ("is_burn".to_string(), "1".to_string()),
+        ];
+
+        let dom = self.client.post_html(&url, form).await?;
+        self.parse_paste(&dom)
+    }
+
+    async fn get_raw(&self, id: &str) -> Result<RawPaste, BackendError> {
+        Ok(self
+            .client
+            .get_string(&format!("{}/raw/{id}", self.base_url))
+            .await?
+            .into())
+    }
+
+    async fn create_paste(
+        &self,
+        paste: NewPaste,
+        account: Option<&Account>,
+    ) -> Result<String, BackendError> {
+        let url = format!("{}/", self.base_url);
+        let mut cookies = account.map(|account| account.cookies.clone());
+        let csrf = match cookies.as_mut() {
+            Some(cookies) => self
+                .client
+                .get_csrf_token_as(&url, cookies)
+                .await?
+                .unwrap_or_default(),
+            None => paste::get_csrftoken(&self.client.get_html(&url).await?).unwrap_or_default(),
+        };
+
+        let mut form = paste_form(csrf, paste);
+        form.push((
+            "PostForm[is_guest]".to_string(),
+            (if cookies.is_some() { "0" } else { "1" }).to_string(),
+        ));
+
+        let response = match &cookies {
+            Some(cookies) => self.client.post_response_as(&url, form, cookies).await?,
+            None => self.client.post_response(&url, form).await?,
+        };
+
+        created_id(&response)
+    }
+
+    async fn create_comment(
+        &self,
+        parent: &str,
+        comment: NewPaste,
+        account: Option<&Account>,
+    ) -> Result<String, BackendError> {
+        let url = format!("{}/{parent}", self.base_url);
+        let mut cookies = account.map(|account| account.cookies.clone());
+        let page = match cookies.as_mut() {
+            Some(cookies) => {
+                let response = self.client.get_response_as(&url, cookies).await?;
+                cookies.update(response.headers());
+                String::from_utf8_lossy(response.body()).into_owned()
+            }
+            None => self.client.get_string(&url).await?,
+        };
+
+        let (csrf, action) = {
+            let dom = Html::parse_document(&page);
+            (
+                paste::get_csrftoken(&dom).unwrap_or_default(),
+                paste::get_comment_action(&dom),
+            )
+        };
+        let action = action.ok_or_else(|| {
+            BackendError::Rejected("Pastebin does not accept comments on this paste".to_string())
+        })?;
+
+        let mut form = paste_form(csrf, comment);
+        form.push((
+            "PostForm[is_guest]".to_string(),
+            (if cookies.is_some() { "0" } else { "1" }).to_string(),
+        ));
+
+        let url = format!("{}{action}", self.base_url);
+        let response = match &cookies {
+            Some(cookies) => self.client.post_response_as(&url, form, cookies).await?,
+            None => self.client.post_response(&url, form).await?,
+        };
+
+        created_id(&response)
+    }
+
+    async fn login(&self, username: &str, password: &str) -> Result<Account, BackendError> {
+        let url = format!("{}/login", self.base_url);
+        let mut cookies = Cookies::default();
+        let csrf = self
+            .client
+            .get_csrf_token_as(&url, &mut cookies)
+            .await?
+            .unwrap_or_default();
+
+        let form = vec![
+            (CSRF_FIELD.to_string(), csrf),
+            ("LoginForm[username]".to_string(), username.to_string()),
+            ("LoginForm[password]".to_string(), password.to_string()),
+            ("LoginForm[rememberMe]".to_string(), "1".to_string()),
+        ];
+
+        let response = self.client.post_response_as(&url, form, &cookies).await?;
+        cookies.update(response.headers());
+
+        // A successful login redirects, a failed one shows the form again
+        if !response.status().is_redirection() {
+            let dom = Html::parse_document(&String::from_utf8_lossy(response.body()));
+            return Err(BackendError::Rejected(
+                account::get_form_error(&dom)
+                    .unwrap_or_else(|| "Incorrect username or password".to_string()),
+            ));
+        }
+
+        Ok(Account {
+            username: username.to_string(),
+            cookies,
+        })
+    }
+
+    async fn get_edit_form(&self, id: &str, account: &Account) -> Result<EditForm, BackendError> {
+        let (form, _) = self.get_edit_page(id, &mut account.cookies.clone()).await?;
+        Ok(form)
+    }
+
+    async fn edit_paste(
+        &self,
+        id: &str,
+        paste: NewPaste,
+        account: &Account,
+    ) -> Result<(), BackendError> {
+        let mut cookies = account.cookies.clone();
+        let (_, csrf) = self.get_edit_page(id, &mut cookies).await?;
+
+        let url = format!("{}/edit/{id}", self.base_url);
+        self.submit_as(&url, paste_form(csrf, paste), &cookies, true)
+            .await
+    }
+
+    async fn delete_paste(&self, id: &str, account: &Account) -> Result<(), BackendError> {
+        // The delete link sits on the edit page and posts its CSRF token
+        let mut cookies = account.cookies.clone();
+        let (_, csrf) = self.get_edit_page(id, &mut cookies).await?;
+
+        let url = format!("{}/delete/{id}", self.base_url);
+        self.submit_as(&url, vec![(CSRF_FIELD.to_string(), csrf)], &cookies, true)
+            .await
+    }
+
+    async fn rate_paste(
+        &self,
+        id: &str,
+        rating: Rating,
+        account: Option<&Account>,
+    ) -> Result<(), BackendError> {
+        let rating = match rating {
+            Rating::Like => "like",
+            Rating::Dislike => "dislike",
+        };
+
+        self.act_as(
+            &format!("{}/{id}", self.base_url),
+            &format!("{}/rating/{id}", self.base_url),
+            vec![("rating".to_string(), rating.to_string())],
+            account,
+        )
+        .await
+    }
+
+    async fn report_paste(
+        &self,
+        id: &str,
+        reason: &str,
+        account: Option<&Account>,
+    ) -> Result<(), BackendError> {
+        let url = format!("{}/report/{id}", self.base_url);
+        self.act_as(
+            &url,
+            &url,
+            vec![("ReportForm[reason]".to_string(), reason.to_string())],
+            account,
+        )
+        .await
+    }
+
+    async fn get_user(&self, username: &str) -> Result<User, BackendError> {
+        let dom = self
+            .client
+            .get_html(&format!("{}/u/{username}", self.base_url))
+            .await?;
+        User::from_html(&dom, &self.base_url)
+            .map_err(|e| BackendError::Parser(format!("Failed to parse user page: {}", e)))
+    }
+
+    async fn get_archive(&self, format: Option<&str>) -> Result<ArchivePage, BackendError> {
+        let url = match format {
+            Some(format) => format!("{}/archive/{format}", self.base_url),
+            None => format!("{}/archive", self.base_url),
+        };
+
+        let dom = self.client.get_html(&url).await?;
+        ArchivePage::from_html(&dom, &self.base_url)
+            .map_err(|e| BackendError::Parser(format!("Failed to parse archive page: {}", e)))
+    }
+
+    async fn get_icon(&self, path: &str) -> Result<Vec<u8>, BackendError> {
+        Ok(self
+            .client
+            .get_bytes(&format!("{}/cache/img/{path}.jpg", self.base_url))
+            .await?)
+    }
+}
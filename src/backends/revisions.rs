@@ -0,0 +1,124 @@
+use async_trait::async_trait;
+use std::sync::Arc;
+
+use crate::{
+    parsers::{account::EditForm, archive::ArchivePage, paste::Paste, user::User},
+    revisions::RevisionStore,
+};
+
+use super::{Account, Backend, BackendError, NewPaste, PasteView, Rating, RawPaste};
+
+/// Records each distinct version of the pastes fetched from another backend.
+///
+/// Like snapshots, pastes that had to be unlocked with a password are never
+/// recorded so their history can not be read without one.
+pub struct RevisionBackend {
+    inner: Arc<dyn Backend>,
+    store: Arc<RevisionStore>,
+}
+
+impl RevisionBackend {
+    pub fn new(inner: Arc<dyn Backend>, store: Arc<RevisionStore>) -> Self {
+        Self { inner, store }
+    }
+}
+
+#[async_trait]
+impl Backend for RevisionBackend {
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    async fn get_paste(&self, id: &str) -> Result<PasteView, BackendError> {
+        let view = self.inner.get_paste(id).await?;
+
+        if let PasteView::Paste(paste) = &view
+            && let Err(e) = self.store.record(id, paste)
+        {
+            eprintln!("Failed to record revision of paste '{}': {}", id, e);
+        }
+
+        Ok(view)
+    }
+
+    async fn unlock_paste(&self, id: &str, password: &str) -> Result<Paste, BackendError> {
+        self.inner.unlock_paste(id, password).await
+    }
+
+    async fn get_raw(&self, id: &str) -> Result<RawPaste, BackendError> {
+        self.inner.get_raw(id).await
+    }
+
+    async fn create_paste(
+        &self,
+        paste: NewPaste,
+        account: Option<&Account>,
+    ) -> Result<String, BackendError> {
+        self.inner.create_paste(paste, account).await
+    }
+
+    async fn create_comment(
+        &self,
+        parent: &str,
+        comment: NewPaste,
+        account: Option<&Account>,
+    ) -> Result<String, BackendError> {
+        self.inner.create_comment(parent, comment, account).await
+    }
+
+    async fn login(&self, username: &str, password: &str) -> Result<Account, BackendError> {
+        self.inner.login(username, password).await
+    }
+
+    async fn get_edit_form(&self, id: &str, account: &Account) -> Result<EditForm, BackendError> {
+        self.inner.get_edit_form(id, account).await
+    }
+
+    async fn edit_paste(
+        &self,
+        id: &str,
+        paste: NewPaste,
+        account: &Account,
+    ) -> Result<(), BackendError> {
+        self.inner.edit_paste(id, paste, account).await
+    }
+
+    async fn delete_paste(&self, id: &str, account: &Account) -> Result<(), BackendError> {
+        self.inner.delete_paste(id, account).await?;
+
+        if let Err(e) = self.store.remove(id) {
+            eprintln!("Failed to remove revisions of '{}': {}", id, e);
+        }
+        Ok(())
+    }
+
+    async fn rate_paste(
+        &self,
+        id: &str,
+        rating: Rating,
+        account: Option<&Account>,
+    ) -> Result<(), BackendError> {
+        self.inner.rate_paste(id, rating, account).await
+    }
+
+    async fn report_paste(
+        &self,
+        id: &str,
+        reason: &str,
+        account: Option<&Account>,
+    ) -> Result<(), BackendError> {
+        self.inner.report_paste(id, reason, account).await
+    }
+
+    async fn get_user(&self, username: &str) -> Result<User, BackendError> {
+        self.inner.get_user(username).await
+    }
+
+    async fn get_archive(&self, format: Option<&str>) -> Result<ArchivePage, BackendError> {
+        self.inner.get_archive(format).await
+    }
+
+    async fn get_icon(&self, path: &str) -> Result<Vec<u8>, BackendError> {
+        self.inner.get_icon(path).await
+    }
+}
@@ -0,0 +1,153 @@
+use async_trait::async_trait;
+use std::sync::Arc;
+
+use crate::{
+    parsers::{account::EditForm, archive::ArchivePage, paste::Paste, user::User},
+    snapshots::SnapshotStore,
+};
+
+use super::{Account, Backend, BackendError, NewPaste, PasteView, Rating, RawPaste};
+
+/// Archives every paste fetched from another backend and falls back to the
+/// archive when upstream is down or the paste was deleted.
+///
+/// Pastes that had to be unlocked with a password are never archived, since
+/// the archive would serve them without one.
+pub struct SnapshotBackend {
+    inner: Arc<dyn Backend>,
+    store: Arc<SnapshotStore>,
+}
+
+impl SnapshotBackend {
+    pub fn new(inner: Arc<dyn Backend>, store: Arc<SnapshotStore>) -> Self {
+        Self { inner, store }
+    }
+
+    /// The newest archived copy of `id`, if upstream can not provide one
+    fn fallback(&self, id: &str, err: &BackendError) -> Option<Paste> {
+        if !err.is_not_found() && !err.is_unavailable() {
+            return None;
+        }
+
+        let snapshot = self.store.latest(id)?;
+        let mut paste = snapshot.paste;
+        paste.set_captured_at(snapshot.captured_at);
+        Some(paste)
+    }
+}
+
+#[async_trait]
+impl Backend for SnapshotBackend {
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    async fn get_paste(&self, id: &str) -> Result<PasteView, BackendError> {
+        match self.inner.get_paste(id).await {
+            Ok(PasteView::Paste(paste)) => {
+                if paste.captured_at().is_none()
+                    && let Err(e) = self.store.record(id, &paste)
+                {
+                    eprintln!("Failed to archive paste '{}': {}", id, e);
+                }
+                Ok(PasteView::Paste(paste))
+            }
+            Err(err) => match self.fallback(id, &err) {
+                Some(paste) => Ok(PasteView::Paste(Box::new(paste))),
+                None => Err(err),
+            },
+            view => view,
+        }
+    }
+
+    async fn unlock_paste(&self, id: &str, password: &str) -> Result<Paste, BackendError> {
+        self.inner.unlock_paste(id, password).await
+    }
+
+    async fn get_raw(&self, id: &str) -> Result<RawPaste, BackendError> {
+        match self.inner.get_raw(id).await {
+            Err(err) => match self.fallback(id, &err) {
+                Some(paste) => Ok(RawPaste {
+                    text: paste.content().to_string(),
+                    captured_at: paste.captured_at(),
+                }),
+                None => Err(err),
+            },
+            raw => raw,
+        }
+    }
+
+    async fn create_paste(
+        &self,
+        paste: NewPaste,
+        account: Option<&Account>,
+    ) -> Result<String, BackendError> {
+        self.inner.create_paste(paste, account).await
+    }
+
+    async fn create_comment(
+        &self,
+        parent: &str,
+        comment: NewPaste,
+        account: Option<&Account>,
+    ) -> Result<String, BackendError> {
+        self.inner.create_comment(parent, comment, account).await
+    }
+
+    async fn login(&self, username: &str, password: &str) -> Result<Account, BackendError> {
+        self.inner.login(username, password).await
+    }
+
+    async fn get_edit_form(&self, id: &str, account: &Account) -> Result<EditForm, BackendError> {
+        self.inner.get_edit_form(id, account).await
+    }
+
+    async fn edit_paste(
+        &self,
+        id: &str,
+        paste: NewPaste,
+        account: &Account,
+    ) -> Result<(), BackendError> {
+        self.inner.edit_paste(id, paste, account).await
+    }
+
+    async fn delete_paste(&self, id: &str, account: &Account) -> Result<(), BackendError> {
+        self.inner.delete_paste(id, account).await?;
+
+        // Authors delete pastes to take them down, so the archive follows
+        if let Err(e) = self.store.remove(id) {
+            eprintln!("Failed to remove snapshots of '{}': {}", id, e);
+        }
+        Ok(())
+    }
+
+    async fn rate_paste(
+        &self,
+        id: &str,
+        rating: Rating,
+        account: Option<&Account>,
+    ) -> Result<(), BackendError> {
+        self.inner.rate_paste(id, rating, account).await
+    }
+
+    async fn report_paste(
+        &self,
+        id: &str,
+        reason: &str,
+        account: Option<&Account>,
+    ) -> Result<(), BackendError> {
+        self.inner.report_paste(id, reason, account).await
+    }
+
+    async fn get_user(&self, username: &str) -> Result<User, BackendError> {
+        self.inner.get_user(username).await
+    }
+
+    async fn get_archive(&self, format: Option<&str>) -> Result<ArchivePage, BackendError> {
+        self.inner.get_archive(format).await
+    }
+
+    async fn get_icon(&self, path: &str) -> Result<Vec<u8>, BackendError> {
+        self.inner.get_icon(path).await
+    }
+}
@@ -0,0 +1,219 @@
+use async_trait::async_trait;
+use std::{future::Future, pin::Pin, sync::Arc};
+use tokio::task::JoinSet;
+
+use crate::parsers::{
+    account::EditForm,
+    archive::ArchivePage,
+    paste::{Comment, Paste},
+    user::User,
+};
+
+use super::{Account, Backend, BackendError, NewPaste, PasteView, Rating, RawPaste};
+
+/// Most comment pages fetched to assemble the threads of one paste
+const MAX_FETCHES: usize = 32;
+
+/// Assembles comment threads from another backend.
+///
+/// Pastebin lists only the direct comments of a paste, and each comment is a
+/// paste of its own listing its replies. Comments with replies have their page
+/// fetched, up to `depth` levels below the paste and `MAX_FETCHES` pages in
+/// total. Pages come from the inner backend, so they are cached like any paste.
+pub struct ThreadedBackend {
+    inner: Arc<dyn Backend>,
+    depth: usize,
+}
+
+impl ThreadedBackend {
+    pub fn new(inner: Arc<dyn Backend>, depth: usize) -> Self {
+        Self { inner, depth }
+    }
+
+    /// Fills in the replies of `comments`, a level at a time
+    fn load_replies<'a>(
+        &'a self,
+        comments: &'a mut [Comment],
+        depth: usize,
+        budget: &'a mut usize,
+    ) -> Pin<Box<dyn Future<Output = ()> + Send + 'a>> {
+        Box::pin(async move {
+            if depth == 0 {
+                return;
+            }
+
+            let mut pages = JoinSet::new();
+            for (i, comment) in comments.iter().enumerate() {
+                let Some(id) = comment.container.id.clone() else {
+                    continue;
+                };
+                if comment.num_comments == 0 || *budget == 0 {
+                    continue;
+                }
+
+                *budget -= 1;
+                let inner = self.inner.clone();
+                pages.spawn(async move { (i, inner.get_paste(&id).await) });
+            }
+
+            while let Some(page) = pages.join_next().await {
+                match page {
+                    Ok((i, Ok(PasteView::Paste(paste)))) => comments[i].replies = paste.comments,
+                    Ok((_, Ok(_))) => {}
+                    // A missing thread only hides replies, the paste is still shown
+                    Ok((_, Err(e))) => eprintln!("Failed to load comment replies: {}", e),
+                    Err(e) => eprintln!("Comment replies task failed: {}", e),
+                }
+            }
+
+            for comment in comments.iter_mut() {
+                self.load_replies(&mut comment.replies, depth - 1, budget)
+                    .await;
+            }
+        })
+    }
+
+    async fn with_threads(&self, mut paste: Paste) -> Paste {
+        let mut budget = MAX_FETCHES;
+        self.load_replies(&mut paste.comments, self.depth, &mut budget)
+            .await;
+        paste
+    }
+}
+
+#[async_trait]
+impl Backend for ThreadedBackend {
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    async fn get_paste(&self, id: &str) -> Result<PasteView, BackendError> {
+        match self.inner.get_paste(id).await? {
+            PasteView::Paste(paste) => {
+                Ok(PasteView::Paste(Box::new(self.with_threads(*paste).await)))
+            }
+            view => Ok(view),
+        }
+    }
+
+    async fn unlock_paste(&self, id: &str, password: &str) -> Result<Paste, BackendError> {
+        let paste = self.inner.unlock_paste(id, password).await?;
+        Ok(self.with_threads(paste).await)
+    }
+
+    async fn get_raw(&self, id: &str) -> Result<RawPaste, BackendError> {
+        self.inner.get_raw(id).await
+    }
+
+    async fn create_paste(
+        &self,
+        paste: NewPaste,
+        account: Option<&Account>,
+    ) -> Result<String, BackendError> {
+        self.inner.create_paste(paste, account).await
+    }
+
+    async fn create_comment(
+        &self,
+        parent: &str,
+        comment: NewPaste,
+        account: Option<&Account>,
+    ) -> Result<String, BackendError> {
+        self.inner.create_comment(parent, comment, account).await
+    }
+
+    async fn login(&self, username: &str, password: &str) -> Result<Account, BackendError> {
+        self.inner.login(username, password).await
+    }
+
+    async fn get_edit_form(&self, id: &str, account: &Account) -> Result<EditForm, BackendError> {
+        self.inner.get_edit_form(id, account).await
+    }
+
+    async fn edit_paste(
+        &self,
+        id: &str,
+        paste: NewPaste,
+        account: &Account,
+    ) -> Result<(), BackendError> {
+        self.inner.edit_paste(id, paste, account).await
+    }
+
+    async fn delete_paste(&self, id: &str, account: &Account) -> Result<(), BackendError> {
+        self.inner.delete_paste(id, account).await
+    }
+
+    async fn rate_paste(
+        &self,
+        id: &str,
+        rating: Rating,
+        account: Option<&Account>,
+    ) -> Result<(), BackendError> {
+        self.inner.rate_paste(id, rating, account).await
+    }
+
+    async fn report_paste(
+        &self,
+        id: &str,
+        reason: &str,
+        account: Option<&Account>,
+    ) -> Result<(), BackendError> {
+        self.inner.report_paste(id, reason, account).await
+    }
+
+    async fn get_user(&self, username: &str) -> Result<User, BackendError> {
+        self.inner.get_user(username).await
+    }
+
+    async fn get_archive(&self, format: Option<&str>) -> Result<ArchivePage, BackendError> {
+        self.inner.get_archive(format).await
+    }
+
+    async fn get_icon(&self, path: &str) -> Result<Vec<u8>, BackendError> {
+        self.inner.get_icon(path).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::mock::MockBackend;
+    use super::*;
+
+    fn thread_depth(comments: &[Comment]) -> usize {
+        comments
+            .iter()
+            .map(|comment| 1 + thread_depth(&comment.replies))
+            .max()
+            .unwrap_or(0)
+    }
+
+    async fn paste(backend: &ThreadedBackend) -> Paste {
+        match backend.get_paste("abc").await {
+            Ok(PasteView::Paste(paste)) => *paste,
+            _ => panic!("Expected a paste"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_replies_are_nested_up_to_the_depth() {
+        let mock = Arc::new(MockBackend::default());
+        let threaded = ThreadedBackend::new(mock.clone(), 2);
+
+        let paste = paste(&threaded).await;
+        assert_eq!(thread_depth(&paste.comments), 3);
+        assert_eq!(
+            paste.comments[0].replies[0].container.id.as_deref(),
+            Some("abc-r-r")
+        );
+        assert_eq!(mock.calls(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_no_depth_keeps_comments_flat() {
+        let mock = Arc::new(MockBackend::default());
+        let threaded = ThreadedBackend::new(mock.clone(), 0);
+
+        assert_eq!(thread_depth(&paste(&threaded).await.comments), 1);
+        assert_eq!(mock.calls(), 1);
+    }
+}
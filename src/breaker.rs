@@ -0,0 +1,125 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Stops sending requests upstream after repeated failures.
+///
+/// Once `threshold` consecutive failures are recorded the breaker opens and
+/// every check fails fast until `cooldown` has passed. The next request is
+/// then let through as a probe while every other check keeps failing: a
+/// success closes the breaker again, a failure reopens it for another
+/// cooldown. A probe that never reports back frees its slot after a cooldown.
+pub struct CircuitBreaker {
+    threshold: u32,
+    cooldown: Duration,
+    state: Mutex<BreakerState>,
+}
+
+#[derive(Default)]
+struct BreakerState {
+    failures: u32,
+    open_until: Option<Instant>,
+}
+
+impl CircuitBreaker {
+    pub fn new(threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            threshold: threshold.max(1),
+            cooldown,
+            state: Mutex::new(BreakerState::default()),
+        }
+    }
+
+    /// Returns how long the breaker stays open, or `Ok` if a request may be sent
+    pub fn check(&self) -> Result<(), Duration> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(until) = state.open_until else {
+            return Ok(());
+        };
+
+        let now = Instant::now();
+        match until.checked_duration_since(now) {
+            Some(remaining) if !remaining.is_zero() => Err(remaining),
+            // Half-open, this caller takes the probe slot and the rest wait on it
+            _ => {
+                state.open_until = Some(now + self.cooldown);
+                Ok(())
+            }
+        }
+    }
+
+    pub fn record_success(&self) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        *state = BreakerState::default();
+    }
+
+    pub fn record_failure(&self) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.failures = state.failures.saturating_add(1);
+
+        if state.failures >= self.threshold {
+            state.open_until = Some(Instant::now() + self.cooldown);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_opens_after_threshold() {
+        let breaker = CircuitBreaker::new(2, Duration::from_secs(30));
+
+        breaker.record_failure();
+        assert!(breaker.check().is_ok());
+
+        breaker.record_failure();
+        let remaining = breaker.check().expect_err("Breaker should be open");
+        assert!(remaining <= Duration::from_secs(30));
+    }
+
+    #[test]
+    fn test_success_resets_failures() {
+        let breaker = CircuitBreaker::new(2, Duration::from_secs(30));
+
+        breaker.record_failure();
+        breaker.record_success();
+        breaker.record_failure();
+        assert!(breaker.check().is_ok());
+    }
+
+    #[test]
+    fn test_half_open_after_cooldown() {
+        let breaker = CircuitBreaker::new(1, Duration::ZERO);
+
+        breaker.record_failure();
+        assert!(breaker.check().is_ok());
+    }
+
+    #[test]
+    fn test_one_probe_at_a_time() {
+        let breaker = CircuitBreaker::new(1, Duration::from_millis(50));
+
+        breaker.record_failure();
+        std::thread::sleep(Duration::from_millis(60));
+
+        let allowed = std::thread::scope(|scope| {
+            let checks = [
+                scope.spawn(|| breaker.check()),
+                scope.spawn(|| breaker.check()),
+            ];
+            checks
+                .map(|check| check.join().expect("Check thread"))
+                .iter()
+                .filter(|result| result.is_ok())
+                .count()
+        });
+        assert_eq!(allowed, 1);
+
+        breaker.record_success();
+        assert!(breaker.check().is_ok());
+        assert!(breaker.check().is_ok());
+    }
+}
@@ -0,0 +1,495 @@
+//! Pastebin's paste formats and categories, the one place their numeric IDs,
+//! slugs, display names, file extensions and MIME types are kept.
+
+use serde::Serialize;
+
+/// A format (syntax) pastebin can highlight pastes in
+#[derive(Debug, Serialize)]
+pub struct Format {
+    /// Numeric ID, as posted by the paste form
+    pub id: u16,
+    /// Name used by the developer API and `/archive/{slug}` links
+    pub slug: &'static str,
+    pub name: &'static str,
+    /// File extensions, and whole names for extensionless files, the first one
+    /// names downloads
+    pub extensions: &'static [&'static str],
+    pub mime: &'static str,
+    /// Listed again at the top of the paste form
+    pub popular: bool,
+}
+
+impl Format {
+    const fn new(id: u16, slug: &'static str, name: &'static str) -> Self {
+        Self {
+            id,
+            slug,
+            name,
+            extensions: &[],
+            mime: "text/plain",
+            popular: false,
+        }
+    }
+
+    const fn files(self, extensions: &'static [&'static str], mime: &'static str) -> Self {
+        Self {
+            extensions,
+            mime,
+            ..self
+        }
+    }
+
+    const fn popular(self) -> Self {
+        Self {
+            popular: true,
+            ..self
+        }
+    }
+
+    /// Extension of downloaded pastes, `txt` for formats without files of their own
+    pub fn extension(&self) -> &'static str {
+        self.extensions.first().copied().unwrap_or("txt")
+    }
+}
+
+/// A category pastes can be filed under
+#[derive(Debug, Serialize)]
+pub struct Category {
+    pub id: u8,
+    pub slug: &'static str,
+    pub name: &'static str,
+}
+
+impl Category {
+    const fn new(id: u8, slug: &'static str, name: &'static str) -> Self {
+        Self { id, slug, name }
+    }
+}
+
+/// Plain text, the format of pastes that were given none
+pub const TEXT: u16 = 1;
+
+/// Sorted by ID, starting with plain text
+pub const FORMATS: &[Format] = &[
+    Format::new(1, "text", "None").files(&["txt"], "text/plain"),
+    Format::new(2, "actionscript", "ActionScript"),
+    Format::new(3, "ada", "Ada"),
+    Format::new(4, "apache", "Apache Log"),
+    Format::new(5, "applescript", "AppleScript"),
+    Format::new(6, "asm", "ASM (NASM)").files(&["asm"], "text/x-asm"),
+    Format::new(7, "asp", "ASP"),
+    Format::new(8, "bash", "Bash")
+        .files(&["sh", "bash"], "application/x-sh")
+        .popular(),
+    Format::new(9, "c", "C")
+        .files(&["c", "h"], "text/x-c")
+        .popular(),
+    Format::new(10, "c_mac", "C for Macs"),
+    Format::new(11, "caddcl", "CAD DCL"),
+    Format::new(12, "cadlisp", "CAD Lisp"),
+    Format::new(13, "cpp", "C++")
+        .files(&["cpp", "cc", "cxx", "hpp"], "text/x-c++")
+        .popular(),
+    Format::new(14, "csharp", "C#")
+        .files(&["cs"], "text/x-csharp")
+        .popular(),
+    Format::new(15, "cfm", "ColdFusion"),
+    Format::new(16, "css", "CSS")
+        .files(&["css"], "text/css")
+        .popular(),
+    Format::new(17, "d", "D").files(&["d"], "text/x-d"),
+    Format::new(18, "delphi", "Delphi"),
+    Format::new(19, "diff", "Diff").files(&["diff", "patch"], "text/x-diff"),
+    Format::new(20, "dos", "Batch").files(&["bat", "cmd"], "application/x-bat"),
+    Format::new(21, "eiffel", "Eiffel"),
+    Format::new(22, "fortran", "Fortran").files(&["f90"], "text/x-fortran"),
+    Format::new(23, "freebasic", "FreeBasic"),
+    Format::new(24, "gml", "Game Maker"),
+    Format::new(25, "html4strict", "HTML").popular(),
+    Format::new(26, "ini", "INI file").files(&["ini"], "text/plain"),
+    Format::new(27, "java", "Java")
+        .files(&["java"], "text/x-java")
+        .popular(),
+    Format::new(28, "javascript", "JavaScript")
+        .files(&["js"], "text/javascript")
+        .popular(),
+    Format::new(29, "lisp", "Lisp").files(&["lisp"], "text/x-lisp"),
+    Format::new(30, "lua", "Lua")
+        .files(&["lua"], "text/x-lua")
+        .popular(),
+    Format::new(32, "mpasm", "MPASM"),
+    Format::new(33, "mysql", "MySQL"),
+    Format::new(34, "nsis", "NullSoft Installer"),
+    Format::new(35, "objc", "Objective C")
+        .files(&["m"], "text/x-objcsrc")
+        .popular(),
+    Format::new(36, "ocaml", "OCaml").files(&["ml"], "text/x-ocaml"),
+    Format::new(37, "oobas", "Openoffice BASIC"),
+    Format::new(38, "oracle8", "Oracle 8"),
+    Format::new(39, "pascal", "Pascal").files(&["pas"], "text/x-pascal"),
+    Format::new(40, "perl", "Perl")
+        .files(&["pl", "pm"], "text/x-perl")
+        .popular(),
+    Format::new(41, "php", "PHP")
+        .files(&["php"], "application/x-httpd-php")
+        .popular(),
+    Format::new(42, "python", "Python")
+        .files(&["py"], "text/x-python")
+        .popular(),
+    Format::new(43, "qbasic", "QBasic"),
+    Format::new(44, "robots", "Robots"),
+    Format::new(45, "ruby", "Ruby")
+        .files(&["rb"], "text/x-ruby")
+        .popular(),
+    Format::new(46, "scheme", "Scheme").files(&["scm"], "text/x-scheme"),
+    Format::new(47, "smarty", "Smarty"),
+    Format::new(48, "sql", "SQL").files(&["sql"], "application/sql"),
+    Format::new(50, "vb", "VisualBasic").files(&["bas"], "text/x-vb"),
+    Format::new(51, "vbnet", "VB.NET").files(&["vb"], "text/x-vb"),
+    Format::new(52, "visualfoxpro", "VisualFoxPro"),
+    Format::new(53, "xml", "XML").files(&["xml"], "application/xml"),
+    Format::new(54, "autoit", "AutoIt"),
+    Format::new(55, "blitzbasic", "Blitz Basic"),
+    Format::new(56, "bnf", "BNF"),
+    Format::new(57, "erlang", "Erlang").files(&["erl"], "text/x-erlang"),
+    Format::new(58, "genero", "Genero"),
+    Format::new(59, "groovy", "Groovy").files(&["groovy"], "text/x-groovy"),
+    Format::new(60, "haskell", "Haskell").files(&["hs"], "text/x-haskell"),
+    Format::new(61, "inno", "Inno Script"),
+    Format::new(62, "latex", "Latex").files(&["tex", "latex"], "application/x-latex"),
+    Format::new(63, "lsl2", "Linden Scripting"),
+    Format::new(64, "matlab", "MatLab"),
+    Format::new(65, "m68k", "M68000 Assembler"),
+    Format::new(66, "mirc", "mIRC"),
+    Format::new(67, "rails", "Rails"),
+    Format::new(68, "plsql", "PL/SQL"),
+    Format::new(69, "smalltalk", "Smalltalk"),
+    Format::new(70, "tcl", "TCL").files(&["tcl"], "text/x-tcl"),
+    Format::new(72, "z80", "Z80 Assembler"),
+    Format::new(73, "abap", "ABAP"),
+    Format::new(74, "actionscript3", "ActionScript 3").files(&["as"], "text/plain"),
+    Format::new(75, "apt_sources", "APT Sources"),
+    Format::new(76, "avisynth", "Avisynth"),
+    Format::new(77, "basic4gl", "Basic4GL"),
+    Format::new(78, "bibtex", "BibTeX"),
+    Format::new(79, "bf", "BrainFuck"),
+    Format::new(80, "boo", "BOO"),
+    Format::new(81, "cfdg", "CFDG"),
+    Format::new(82, "cil", "C Intermediate Language"),
+    Format::new(83, "cmake", "CMake").files(&["cmake"], "text/x-cmake"),
+    Format::new(84, "cobol", "COBOL"),
+    Format::new(85, "dcs", "DCS"),
+    Format::new(86, "div", "DIV"),
+    Format::new(87, "dot", "DOT"),
+    Format::new(88, "email", "Email"),
+    Format::new(89, "fo", "FO Language"),
+    Format::new(90, "gettext", "GetText"),
+    Format::new(91, "glsl", "OpenGL Shading"),
+    Format::new(92, "gnuplot", "Ruby Gnuplot"),
+    Format::new(93, "hq9plus", "HQ9 Plus"),
+    Format::new(94, "idl", "IDL"),
+    Format::new(95, "intercal", "INTERCAL"),
+    Format::new(96, "io", "IO"),
+    Format::new(97, "java5", "Java 5"),
+    Format::new(98, "kixtart", "KiXtart"),
+    Format::new(99, "klonec", "Clone C"),
+    Format::new(100, "klonecpp", "Clone C++"),
+    Format::new(101, "locobasic", "Loco Basic"),
+    Format::new(102, "lolcode", "LOL Code"),
+    Format::new(103, "lotusformulas", "Lotus Formulas"),
+    Format::new(104, "lotusscript", "Lotus Script"),
+    Format::new(105, "lscript", "LScript"),
+    Format::new(106, "make", "Make").files(&["mk", "makefile"], "text/x-makefile"),
+    Format::new(107, "modula3", "Modula 3"),
+    Format::new(108, "mxml", "MXML"),
+    Format::new(109, "oberon2", "Oberon 2"),
+    Format::new(110, "ocaml-brief", "OCaml Brief"),
+    Format::new(111, "oracle11", "Oracle 11"),
+    Format::new(112, "per", "Per"),
+    Format::new(113, "php-brief", "PHP Brief"),
+    Format::new(114, "pic16", "Pic 16"),
+    Format::new(115, "pixelbender", "Pixel Bender"),
+    Format::new(116, "povray", "POV-Ray"),
+    Format::new(117, "powershell", "PowerShell").files(&["ps1"], "text/plain"),
+    Format::new(118, "progress", "Progress"),
+    Format::new(119, "prolog", "Prolog"),
+    Format::new(120, "properties", "Properties").files(&["properties"], "text/x-java-properties"),
+    Format::new(121, "providex", "ProvideX"),
+    Format::new(122, "rebol", "REBOL"),
+    Format::new(123, "reg", "REG").files(&["reg"], "text/plain"),
+    Format::new(124, "sas", "SAS"),
+    Format::new(125, "scala", "Scala").files(&["scala"], "text/x-scala"),
+    Format::new(126, "scilab", "Scilab"),
+    Format::new(127, "sdlbasic", "SdlBasic"),
+    Format::new(128, "teraterm", "Tera Term"),
+    Format::new(129, "thinbasic", "thinBasic"),
+    Format::new(130, "tsql", "T-SQL"),
+    Format::new(131, "typoscript", "TypoScript"),
+    Format::new(132, "verilog", "VeriLog"),
+    Format::new(133, "vhdl", "VHDL").files(&["vhd"], "text/x-vhdl"),
+    Format::new(134, "vim", "VIM").files(&["vim"], "text/plain"),
+    Format::new(135, "visualprolog", "Visual Pro Log"),
+    Format::new(136, "whitespace", "WhiteSpace"),
+    Format::new(137, "whois", "WHOIS"),
+    Format::new(138, "winbatch", "Winbatch"),
+    Format::new(139, "xorg_conf", "Xorg Config"),
+    Format::new(140, "xpp", "XPP"),
+    Format::new(141, "pawn", "Pawn"),
+    Format::new(142, "4cs", "4CS"),
+    Format::new(143, "6502acme", "6502 ACME Cross Assembler"),
+    Format::new(144, "6502kickass", "6502 Kick Assembler"),
+    Format::new(145, "6502tasm", "6502 TASM/64TASS"),
+    Format::new(146, "68000devpac", "Motorola 68000 HiSoft Dev"),
+    Format::new(147, "algol68", "ALGOL 68"),
+    Format::new(148, "autoconf", "autoconf"),
+    Format::new(149, "autohotkey", "Autohotkey"),
+    Format::new(150, "awk", "Awk").files(&["awk"], "text/x-awk"),
+    Format::new(151, "cuesheet", "Cuesheet"),
+    Format::new(152, "chaiscript", "ChaiScript"),
+    Format::new(153, "clojure", "Clojure").files(&["clj"], "text/x-clojure"),
+    Format::new(154, "cpp-qt", "C++ (with Qt extensions)"),
+    Format::new(155, "e", "E"),
+    Format::new(156, "ecmascript", "ECMAScript"),
+    Format::new(157, "f1", "Formula One"),
+    Format::new(158, "fsharp", "F#").files(&["fs"], "text/x-fsharp"),
+    Format::new(159, "gambas", "GAMBAS"),
+    Format::new(160, "gdb", "GDB"),
+    Format::new(161, "genie", "Genie"),
+    Format::new(162, "go", "Go").files(&["go"], "text/x-go"),
+    Format::new(163, "gwbasic", "GwBasic"),
+    Format::new(164, "hicest", "HicEst"),
+    Format::new(165, "icon", "Icon"),
+    Format::new(166, "j", "J"),
+    Format::new(167, "jquery", "jQuery"),
+    Format::new(168, "lb", "Liberty BASIC"),
+    Format::new(169, "logtalk", "Logtalk"),
+    Format::new(170, "magiksf", "MagikSF"),
+    Format::new(171, "mapbasic", "MapBasic"),
+    Format::new(172, "mmix", "MIX Assembler"),
+    Format::new(173, "modula2", "Modula 2"),
+    Format::new(174, "newlisp", "newLISP"),
+    Format::new(175, "objeck", "Objeck Programming Langua"),
+    Format::new(177, "oxygene", "Delphi Prism (Oxygene)"),
+    Format::new(178, "oz", "Oz"),
+    Format::new(179, "pcre", "PCRE"),
+    Format::new(180, "perl6", "Perl 6"),
+    Format::new(181, "pf", "OpenBSD PACKET FILTER"),
+    Format::new(182, "pike", "Pike"),
+    Format::new(183, "postgresql", "PostgreSQL"),
+    Format::new(184, "powerbuilder", "PowerBuilder"),
+    Format::new(185, "purebasic", "PureBasic"),
+    Format::new(186, "q", "q/kdb+"),
+    Format::new(187, "rpmspec", "RPM Spec"),
+    Format::new(188, "rsplus", "R").files(&["r"], "text/x-r"),
+    Format::new(189, "systemverilog", "SystemVerilog"),
+    Format::new(191, "unicon", "Unicon"),
+    Format::new(192, "vala", "Vala"),
+    Format::new(193, "xbasic", "XBasic"),
+    Format::new(194, "zxbasic", "ZXBasic"),
+    Format::new(195, "uscript", "UnrealScript"),
+    Format::new(196, "html5", "HTML 5").files(&["html", "htm"], "text/html"),
+    Format::new(197, "proftpd", "ProFTPd"),
+    Format::new(198, "bascomavr", "BASCOM AVR"),
+    Format::new(199, "c_loadrunner", "C: Loadrunner"),
+    Format::new(200, "coffeescript", "CoffeeScript").files(&["coffee"], "text/coffeescript"),
+    Format::new(201, "epc", "EPC"),
+    Format::new(202, "falcon", "Falcon"),
+    Format::new(203, "llvm", "LLVM"),
+    Format::new(204, "pycon", "PyCon"),
+    Format::new(205, "yaml", "YAML").files(&["yaml", "yml"], "application/yaml"),
+    Format::new(206, "freeswitch", "FreeSWITCH"),
+    Format::new(217, "arm", "ARM"),
+    Format::new(218, "asymptote", "Asymptote"),
+    Format::new(219, "dcl", "DCL"),
+    Format::new(220, "dcpu16", "DCPU-16"),
+    Format::new(221, "haxe", "Haxe"),
+    Format::new(222, "ldif", "LDIF"),
+    Format::new(223, "nagios", "Nagios"),
+    Format::new(224, "octave", "Octave"),
+    Format::new(225, "parasail", "ParaSail"),
+    Format::new(226, "parigp", "PARI/GP"),
+    Format::new(227, "pys60", "Python for S60"),
+    Format::new(228, "rexx", "Rexx"),
+    Format::new(229, "spark", "SPARK"),
+    Format::new(230, "sparql", "SPARQL"),
+    Format::new(231, "stonescript", "StoneScript"),
+    Format::new(232, "upc", "UPC"),
+    Format::new(233, "urbi", "Urbi"),
+    Format::new(234, "vedit", "Vedit"),
+    Format::new(236, "aimms", "AIMMS"),
+    Format::new(237, "chapel", "Chapel"),
+    Format::new(238, "dart", "Dart").files(&["dart"], "application/dart"),
+    Format::new(239, "easytrieve", "Easytrieve"),
+    Format::new(240, "ispfpanel", "ISPF Panel Definition"),
+    Format::new(241, "jcl", "JCL"),
+    Format::new(242, "nginx", "Nginx"),
+    Format::new(243, "nim", "Nim").files(&["nim"], "text/x-nim"),
+    Format::new(244, "postscript", "PostScript"),
+    Format::new(245, "qml", "QML").files(&["qml"], "text/x-qml"),
+    Format::new(246, "racket", "Racket").files(&["rkt"], "text/x-racket"),
+    Format::new(247, "rbs", "RBScript"),
+    Format::new(248, "rust", "Rust").files(&["rs"], "text/x-rust"),
+    Format::new(249, "scl", "SCL"),
+    Format::new(250, "standardml", "StandardML"),
+    Format::new(251, "vbscript", "VBScript").files(&["vbs"], "text/vbscript"),
+    Format::new(252, "c_winapi", "C (WinAPI)"),
+    Format::new(253, "cpp-winapi", "C++ (WinAPI)"),
+    Format::new(254, "netrexx", "NetRexx"),
+    Format::new(255, "json", "JSON")
+        .files(&["json"], "application/json")
+        .popular(),
+    Format::new(256, "swift", "Swift")
+        .files(&["swift"], "text/x-swift")
+        .popular(),
+    Format::new(257, "sclang", "SuperCollider"),
+    Format::new(258, "julia", "Julia").files(&["jl"], "text/x-julia"),
+    Format::new(259, "b3d", "Blitz3D"),
+    Format::new(260, "bmx", "BlitzMax"),
+    Format::new(261, "sqf", "SQF"),
+    Format::new(262, "puppet", "Puppet"),
+    Format::new(263, "filemaker", "Filemaker"),
+    Format::new(264, "euphoria", "Euphoria"),
+    Format::new(265, "pli", "PL/I"),
+    Format::new(266, "oorexx", "Open Object Rexx"),
+    Format::new(267, "markdown", "Markdown")
+        .files(&["md"], "text/markdown")
+        .popular(),
+    Format::new(268, "kotlin", "Kotlin").files(&["kt", "kts"], "text/x-kotlin"),
+    Format::new(269, "ceylon", "Ceylon"),
+    Format::new(270, "arduino", "Arduino"),
+    Format::new(271, "yara", "YARA"),
+    Format::new(272, "typescript", "TypeScript").files(&["ts"], "application/typescript"),
+    Format::new(276, "mercury", "Mercury"),
+    Format::new(277, "metapost", "MetaPost"),
+    Format::new(278, "mk-61", "MK-61/52"),
+    Format::new(279, "phix", "Phix"),
+    Format::new(280, "roff", "Roff Manpage"),
+    Format::new(282, "sshconfig", "SSH Config"),
+    Format::new(283, "texgraph", "TeXgraph"),
+    Format::new(285, "xojo", "Xojo"),
+    Format::new(286, "ksp", "KSP (Kontakt Script)"),
+    Format::new(287, "gdscript", "GDScript"),
+    Format::new(288, "godot-glsl", "Godot GLSL"),
+];
+
+/// In the order the paste form lists them
+pub const CATEGORIES: &[Category] = &[
+    Category::new(0, "none", "None"),
+    Category::new(5, "cryptocurrency", "Cryptocurrency"),
+    Category::new(7, "fixit", "Fixit"),
+    Category::new(8, "food", "Food"),
+    Category::new(9, "gaming", "Gaming"),
+    Category::new(10, "haiku", "Haiku"),
+    Category::new(11, "help", "Help"),
+    Category::new(12, "history", "History"),
+    Category::new(13, "housing", "Housing"),
+    Category::new(14, "jokes", "Jokes"),
+    Category::new(15, "legal", "Legal"),
+    Category::new(16, "money", "Money"),
+    Category::new(6, "movies", "Movies"),
+    Category::new(17, "music", "Music"),
+    Category::new(18, "pets", "Pets"),
+    Category::new(19, "photo", "Photo"),
+    Category::new(20, "science", "Science"),
+    Category::new(21, "software", "Software"),
+    Category::new(27, "source_code", "Source Code"),
+    Category::new(22, "spirit", "Spirit"),
+    Category::new(23, "sports", "Sports"),
+    Category::new(24, "travel", "Travel"),
+    Category::new(25, "tv", "TV"),
+    Category::new(26, "writing", "Writing"),
+];
+
+pub fn format(id: u16) -> Option<&'static Format> {
+    FORMATS.iter().find(|format| format.id == id)
+}
+
+/// Plain text, what pastes in unknown formats are treated as
+pub const PLAIN_TEXT: &Format = &FORMATS[0];
+
+pub fn format_by_slug(slug: &str) -> Option<&'static Format> {
+    FORMATS
+        .iter()
+        .find(|format| format.slug.eq_ignore_ascii_case(slug))
+}
+
+/// The format a file is likely written in, from its name
+pub fn format_by_filename(filename: &str) -> Option<&'static Format> {
+    let name = filename.rsplit(['/', '\\']).next()?.to_ascii_lowercase();
+    let extension = name.rsplit_once('.').map_or(name.as_str(), |(_, ext)| ext);
+
+    FORMATS
+        .iter()
+        .find(|format| format.extensions.contains(&extension))
+}
+
+/// Every format sorted by display name, as the paste form and archive list them
+pub fn formats_by_name() -> Vec<&'static Format> {
+    let mut formats = FORMATS.iter().collect::<Vec<_>>();
+    formats.sort_by_key(|format| format.name);
+    formats
+}
+
+pub fn category(id: u8) -> Option<&'static Category> {
+    CATEGORIES.iter().find(|category| category.id == id)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    #[test]
+    fn test_ids_slugs_and_extensions_are_unique() {
+        let ids = FORMATS.iter().map(|f| f.id).collect::<HashSet<_>>();
+        let slugs = FORMATS.iter().map(|f| f.slug).collect::<HashSet<_>>();
+        let extensions = FORMATS
+            .iter()
+            .flat_map(|f| f.extensions)
+            .collect::<Vec<_>>();
+
+        assert_eq!(ids.len(), FORMATS.len());
+        assert_eq!(slugs.len(), FORMATS.len());
+        assert_eq!(
+            extensions.iter().collect::<HashSet<_>>().len(),
+            extensions.len()
+        );
+
+        let ids = CATEGORIES.iter().map(|c| c.id).collect::<HashSet<_>>();
+        assert_eq!(ids.len(), CATEGORIES.len());
+    }
+
+    #[test]
+    fn test_lookups() {
+        assert_eq!(format_by_slug("Rust").map(|f| f.id), Some(248));
+        assert_eq!(format_by_slug("unknown").map(|f| f.id), None);
+        assert_eq!(format(42).map(|f| f.name), Some("Python"));
+        assert_eq!(PLAIN_TEXT.id, TEXT);
+        assert_eq!(PLAIN_TEXT.extension(), "txt");
+        assert_eq!(format(2).map(Format::extension), Some("txt"));
+        assert_eq!(format(13).map(Format::extension), Some("cpp"));
+        assert_eq!(format(255).map(|f| f.mime), Some("application/json"));
+
+        assert_eq!(category(27).map(|c| c.name), Some("Source Code"));
+        assert!(category(1).is_none());
+    }
+
+    #[test]
+    fn test_format_by_filename() {
+        let id = |name| format_by_filename(name).map(|f| f.id);
+
+        assert_eq!(id("main.rs"), Some(248));
+        assert_eq!(id("dir/SCRIPT.PY"), Some(42));
+        assert_eq!(id("Makefile"), Some(106));
+        assert_eq!(id("archive.tar.unknown"), None);
+    }
+
+    #[test]
+    fn test_formats_by_name() {
+        let formats = formats_by_name();
+
+        assert_eq!(formats.len(), FORMATS.len());
+        assert!(formats.windows(2).all(|w| w[0].name <= w[1].name));
+    }
+}
@@ -1,28 +1,165 @@
-use axum::http::Response;
+use axum::http::{HeaderMap, Response, header};
 use scraper::Html;
-use std::fmt;
-use ureq::{Agent, Body};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    hash::{BuildHasher, RandomState},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::{sync::Semaphore, task, time};
+use ureq::Agent;
 
+use crate::{breaker::CircuitBreaker, parsers::paste};
+
+/// Form field upstream reads the CSRF token of the submitting page from
+pub const CSRF_FIELD: &str = "_csrf-frontend";
+
+pub struct ClientConfig {
+    /// Number of upstream requests allowed in flight at once
+    pub max_concurrent: usize,
+    /// Time allowed for a whole request, from connecting to reading the body
+    pub timeout: Duration,
+    /// Extra attempts made for a failed GET request
+    pub retries: u32,
+    /// Base delay between retries, doubled on each attempt
+    pub backoff: Duration,
+    /// Consecutive failures before the circuit breaker opens
+    pub breaker_threshold: u32,
+    /// How long the circuit breaker stays open
+    pub breaker_cooldown: Duration,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        Self {
+            max_concurrent: 64,
+            timeout: Duration::from_secs(10),
+            retries: 2,
+            backoff: Duration::from_millis(250),
+            breaker_threshold: 5,
+            breaker_cooldown: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Async wrapper around a blocking `ureq` agent.
+///
+/// Requests run on Tokio's blocking pool so they never stall runtime workers,
+/// and a semaphore bounds how many of them can occupy that pool at once. The
+/// agent keeps enough idle connections per host to reuse one for every permit.
 #[derive(Clone)]
 pub struct Client {
     agent: Agent,
+    permits: Arc<Semaphore>,
+    breaker: Arc<CircuitBreaker>,
+    retries: u32,
+    backoff: Duration,
+    timeout: Duration,
+}
+
+/// Upstream cookies of one signed-in account.
+///
+/// They are sent explicitly with each request made on the account's behalf,
+/// never through the shared agent's jar, so one visitor's login can not leak
+/// into requests made for anyone else.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Cookies(Vec<(String, String)>);
+
+impl Cookies {
+    fn header(&self) -> String {
+        self.0
+            .iter()
+            .map(|(name, value)| format!("{name}={value}"))
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+
+    /// Applies the `Set-Cookie` headers of a response, deleted cookies are dropped
+    pub fn update(&mut self, headers: &HeaderMap) {
+        for value in headers.get_all(header::SET_COOKIE) {
+            let Ok(value) = value.to_str() else {
+                continue;
+            };
+            let mut attributes = value.split(';').map(str::trim);
+            let Some((name, value)) = attributes.next().and_then(|pair| pair.split_once('='))
+            else {
+                continue;
+            };
+
+            let deleted = value.is_empty()
+                || value == "deleted"
+                || attributes.any(|attribute| {
+                    let attribute = attribute.to_ascii_lowercase();
+                    attribute.starts_with("max-age=0")
+                        || attribute.starts_with("max-age=-")
+                        || (attribute.starts_with("expires=") && attribute.contains("1970"))
+                });
+
+            self.0.retain(|(n, _)| n != name);
+            if !deleted {
+                self.0.push((name.to_string(), value.to_string()));
+            }
+        }
+    }
 }
 
-#[derive(Debug)]
+// Errors are reference counted so one failed request can be shared with
+// every caller that was waiting on it
+#[derive(Debug, Clone)]
 pub enum ClientError {
-    UreqError(ureq::Error),
-    IoError(std::io::Error),
+    UreqError(Arc<ureq::Error>),
+    IoError(Arc<std::io::Error>),
+    Task(String),
+    CircuitOpen { retry_after: Duration },
+}
+
+impl ClientError {
+    /// Whether the error means upstream is down or misbehaving, as opposed to
+    /// answering normally with something like a 404
+    pub fn is_upstream_failure(&self) -> bool {
+        match self {
+            ClientError::UreqError(err) => match **err {
+                ureq::Error::StatusCode(code) => code >= 500,
+                ureq::Error::Timeout(_)
+                | ureq::Error::Io(_)
+                | ureq::Error::ConnectionFailed
+                | ureq::Error::HostNotFound
+                | ureq::Error::Protocol(_) => true,
+                _ => false,
+            },
+            ClientError::IoError(_) => true,
+            ClientError::Task(_) | ClientError::CircuitOpen { .. } => false,
+        }
+    }
+
+    /// The HTTP status upstream answered with, if it answered at all
+    pub fn status_code(&self) -> Option<u16> {
+        match self {
+            ClientError::UreqError(err) => match **err {
+                ureq::Error::StatusCode(code) => Some(code),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
 }
 
 impl From<ureq::Error> for ClientError {
     fn from(value: ureq::Error) -> Self {
-        ClientError::UreqError(value)
+        ClientError::UreqError(Arc::new(value))
     }
 }
 
 impl From<std::io::Error> for ClientError {
     fn from(value: std::io::Error) -> Self {
-        ClientError::IoError(value)
+        ClientError::IoError(Arc::new(value))
+    }
+}
+
+impl From<task::JoinError> for ClientError {
+    fn from(value: task::JoinError) -> Self {
+        ClientError::Task(value.to_string())
     }
 }
 
@@ -31,55 +168,223 @@ impl fmt::Display for ClientError {
         match self {
             ClientError::UreqError(err) => write!(f, "HTTP request error: {}", err),
             ClientError::IoError(err) => write!(f, "IO error: {}", err),
+            ClientError::Task(err) => write!(f, "Request task error: {}", err),
+            ClientError::CircuitOpen { retry_after } => write!(
+                f,
+                "Upstream unavailable, retrying in {}s",
+                retry_after.as_secs()
+            ),
         }
     }
 }
 
+/// Exponential backoff with up to one extra `base` of random jitter
+fn backoff_delay(base: Duration, attempt: u32) -> Duration {
+    let jitter = RandomState::new().hash_one(attempt) % (base.as_millis() as u64).max(1);
+    base.saturating_mul(1 << attempt.min(16)) + Duration::from_millis(jitter)
+}
+
+impl Default for Client {
+    fn default() -> Self {
+        Self::with_config(ClientConfig::default())
+    }
+}
+
 impl Client {
-    pub fn new() -> Self {
+    pub fn with_config(config: ClientConfig) -> Self {
+        let max_concurrent = config.max_concurrent.max(1);
+
         Self {
-            agent: Agent::config_builder().max_redirects(0).build().new_agent(),
+            agent: Agent::config_builder()
+                .max_redirects(0)
+                .max_idle_connections(max_concurrent)
+                .max_idle_connections_per_host(max_concurrent)
+                .timeout_global(Some(config.timeout))
+                .build()
+                .new_agent(),
+            permits: Arc::new(Semaphore::new(max_concurrent)),
+            breaker: Arc::new(CircuitBreaker::new(
+                config.breaker_threshold,
+                config.breaker_cooldown,
+            )),
+            retries: config.retries,
+            backoff: config.backoff,
+            timeout: config.timeout,
+        }
+    }
+
+    // Requests made for an account get an agent of their own, whose jar is
+    // dropped with it
+    fn account_agent(timeout: Duration) -> Agent {
+        Agent::config_builder()
+            .max_redirects(0)
+            .http_status_as_error(false)
+            .timeout_global(Some(timeout))
+            .build()
+            .new_agent()
+    }
+
+    /// Runs a blocking request on the blocking pool once a permit is free
+    async fn run<T, F>(&self, request: F) -> Result<T, ClientError>
+    where
+        T: Send + 'static,
+        F: FnOnce(Agent) -> Result<T, ClientError> + Send + 'static,
+    {
+        self.breaker
+            .check()
+            .map_err(|retry_after| ClientError::CircuitOpen { retry_after })?;
+
+        let _permit = self
+            .permits
+            .acquire()
+            .await
+            .map_err(|e| ClientError::Task(e.to_string()))?;
+
+        let agent = self.agent.clone();
+        let result = task::spawn_blocking(move || request(agent)).await?;
+
+        match &result {
+            Err(err) if err.is_upstream_failure() => self.breaker.record_failure(),
+            _ => self.breaker.record_success(),
+        }
+
+        result
+    }
+
+    pub async fn get_response(&self, url: &str) -> Result<Response<Vec<u8>>, ClientError> {
+        let mut attempt = 0;
+
+        loop {
+            let url = url.to_owned();
+            let result = self
+                .run(move |agent| {
+                    let response = agent.get(&url).call()?;
+                    let (parts, mut body) = response.into_parts();
+                    Ok(Response::from_parts(parts, body.read_to_vec()?))
+                })
+                .await;
+
+            match result {
+                Err(err) if attempt < self.retries && err.is_upstream_failure() => {
+                    time::sleep(backoff_delay(self.backoff, attempt)).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
         }
     }
 
-    pub fn get_response(&self, url: &str) -> Result<Response<Body>, ClientError> {
-        Ok(self.agent.get(url).call()?)
+    pub async fn post_response(
+        &self,
+        url: &str,
+        form: Vec<(String, String)>,
+    ) -> Result<Response<Vec<u8>>, ClientError> {
+        let url = url.to_owned();
+        self.run(move |agent| {
+            let response = agent.post(&url).send_form(form)?;
+            let (parts, mut body) = response.into_parts();
+            Ok(Response::from_parts(parts, body.read_to_vec()?))
+        })
+        .await
+    }
+
+    /// Sends a GET request as the account owning `cookies`. Every status is
+    /// returned as a response, as upstream answers form errors with a 200 and
+    /// success with a redirect.
+    pub async fn get_response_as(
+        &self,
+        url: &str,
+        cookies: &Cookies,
+    ) -> Result<Response<Vec<u8>>, ClientError> {
+        let url = url.to_owned();
+        let cookies = cookies.header();
+        let timeout = self.timeout;
+        self.run(move |_| {
+            let response = Self::account_agent(timeout)
+                .get(&url)
+                .header(header::COOKIE, cookies)
+                .call()?;
+            let (parts, mut body) = response.into_parts();
+            Ok(Response::from_parts(parts, body.read_to_vec()?))
+        })
+        .await
     }
 
-    pub fn post_response(
+    /// Sends a form as the account owning `cookies`, see [`Self::get_response_as`]
+    pub async fn post_response_as(
         &self,
         url: &str,
         form: Vec<(String, String)>,
-    ) -> Result<Response<Body>, ClientError> {
-        Ok(self.agent.post(url).send_form(form)?)
+        cookies: &Cookies,
+    ) -> Result<Response<Vec<u8>>, ClientError> {
+        let url = url.to_owned();
+        let cookies = cookies.header();
+        let timeout = self.timeout;
+        self.run(move |_| {
+            let response = Self::account_agent(timeout)
+                .post(&url)
+                .header(header::COOKIE, cookies)
+                .send_form(form)?;
+            let (parts, mut body) = response.into_parts();
+            Ok(Response::from_parts(parts, body.read_to_vec()?))
+        })
+        .await
     }
 
-    pub fn get_string(&self, url: &str) -> Result<String, ClientError> {
-        let mut response = self.get_response(url)?;
-        Ok(response.body_mut().read_to_string()?)
+    /// Fetches the page a form sits on as the account owning `cookies` and
+    /// returns its CSRF token, keeping the cookies the token is bound to.
+    /// Upstream redirects accounts it won't let use the form, so `None` means
+    /// the form may not be sent.
+    pub async fn get_csrf_token_as(
+        &self,
+        url: &str,
+        cookies: &mut Cookies,
+    ) -> Result<Option<String>, ClientError> {
+        let response = self.get_response_as(url, cookies).await?;
+        cookies.update(response.headers());
+
+        let status = response.status();
+        if status.is_client_error() || status.is_server_error() {
+            return Err(ureq::Error::StatusCode(status.as_u16()).into());
+        }
+        if status.is_redirection() {
+            return Ok(None);
+        }
+        let dom = Html::parse_document(&String::from_utf8_lossy(response.body()));
+        Ok(paste::get_csrftoken(&dom))
+    }
+
+    pub async fn get_string(&self, url: &str) -> Result<String, ClientError> {
+        let bytes = self.get_bytes(url).await?;
+        String::from_utf8(bytes)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e).into())
     }
 
-    pub fn post_string(
+    pub async fn post_string(
         &self,
         url: &str,
         form: Vec<(String, String)>,
     ) -> Result<String, ClientError> {
-        let mut response = self.post_response(url, form)?;
-        Ok(response.body_mut().read_to_string()?)
+        let bytes = self.post_response(url, form).await?.into_body();
+        String::from_utf8(bytes)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e).into())
     }
 
-    pub fn get_bytes(&self, url: &str) -> Result<Vec<u8>, ClientError> {
-        let data = self.agent.get(url).call()?.body_mut().read_to_vec()?;
-
-        Ok(data)
+    pub async fn get_bytes(&self, url: &str) -> Result<Vec<u8>, ClientError> {
+        Ok(self.get_response(url).await?.into_body())
     }
 
-    pub fn get_html(&self, url: &str) -> Result<Html, ClientError> {
-        self.get_string(url).map(|s| Html::parse_document(&s))
+    pub async fn get_html(&self, url: &str) -> Result<Html, ClientError> {
+        self.get_string(url).await.map(|s| Html::parse_document(&s))
     }
 
-    pub fn post_html(&self, url: &str, form: Vec<(String, String)>) -> Result<Html, ClientError> {
+    pub async fn post_html(
+        &self,
+        url: &str,
+        form: Vec<(String, String)>,
+    ) -> Result<Html, ClientError> {
         self.post_string(url, form)
+            .await
             .map(|s| Html::parse_document(&s))
     }
 }
@@ -88,11 +393,11 @@ impl Client {
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_client() {
-        let client = Client::new();
+    #[tokio::test]
+    async fn test_client() {
+        let client = Client::default();
         // Test should handle network failures gracefully
-        match client.get_response("https://pastebin.com") {
+        match client.get_response("https://pastebin.com").await {
             Ok(response) => assert_eq!(response.status(), 200),
             Err(_) => {
                 // Network failures are acceptable in tests
@@ -101,10 +406,10 @@ mod tests {
         }
     }
 
-    #[test]
-    fn test_get_string() {
-        let client = Client::new();
-        match client.get_string("https://pastebin.com") {
+    #[tokio::test]
+    async fn test_get_string() {
+        let client = Client::default();
+        match client.get_string("https://pastebin.com").await {
             Ok(response) => assert!(response.contains("Pastebin.com")),
             Err(_) => {
                 // Network failures are acceptable in tests
@@ -113,15 +418,88 @@ mod tests {
         }
     }
 
-    #[test]
-    fn test_get_bytes() {
-        let client = Client::new();
-        match client.get_bytes("https://pastebin.com") {
-            Ok(response) => assert!(response.len() > 0),
+    #[tokio::test]
+    async fn test_get_bytes() {
+        let client = Client::default();
+        match client.get_bytes("https://pastebin.com").await {
+            Ok(response) => assert!(!response.is_empty()),
             Err(_) => {
                 // Network failures are acceptable in tests
                 println!("Network request failed (expected in some environments)");
             }
         }
     }
+
+    #[tokio::test]
+    async fn test_concurrency_is_bounded() {
+        let client = Client::with_config(ClientConfig {
+            max_concurrent: 1,
+            ..Default::default()
+        });
+        let permit = client.permits.acquire().await.expect("Semaphore open");
+
+        let pending =
+            tokio::time::timeout(std::time::Duration::from_millis(50), client.run(|_| Ok(())))
+                .await;
+        assert!(pending.is_err());
+
+        drop(permit);
+        assert!(client.run(|_| Ok(())).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker_fails_fast() {
+        let client = Client::with_config(ClientConfig {
+            retries: 0,
+            breaker_threshold: 1,
+            ..Default::default()
+        });
+
+        let failed = client
+            .run::<(), _>(|_| Err(ureq::Error::ConnectionFailed.into()))
+            .await;
+        assert!(matches!(failed, Err(ClientError::UreqError(_))));
+
+        let rejected = client.run(|_| Ok(())).await;
+        assert!(matches!(rejected, Err(ClientError::CircuitOpen { .. })));
+    }
+
+    #[test]
+    fn test_not_found_is_not_an_upstream_failure() {
+        assert!(!ClientError::from(ureq::Error::StatusCode(404)).is_upstream_failure());
+        assert!(ClientError::from(ureq::Error::StatusCode(503)).is_upstream_failure());
+    }
+
+    #[test]
+    fn test_cookies_follow_set_cookie() {
+        let mut headers = HeaderMap::new();
+        headers.append(
+            header::SET_COOKIE,
+            "_csrf-frontend=abc; path=/; HttpOnly"
+                .parse()
+                .expect("Valid header"),
+        );
+        headers.append(
+            header::SET_COOKIE,
+            "pastebin_user=1; expires=Thu, 01-Jan-1970 00:00:01 GMT"
+                .parse()
+                .expect("Valid header"),
+        );
+
+        let mut cookies = Cookies(vec![("pastebin_user".to_string(), "old".to_string())]);
+        cookies.update(&headers);
+
+        assert_eq!(cookies.header(), "_csrf-frontend=abc");
+    }
+
+    #[test]
+    fn test_backoff_delay() {
+        let base = Duration::from_millis(100);
+
+        for attempt in 0..4 {
+            let delay = backoff_delay(base, attempt);
+            assert!(delay >= base * 2u32.pow(attempt));
+            assert!(delay < base * 2u32.pow(attempt) + base);
+        }
+    }
 }
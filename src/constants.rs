@@ -1,3 +1,4 @@
+/// Default upstream origin, overridden by the `UPSTREAM_URL` environment variable
 pub const URL: &str = "https://pastebin.com";
 
 #[cfg(test)]
@@ -0,0 +1,311 @@
+use crate::catalog;
+
+/// Lowest keyword score trusted enough to pick a format
+const MIN_SCORE: u32 = 3;
+
+/// Only the start of a paste is looked at, which is plenty to tell languages apart
+const SAMPLE_BYTES: usize = 16 * 1024;
+
+/// Interpreters named in shebang lines and the slug of their format
+const INTERPRETERS: &[(&str, &str)] = &[
+    ("ash", "bash"),
+    ("awk", "awk"),
+    ("bash", "bash"),
+    ("dash", "bash"),
+    ("deno", "typescript"),
+    ("fish", "bash"),
+    ("gawk", "awk"),
+    ("julia", "julia"),
+    ("ksh", "bash"),
+    ("lua", "lua"),
+    ("node", "javascript"),
+    ("perl", "perl"),
+    ("php", "php"),
+    ("pwsh", "powershell"),
+    ("python", "python"),
+    ("rscript", "rsplus"),
+    ("ruby", "ruby"),
+    ("sh", "bash"),
+    ("tclsh", "tcl"),
+    ("zsh", "bash"),
+];
+
+/// Snippets typical of a language, with how strongly each one points to it.
+/// Every snippet found counts once.
+const SIGNALS: &[(&str, &str, u32)] = &[
+    ("rust", "fn main()", 3),
+    ("rust", "let mut ", 2),
+    ("rust", "pub fn ", 2),
+    ("rust", "use std::", 3),
+    ("rust", "#[derive(", 3),
+    ("rust", "impl ", 1),
+    ("rust", "-> Result<", 2),
+    ("rust", "println!(", 3),
+    ("python", "def ", 1),
+    ("python", "import ", 1),
+    ("python", "elif ", 3),
+    ("python", "self.", 1),
+    ("python", "__name__", 3),
+    ("python", "print(", 1),
+    ("python", "    return ", 1),
+    ("python", "):\n", 2),
+    ("javascript", "function ", 1),
+    ("javascript", "const ", 1),
+    ("javascript", "=> ", 1),
+    ("javascript", "console.log(", 3),
+    ("javascript", "require(", 2),
+    ("javascript", "document.", 2),
+    ("javascript", "module.exports", 3),
+    ("typescript", "interface ", 1),
+    ("typescript", ": string", 2),
+    ("typescript", ": number", 2),
+    ("typescript", "export type ", 3),
+    ("c", "#include <stdio.h>", 3),
+    ("c", "#include <stdlib.h>", 3),
+    ("c", "printf(", 1),
+    ("c", "int main(", 1),
+    ("c", "malloc(", 2),
+    ("cpp", "#include <iostream>", 3),
+    ("cpp", "std::", 2),
+    ("cpp", "cout <<", 3),
+    ("cpp", "template <", 2),
+    ("cpp", "int main(", 1),
+    ("csharp", "using System", 3),
+    ("csharp", "Console.WriteLine(", 3),
+    ("csharp", "namespace ", 1),
+    ("csharp", "{ get; set; }", 3),
+    ("java", "public class ", 2),
+    ("java", "System.out.println(", 3),
+    ("java", "public static void main(", 3),
+    ("java", "import java.", 3),
+    ("go", "package main", 3),
+    ("go", "func ", 1),
+    ("go", ":= ", 2),
+    ("go", "fmt.", 2),
+    ("go", "if err != nil", 3),
+    ("ruby", "puts ", 2),
+    ("ruby", " do |", 3),
+    ("ruby", "require '", 2),
+    ("ruby", "attr_accessor", 3),
+    ("ruby", "\nend", 1),
+    ("bash", "echo ", 1),
+    ("bash", "\nfi\n", 3),
+    ("bash", "; then", 3),
+    ("bash", "$(", 1),
+    ("bash", "esac", 3),
+    ("sql", "SELECT ", 2),
+    ("sql", "INSERT INTO ", 3),
+    ("sql", "CREATE TABLE ", 3),
+    ("sql", " WHERE ", 1),
+    ("sql", " FROM ", 1),
+    ("lua", "local ", 2),
+    ("lua", "~=", 2),
+    ("lua", "\nend", 1),
+    ("lua", " then\n", 1),
+    ("lua", "elseif ", 2),
+    ("css", "margin:", 2),
+    ("css", "padding:", 2),
+    ("css", "color:", 1),
+    ("css", "font-family:", 2),
+    ("css", "@media ", 3),
+    ("yaml", "---\n", 1),
+    ("yaml", ":\n  - ", 2),
+    ("yaml", ":\n  ", 1),
+    ("markdown", "\n## ", 2),
+    ("markdown", "# ", 1),
+    ("markdown", "```", 2),
+    ("markdown", "](http", 2),
+    ("markdown", "\n- [ ] ", 3),
+];
+
+fn id(slug: &str) -> Option<u16> {
+    catalog::format_by_slug(slug).map(|format| format.id)
+}
+
+// Editors name formats by slug or by extension, e.g. `ft=python` or `ft=sh`
+fn known(name: &str) -> Option<u16> {
+    id(name).or_else(|| catalog::format_by_filename(&format!("file.{name}")).map(|f| f.id))
+}
+
+// `#!/usr/bin/env python3` and `#!/bin/sh -e` both name the interpreter last
+fn from_shebang(first_line: &str) -> Option<u16> {
+    let command = first_line.strip_prefix("#!")?;
+    let mut words = command.split_whitespace();
+    let mut program = words.next()?.rsplit('/').next()?;
+    if program == "env" {
+        program = words.find(|word| !word.starts_with('-'))?;
+    }
+
+    let name = program
+        .trim_end_matches(|c: char| c.is_ascii_digit() || c == '.')
+        .to_ascii_lowercase();
+    INTERPRETERS
+        .iter()
+        .find(|(interpreter, _)| *interpreter == name)
+        .and_then(|(_, slug)| id(slug))
+}
+
+// `vim: set ft=python:`, `vi: filetype=sh` or `-*- mode: ruby -*-`
+fn from_modeline(line: &str) -> Option<u16> {
+    if let Some((_, rest)) = line.split_once("-*-") {
+        let inner = rest.split("-*-").next()?;
+        let mode = inner
+            .split(';')
+            .find_map(|part| part.trim().strip_prefix("mode:"))
+            .unwrap_or(inner)
+            .trim();
+        return known(&mode.to_ascii_lowercase());
+    }
+
+    let (_, settings) = line.split_once("vim:").or_else(|| line.split_once("vi:"))?;
+    settings
+        .split([' ', ':'])
+        .find_map(|setting| {
+            setting
+                .strip_prefix("ft=")
+                .or_else(|| setting.strip_prefix("filetype="))
+        })
+        .and_then(|name| known(&name.to_ascii_lowercase()))
+}
+
+// Formats that can be recognised from their structure alone
+fn from_structure(content: &str) -> Option<&'static str> {
+    let trimmed = content.trim_start();
+    let lower = trimmed
+        .get(..trimmed.len().min(64))
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+
+    if lower.starts_with("<?php") {
+        Some("php")
+    } else if lower.starts_with("<!doctype html") || lower.starts_with("<html") {
+        Some("html5")
+    } else if lower.starts_with("<?xml") {
+        Some("xml")
+    } else if (trimmed.starts_with('{') || trimmed.starts_with('['))
+        && serde_json::from_str::<serde_json::Value>(content).is_ok()
+    {
+        Some("json")
+    } else if trimmed.starts_with("diff --git ")
+        || (trimmed.starts_with("--- ") && content.contains("\n+++ ") && content.contains("\n@@ "))
+    {
+        Some("diff")
+    } else {
+        None
+    }
+}
+
+fn from_keywords(content: &str) -> Option<&'static str> {
+    let mut scores: Vec<(&str, u32)> = Vec::new();
+
+    for (slug, needle, weight) in SIGNALS {
+        if !content.contains(needle) {
+            continue;
+        }
+        match scores.iter_mut().find(|(s, _)| s == slug) {
+            Some((_, score)) => *score += weight,
+            None => scores.push((slug, *weight)),
+        }
+    }
+
+    // Ties go to the language listed first
+    scores
+        .into_iter()
+        .filter(|(_, score)| *score >= MIN_SCORE)
+        .fold(
+            None,
+            |best: Option<(&str, u32)>, (slug, score)| match best {
+                Some((_, top)) if top >= score => best,
+                _ => Some((slug, score)),
+            },
+        )
+        .map(|(slug, _)| slug)
+}
+
+/// Guesses pastebin's numeric format ID for `content`, trusting in order the
+/// uploaded file's name, a shebang, an editor modeline, the content's structure
+/// and finally typical keywords. Falls back to plain text.
+pub fn format_id(content: &str, filename: Option<&str>) -> u16 {
+    if let Some(format) = filename.and_then(catalog::format_by_filename)
+        && format.id != catalog::TEXT
+    {
+        return format.id;
+    }
+
+    let end = content.floor_char_boundary(SAMPLE_BYTES);
+    let sample = &content[..end];
+    if let Some(id) = sample.lines().next().and_then(from_shebang) {
+        return id;
+    }
+
+    // Modelines sit within the first or last few lines
+    let tail = sample.lines().rev().take(5);
+    if let Some(id) = sample.lines().take(5).chain(tail).find_map(from_modeline) {
+        return id;
+    }
+
+    from_structure(sample)
+        .or_else(|| from_keywords(sample))
+        .and_then(id)
+        .unwrap_or(catalog::TEXT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detect(content: &str) -> u16 {
+        format_id(content, None)
+    }
+
+    #[test]
+    fn test_filename_wins() {
+        assert_eq!(format_id("SELECT 1;", Some("query.rs")), 248);
+        assert_eq!(
+            format_id("SELECT * FROM t WHERE id = 1;", Some("notes.txt")),
+            48
+        );
+    }
+
+    #[test]
+    fn test_shebang() {
+        assert_eq!(detect("#!/usr/bin/env python3\nprint(1)"), 42);
+        assert_eq!(detect("#!/bin/sh -e\nls"), 8);
+        assert_eq!(detect("#!/usr/bin/env -S node --harmony\n"), 28);
+        assert_eq!(detect("#!/usr/bin/perl5.36\n"), 40);
+    }
+
+    #[test]
+    fn test_modeline() {
+        assert_eq!(detect("x = 1\n# vim: set ft=ruby:"), 45);
+        assert_eq!(detect("// -*- mode: rust -*-\nlet x;"), 248);
+        assert_eq!(detect("/* vi: filetype=c */"), 9);
+    }
+
+    #[test]
+    fn test_structure() {
+        assert_eq!(detect("{\"a\": [1, 2]}"), 255);
+        assert_eq!(detect("<?php echo 1;"), 41);
+        assert_eq!(detect("<!DOCTYPE html>\n<html></html>"), 196);
+        assert_eq!(detect("--- a/x\n+++ b/x\n@@ -1 +1 @@\n-a\n+b\n"), 19);
+    }
+
+    #[test]
+    fn test_keywords() {
+        assert_eq!(
+            detect("use std::io;\n\nfn main() {\n    let mut x = 1;\n}"),
+            248
+        );
+        assert_eq!(
+            detect("def main():\n    return 1\n\nif __name__ == '__main__':\n    main()"),
+            42
+        );
+        assert_eq!(detect("package main\n\nfunc main() {\n\tx := 1\n}"), 162);
+        assert_eq!(
+            detect("#include <stdio.h>\nint main() { printf(\"hi\"); }"),
+            9
+        );
+        assert_eq!(detect("if [ -f x ]; then\n  echo hi\nfi\n"), 8);
+        assert_eq!(detect("just some notes about lunch"), 1);
+    }
+}
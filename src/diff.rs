@@ -0,0 +1,198 @@
+use serde::Serialize;
+use similar::{DiffTag, TextDiff, udiff::UnifiedHunkHeader};
+
+/// Unchanged lines shown around each change
+const CONTEXT_LINES: usize = 3;
+
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum LineKind {
+    Equal,
+    Insert,
+    Delete,
+}
+
+/// A line in a unified diff, numbered in the old and/or new text
+#[derive(Serialize)]
+pub struct Line {
+    kind: LineKind,
+    old: Option<usize>,
+    new: Option<usize>,
+    text: String,
+}
+
+/// One side of a row in a side-by-side diff
+#[derive(Serialize)]
+pub struct Cell {
+    kind: LineKind,
+    number: usize,
+    text: String,
+}
+
+#[derive(Serialize)]
+pub struct Row {
+    left: Option<Cell>,
+    right: Option<Cell>,
+}
+
+/// A group of nearby changes with their surrounding context
+#[derive(Serialize)]
+pub struct Hunk {
+    header: String,
+    lines: Vec<Line>,
+    rows: Vec<Row>,
+}
+
+/// Line diff between two texts, laid out for both unified and side-by-side display
+#[derive(Serialize)]
+pub struct Diff {
+    hunks: Vec<Hunk>,
+    insertions: usize,
+    deletions: usize,
+}
+
+impl Diff {
+    pub fn new(old: &str, new: &str) -> Self {
+        let diff = TextDiff::from_lines(old, new);
+        let old_lines = diff.old_slices();
+        let new_lines = diff.new_slices();
+
+        let mut insertions = 0;
+        let mut deletions = 0;
+        let mut hunks = Vec::new();
+
+        for group in diff.grouped_ops(CONTEXT_LINES) {
+            if group.is_empty() {
+                continue;
+            }
+
+            let mut lines = Vec::new();
+            let mut rows = Vec::new();
+
+            for op in &group {
+                let (tag, old_range, new_range) = op.as_tag_tuple();
+
+                for i in old_range.clone() {
+                    let kind = match tag {
+                        DiffTag::Equal => continue,
+                        _ => LineKind::Delete,
+                    };
+                    deletions += 1;
+                    lines.push(Line {
+                        kind,
+                        old: Some(i + 1),
+                        new: None,
+                        text: trim_newline(old_lines[i]),
+                    });
+                }
+
+                for (offset, j) in new_range.clone().enumerate() {
+                    let (kind, old) = match tag {
+                        DiffTag::Equal => (LineKind::Equal, Some(old_range.start + offset + 1)),
+                        _ => (LineKind::Insert, None),
+                    };
+                    if kind == LineKind::Insert {
+                        insertions += 1;
+                    }
+                    lines.push(Line {
+                        kind,
+                        old,
+                        new: Some(j + 1),
+                        text: trim_newline(new_lines[j]),
+                    });
+                }
+
+                // Pair removed and added lines up so replacements line up side by side
+                let len = old_range.len().max(new_range.len());
+                for offset in 0..len {
+                    let (left_kind, right_kind) = match tag {
+                        DiffTag::Equal => (LineKind::Equal, LineKind::Equal),
+                        _ => (LineKind::Delete, LineKind::Insert),
+                    };
+                    let cell = |kind, start: usize, lines: &[&str]| {
+                        let index = start + offset;
+                        lines.get(index).map(|text| Cell {
+                            kind,
+                            number: index + 1,
+                            text: trim_newline(text),
+                        })
+                    };
+
+                    rows.push(Row {
+                        left: (offset < old_range.len())
+                            .then(|| cell(left_kind, old_range.start, old_lines))
+                            .flatten(),
+                        right: (offset < new_range.len())
+                            .then(|| cell(right_kind, new_range.start, new_lines))
+                            .flatten(),
+                    });
+                }
+            }
+
+            hunks.push(Hunk {
+                header: UnifiedHunkHeader::new(&group).to_string(),
+                lines,
+                rows,
+            });
+        }
+
+        Self {
+            hunks,
+            insertions,
+            deletions,
+        }
+    }
+}
+
+fn trim_newline(line: &str) -> String {
+    line.trim_end_matches(['\n', '\r']).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identical_texts_have_no_hunks() {
+        let diff = Diff::new("a\nb\n", "a\nb\n");
+        assert!(diff.hunks.is_empty());
+    }
+
+    #[test]
+    fn test_unified_lines() {
+        let diff = Diff::new("a\nb\nc\n", "a\nB\nc\n");
+
+        assert_eq!(diff.insertions, 1);
+        assert_eq!(diff.deletions, 1);
+
+        let hunk = &diff.hunks[0];
+        assert_eq!(hunk.header, "@@ -1,3 +1,3 @@");
+
+        let kinds = hunk.lines.iter().map(|l| l.kind).collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            vec![
+                LineKind::Equal,
+                LineKind::Delete,
+                LineKind::Insert,
+                LineKind::Equal
+            ]
+        );
+        assert_eq!(hunk.lines[1].text, "b");
+        assert_eq!(hunk.lines[2].text, "B");
+    }
+
+    #[test]
+    fn test_side_by_side_rows_pair_replacements() {
+        let diff = Diff::new("a\nb\nc\n", "a\nB\nC\nD\nc\n");
+        let rows = &diff.hunks[0].rows;
+
+        // a, b/B, C, D, c
+        assert_eq!(rows.len(), 5);
+        assert_eq!(rows[1].left.as_ref().map(|c| c.text.as_str()), Some("b"));
+        assert_eq!(rows[1].right.as_ref().map(|c| c.text.as_str()), Some("B"));
+        assert!(rows[2].left.is_none());
+        assert_eq!(rows[4].left.as_ref().map(|c| c.number), Some(3));
+        assert_eq!(rows[4].right.as_ref().map(|c| c.number), Some(5));
+    }
+}
@@ -0,0 +1,204 @@
+use once_cell::sync::Lazy;
+use syntect::{
+    highlighting::ThemeSet,
+    html::{ClassStyle, ClassedHTMLGenerator, css_for_theme_with_class_style},
+    parsing::{SyntaxReference, SyntaxSet},
+    util::LinesWithEndings,
+};
+
+/// Prefixed so highlighter classes never clash with the page's own
+const CLASS_STYLE: ClassStyle = ClassStyle::SpacedPrefixed { prefix: "hl-" };
+
+/// Larger pastes are shown as plain text, highlighting them would stall the page
+pub const MAX_HIGHLIGHT_BYTES: usize = 128 * 1024;
+
+const LIGHT_THEME: &str = "InspiredGitHub";
+const DARK_THEME: &str = "base16-ocean.dark";
+
+/// Pastebin format slugs and the grammar token used to look them up.
+///
+/// Formats missing here are looked up as grammar names instead, and shown as
+/// plain text if nothing matches.
+const GRAMMARS: &[(&str, &str)] = &[
+    ("actionscript", "as"),
+    ("actionscript3", "as"),
+    ("applescript", "applescript"),
+    ("asp", "asp"),
+    ("bash", "sh"),
+    ("c", "c"),
+    ("c_mac", "c"),
+    ("clojure", "clj"),
+    ("cpp", "cpp"),
+    ("cpp-qt", "cpp"),
+    ("csharp", "cs"),
+    ("css", "css"),
+    ("d", "d"),
+    ("delphi", "pas"),
+    ("diff", "diff"),
+    ("dos", "bat"),
+    ("dot", "dot"),
+    ("erlang", "erl"),
+    ("go", "go"),
+    ("groovy", "groovy"),
+    ("haskell", "hs"),
+    ("html4strict", "html"),
+    ("html5", "html"),
+    ("java", "java"),
+    ("java5", "java"),
+    ("javascript", "js"),
+    ("json", "json"),
+    ("latex", "tex"),
+    ("lisp", "lisp"),
+    ("lua", "lua"),
+    ("make", "mk"),
+    ("markdown", "md"),
+    ("matlab", "matlab"),
+    ("mysql", "sql"),
+    ("objc", "m"),
+    ("ocaml", "ml"),
+    ("pascal", "pas"),
+    ("perl", "pl"),
+    ("php", "php"),
+    ("php-brief", "php"),
+    ("python", "py"),
+    ("rsplus", "r"),
+    ("ruby", "rb"),
+    ("rails", "rb"),
+    ("rust", "rs"),
+    ("scala", "scala"),
+    ("sql", "sql"),
+    ("tcl", "tcl"),
+    ("xml", "xml"),
+    ("yaml", "yaml"),
+];
+
+static SYNTAXES: Lazy<SyntaxSet> = Lazy::new(SyntaxSet::load_defaults_newlines);
+
+/// Stylesheet for the highlighter classes, following the user's color scheme
+pub static CSS: Lazy<String> = Lazy::new(|| {
+    let themes = ThemeSet::load_defaults();
+    let css = |name: &str| {
+        themes
+            .themes
+            .get(name)
+            .and_then(|theme| css_for_theme_with_class_style(theme, CLASS_STYLE).ok())
+            .unwrap_or_default()
+    };
+
+    format!(
+        "{}\n@media screen and (prefers-color-scheme: dark) {{\n{}\n}}",
+        css(LIGHT_THEME),
+        css(DARK_THEME)
+    )
+});
+
+// Falls back to the grammar's own names and extensions, as used by Markdown code fences
+fn grammar(format: &str) -> Option<&'static SyntaxReference> {
+    let token = GRAMMARS
+        .iter()
+        .find(|(slug, _)| *slug == format)
+        .map_or(format, |(_, token)| *token);
+
+    SYNTAXES.find_syntax_by_token(token)
+}
+
+/// Highlights `content` as pastebin's `format` or a language name, returning
+/// HTML spans with classes from [`CSS`], or `None` if it should be shown as plain text
+pub fn highlight(content: &str, format: &str) -> Option<String> {
+    if content.len() > MAX_HIGHLIGHT_BYTES {
+        return None;
+    }
+
+    let syntax = grammar(format)?;
+    let mut generator = ClassedHTMLGenerator::new_with_class_style(syntax, &SYNTAXES, CLASS_STYLE);
+
+    for line in LinesWithEndings::from(content) {
+        generator
+            .parse_html_for_line_which_includes_newline(line)
+            .ok()?;
+    }
+
+    Some(generator.finalize())
+}
+
+/// Splits highlighted HTML into one fragment per line, closing the spans still
+/// open at the end of a line and reopening them at the start of the next
+pub fn split_lines(html: &str) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut open: Vec<&str> = Vec::new();
+    let mut line = String::new();
+    let mut has_text = false;
+    let mut rest = html;
+
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix("</span>") {
+            open.pop();
+            line.push_str("</span>");
+            rest = after;
+        } else if rest.starts_with('<') {
+            let end = rest.find('>').map_or(rest.len(), |i| i + 1);
+            open.push(&rest[..end]);
+            line.push_str(&rest[..end]);
+            rest = &rest[end..];
+        } else if let Some(after) = rest.strip_prefix('\n') {
+            line.extend(open.iter().map(|_| "</span>"));
+            lines.push(std::mem::take(&mut line));
+            line.extend(open.iter().copied());
+            has_text = false;
+            rest = after;
+        } else {
+            let end = rest.find(['<', '\n']).unwrap_or(rest.len());
+            line.push_str(&rest[..end]);
+            has_text = true;
+            rest = &rest[end..];
+        }
+    }
+
+    if has_text {
+        lines.push(line);
+    }
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_mapped_format_has_a_grammar() {
+        for (slug, _) in GRAMMARS {
+            assert!(grammar(slug).is_some(), "No grammar for '{}'", slug);
+        }
+    }
+
+    #[test]
+    fn test_highlight_escapes_and_classes() {
+        let html = highlight("fn main() { \"<b>\" }\n", "rust").expect("Rust is highlighted");
+
+        assert!(html.contains("hl-"));
+        assert!(html.contains("&lt;b&gt;"));
+        assert!(!html.contains("<b>"));
+    }
+
+    #[test]
+    fn test_split_lines_balances_spans() {
+        let lines = split_lines("<span class=\"a\">x\ny</span>\nz");
+
+        assert_eq!(
+            lines,
+            vec![
+                "<span class=\"a\">x</span>",
+                "<span class=\"a\">y</span>",
+                "z"
+            ]
+        );
+        assert_eq!(split_lines("a\n\nb\n"), vec!["a", "", "b"]);
+    }
+
+    #[test]
+    fn test_plain_text_is_not_highlighted() {
+        assert!(highlight("hello", "text").is_none());
+        assert!(highlight("hello", "unknown").is_none());
+    }
+}
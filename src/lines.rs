@@ -0,0 +1,84 @@
+use std::{fmt, ops::RangeInclusive, str::FromStr};
+
+/// Maximum number of ranges accepted in one `lines` parameter
+const MAX_RANGES: usize = 32;
+
+/// A selection of 1-based line numbers, parsed from values like `42`, `10-20` or `1-3,7`
+#[derive(Debug, Default, PartialEq)]
+pub struct LineRanges(Vec<RangeInclusive<usize>>);
+
+#[derive(Debug, PartialEq)]
+pub struct InvalidLineRanges;
+
+impl fmt::Display for InvalidLineRanges {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Line ranges must look like `42`, `10-20` or `1-3,7`")
+    }
+}
+
+impl FromStr for LineRanges {
+    type Err = InvalidLineRanges;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let ranges = s
+            .split(',')
+            .map(|part| {
+                let (start, end) = part.trim().split_once('-').unwrap_or((part, part));
+                let start = start
+                    .trim()
+                    .parse::<usize>()
+                    .map_err(|_| InvalidLineRanges)?;
+                let end = end.trim().parse::<usize>().map_err(|_| InvalidLineRanges)?;
+
+                if start == 0 || end < start {
+                    return Err(InvalidLineRanges);
+                }
+                Ok(start..=end)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if ranges.len() > MAX_RANGES {
+            return Err(InvalidLineRanges);
+        }
+
+        Ok(Self(ranges))
+    }
+}
+
+impl LineRanges {
+    pub fn contains(&self, line: usize) -> bool {
+        self.0.iter().any(|range| range.contains(&line))
+    }
+
+    /// The selected lines of `text`, keeping their line endings
+    pub fn slice(&self, text: &str) -> String {
+        text.split_inclusive('\n')
+            .enumerate()
+            .filter(|(i, _)| self.contains(i + 1))
+            .map(|(_, line)| line)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!("42".parse(), Ok(LineRanges(vec![42..=42])));
+        assert_eq!("10-20".parse(), Ok(LineRanges(vec![10..=20])));
+        assert_eq!("1-3, 7".parse(), Ok(LineRanges(vec![1..=3, 7..=7])));
+
+        assert_eq!("0".parse::<LineRanges>(), Err(InvalidLineRanges));
+        assert_eq!("20-10".parse::<LineRanges>(), Err(InvalidLineRanges));
+        assert_eq!("a-b".parse::<LineRanges>(), Err(InvalidLineRanges));
+        assert_eq!("".parse::<LineRanges>(), Err(InvalidLineRanges));
+    }
+
+    #[test]
+    fn test_slice() {
+        let ranges: LineRanges = "2-3,5".parse().expect("Valid ranges");
+        assert_eq!(ranges.slice("a\nb\nc\nd\ne"), "b\nc\ne");
+    }
+}
@@ -1,15 +1,46 @@
-use axum;
 use state::AppState;
-use std::env;
+use std::{env, net::SocketAddr, time::Duration};
 use tokio::net::TcpListener;
 
+mod backends;
+mod breaker;
+mod catalog;
 mod client;
 mod constants;
+mod detect;
+mod diff;
+mod highlight;
+mod lines;
+mod markdown;
 mod parsers;
+mod ratelimit;
+mod revisions;
 mod routes;
+mod sessions;
+mod singleflight;
+mod snapshots;
 mod state;
+mod tar;
 mod templates;
 
+/// Runs `prune` on the blocking pool once an hour
+fn prune_hourly<F>(name: &'static str, prune: F)
+where
+    F: Fn() -> sled::Result<usize> + Clone + Send + 'static,
+{
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(60 * 60));
+        loop {
+            interval.tick().await;
+            match tokio::task::spawn_blocking(prune.clone()).await {
+                Ok(Ok(_)) => {}
+                Ok(Err(e)) => eprintln!("Failed to prune {}: {}", name, e),
+                Err(e) => eprintln!("Pruning task for {} failed: {}", name, e),
+            }
+        }
+    });
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let port = env::var("PORT").unwrap_or("3000".to_string());
@@ -22,11 +53,25 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         Ok(state) => state,
         Err(e) => {
             eprintln!("Failed to initialize application state: {}", e);
-            return Err(e.into());
+            return Err(e);
         }
     };
 
-    let app = routes::get_router(state);
+    // Sweep expired entries that are never looked up again
+    if let Some(snapshots) = state.snapshots.clone() {
+        prune_hourly("snapshots", move || snapshots.prune());
+    }
+    if let Some(revisions) = state.revisions.clone() {
+        prune_hourly("revisions", move || revisions.prune());
+    }
+    if let Some(cache) = state.cache.clone() {
+        prune_hourly("cache", move || cache.prune());
+    }
+    let sessions = state.sessions.clone();
+    prune_hourly("sessions", move || sessions.prune());
+
+    // Peer addresses tell apart visitors who are not signed in
+    let app = routes::get_router(state).into_make_service_with_connect_info::<SocketAddr>();
 
     let listener = match TcpListener::bind(&addr).await {
         Ok(listener) => listener,
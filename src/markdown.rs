@@ -0,0 +1,130 @@
+use pulldown_cmark::{CodeBlockKind, Event, Options, Parser, Tag, TagEnd, html};
+use std::borrow::Cow;
+
+use crate::highlight;
+
+/// Renders a Markdown paste to HTML that is safe to embed in a page.
+///
+/// Fenced code blocks are highlighted like pastes of their language, and the
+/// output is sanitized so raw HTML in the paste can not run scripts or embed frames.
+/// Returns `None` for pastes too large to highlight, which are shown as source instead.
+pub fn render(content: &str) -> Option<String> {
+    if content.len() > highlight::MAX_HIGHLIGHT_BYTES {
+        return None;
+    }
+
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_FOOTNOTES;
+
+    let mut events = Vec::new();
+    let mut code: Option<(String, String)> = None;
+
+    for event in Parser::new_ext(content, options) {
+        match (event, &mut code) {
+            (Event::Start(Tag::CodeBlock(kind)), None) => {
+                let lang = match kind {
+                    CodeBlockKind::Fenced(info) => info
+                        .split_whitespace()
+                        .next()
+                        .unwrap_or_default()
+                        .to_string(),
+                    CodeBlockKind::Indented => String::new(),
+                };
+                code = Some((lang, String::new()));
+            }
+            (Event::Text(text), Some((_, source))) => source.push_str(&text),
+            (Event::End(TagEnd::CodeBlock), Some((lang, source))) => {
+                let body =
+                    highlight::highlight(source, lang).unwrap_or_else(|| tera::escape_html(source));
+                events.push(Event::Html(
+                    format!("<pre><code>{body}</code></pre>\n").into(),
+                ));
+                code = None;
+            }
+            (event, _) => events.push(event),
+        }
+    }
+
+    let mut unsafe_html = String::new();
+    html::push_html(&mut unsafe_html, events.into_iter());
+
+    let html = ammonia::Builder::default()
+        .add_tags(["input"])
+        .add_tag_attributes("input", ["checked", "disabled"])
+        .set_tag_attribute_value("input", "type", "checkbox")
+        .set_tag_attribute_value("input", "disabled", "")
+        .add_tag_attributes("span", ["class"])
+        .attribute_filter(|element, attribute, value| match (element, attribute) {
+            ("span", "class") => highlight_classes(value),
+            _ => Some(value.into()),
+        })
+        .clean(&unsafe_html)
+        .to_string();
+
+    Some(html)
+}
+
+// Spans may only carry the highlighter's classes, not the page's own
+fn highlight_classes(value: &str) -> Option<Cow<'_, str>> {
+    let classes = value
+        .split_whitespace()
+        .filter(|class| class.starts_with("hl-"))
+        .collect::<Vec<_>>();
+
+    (!classes.is_empty()).then(|| classes.join(" ").into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tables_and_task_lists() {
+        let html = render("| a | b |\n|---|---|\n| 1 | 2 |\n\n- [x] done\n- [ ] todo\n")
+            .expect("Rendered");
+
+        assert!(html.contains("<table>"));
+        assert!(html.contains("<td>1</td>"));
+        assert!(html.contains("type=\"checkbox\""));
+        assert!(html.contains("checked"));
+    }
+
+    #[test]
+    fn test_fenced_code_is_highlighted() {
+        let html = render("```rust\nfn main() {}\n```\n").expect("Rendered");
+
+        assert!(html.contains("<pre><code>"));
+        assert!(html.contains("class=\"hl-"));
+    }
+
+    #[test]
+    fn test_scripts_and_frames_are_removed() {
+        let html = render(
+            "<script>alert(1)</script>\n\n<iframe src=\"https://example.com\"></iframe>\n\n<a href=\"javascript:alert(1)\" onclick=\"x\">link</a>\n",
+        )
+        .expect("Rendered");
+
+        assert!(!html.contains("<script"));
+        assert!(!html.contains("<iframe"));
+        assert!(!html.contains("javascript:"));
+        assert!(!html.contains("onclick"));
+    }
+
+    #[test]
+    fn test_spans_only_keep_highlighter_classes() {
+        let html = render("<span class=\"hl-keyword line selected\">a</span> <span class=\"container\">b</span>\n")
+            .expect("Rendered");
+
+        assert!(html.contains("<span class=\"hl-keyword\">a</span>"));
+        assert!(html.contains("<span>b</span>"));
+    }
+
+    #[test]
+    fn test_large_pastes_are_not_rendered() {
+        let content = "x".repeat(highlight::MAX_HIGHLIGHT_BYTES + 1);
+
+        assert!(render(&content).is_none());
+    }
+}
@@ -0,0 +1,143 @@
+use once_cell::sync::Lazy;
+use scraper::{ElementRef, Html, Selector};
+use serde::{Deserialize, Serialize};
+
+use super::{FromHtml, utils::safe_attr_content};
+
+static SELECTOR_FORM_ERROR: Lazy<Selector> = Lazy::new(|| {
+    Selector::parse(".has-error .help-block, .error-summary li, .notice.-error")
+        .expect("Valid CSS selector")
+});
+static SELECTOR_EDIT_TEXT: Lazy<Selector> =
+    Lazy::new(|| Selector::parse("#postform-text").expect("Valid CSS selector"));
+static SELECTOR_EDIT_TITLE: Lazy<Selector> =
+    Lazy::new(|| Selector::parse("#postform-name").expect("Valid CSS selector"));
+static SELECTOR_EDIT_TAGS: Lazy<Selector> =
+    Lazy::new(|| Selector::parse("#postform-tag").expect("Valid CSS selector"));
+static SELECTOR_EDIT_CATEGORY: Lazy<Selector> = Lazy::new(|| {
+    Selector::parse("#postform-category_id option[selected]").expect("Valid CSS selector")
+});
+static SELECTOR_EDIT_FORMAT: Lazy<Selector> =
+    Lazy::new(|| Selector::parse("#postform-format option[selected]").expect("Valid CSS selector"));
+static SELECTOR_EDIT_EXPIRATION: Lazy<Selector> = Lazy::new(|| {
+    Selector::parse("#postform-expiration option[selected]").expect("Valid CSS selector")
+});
+static SELECTOR_EDIT_EXPOSURE: Lazy<Selector> =
+    Lazy::new(|| Selector::parse("#postform-status option[selected]").expect("Valid CSS selector"));
+
+/// A paste as its author sees it on upstream's edit page
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct EditForm {
+    pub text: String,
+    pub title: String,
+    /// Comma separated
+    pub tags: String,
+    pub category: u8,
+    pub format: u16,
+    pub expiration: Option<String>,
+    pub exposure: u8,
+}
+
+fn selected<T: std::str::FromStr>(dom: &Html, selector: &Selector) -> Option<T> {
+    dom.select(selector)
+        .next()
+        .and_then(|el| el.value().attr("value"))
+        .and_then(|value| value.parse().ok())
+}
+
+fn first<'a>(dom: &'a Html, selector: &Selector) -> Option<ElementRef<'a>> {
+    dom.select(selector).next()
+}
+
+impl FromHtml for EditForm {
+    fn from_html(dom: &Html, _base_url: &str) -> Result<Self, String> {
+        // Only the author is shown the form, everyone else gets an error page
+        let text = first(dom, &SELECTOR_EDIT_TEXT).ok_or("Missing paste edit form")?;
+
+        Ok(EditForm {
+            text: text.text().collect(),
+            title: safe_attr_content(first(dom, &SELECTOR_EDIT_TITLE), "value"),
+            tags: safe_attr_content(first(dom, &SELECTOR_EDIT_TAGS), "value"),
+            category: selected(dom, &SELECTOR_EDIT_CATEGORY).unwrap_or_default(),
+            format: selected(dom, &SELECTOR_EDIT_FORMAT).unwrap_or(1),
+            expiration: selected(dom, &SELECTOR_EDIT_EXPIRATION),
+            exposure: selected(dom, &SELECTOR_EDIT_EXPOSURE).unwrap_or(1),
+        })
+    }
+}
+
+/// The first validation error shown on a submitted upstream form
+pub fn get_form_error(dom: &Html) -> Option<String> {
+    dom.select(&SELECTOR_FORM_ERROR)
+        .map(|el| el.text().collect::<String>().trim().to_owned())
+        .find(|text| !text.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_form_error() {
+        let dom = Html::parse_document(
+            r#"
+            <form id="w0">
+                <div class="form-group has-error">
+                    <input id="loginform-password">
+                    <p class="help-block help-block-error">Incorrect username or password.</p>
+                </div>
+                <div class="form-group">
+                    <p class="help-block help-block-error"></p>
+                </div>
+            </form>
+        "#,
+        );
+
+        assert_eq!(
+            get_form_error(&dom),
+            Some("Incorrect username or password.".to_string())
+        );
+        assert_eq!(get_form_error(&Html::parse_document("<form></form>")), None);
+    }
+
+    #[test]
+    fn test_edit_form() {
+        let dom = Html::parse_document(
+            r#"
+            <form id="w0" action="/edit/abc123" method="post">
+                <textarea id="postform-text" name="PostForm[text]">
+let x = 1;</textarea>
+                <select id="postform-category_id" name="PostForm[category_id]">
+                    <option value="0">None</option>
+                    <option value="8" selected>Software</option>
+                </select>
+                <input id="postform-tag" name="PostForm[tag]" value="rust,demo">
+                <select id="postform-format" name="PostForm[format]">
+                    <option value="1">None</option>
+                    <option value="248" selected>Rust</option>
+                </select>
+                <select id="postform-status" name="PostForm[status]">
+                    <option value="0">Public</option>
+                    <option value="1">Unlisted</option>
+                    <option value="2" selected>Private</option>
+                </select>
+                <input id="postform-name" name="PostForm[name]" value="Example">
+            </form>
+        "#,
+        );
+
+        assert_eq!(
+            EditForm::from_html(&dom, ""),
+            Ok(EditForm {
+                text: "let x = 1;".to_string(),
+                title: "Example".to_string(),
+                tags: "rust,demo".to_string(),
+                category: 8,
+                format: 248,
+                expiration: None,
+                exposure: 2,
+            })
+        );
+        assert!(EditForm::from_html(&Html::parse_document("<p>Not found</p>"), "").is_err());
+    }
+}
@@ -2,14 +2,10 @@ use once_cell::sync::Lazy;
 use scraper::{ElementRef, Html, Selector};
 use serde::Serialize;
 
-use crate::constants::URL;
-
 use super::{FromElement, FromHtml};
-use super::utils::{safe_text_content, safe_attr_content, safe_select};
+use super::utils::{SELECTOR_META_OG_URL, safe_attr_content, safe_select, safe_text_content};
 
 // Pre-compiled selectors to avoid unwrap() calls
-static SELECTOR_META_OG_URL: Lazy<Selector> =
-    Lazy::new(|| Selector::parse("meta[property='og:url']").expect("Valid CSS selector"));
 static SELECTOR_ARCHIVE_TABLE: Lazy<Selector> =
     Lazy::new(|| Selector::parse(".archive-table").expect("Valid CSS selector"));
 static SELECTOR_MAINTABLE_TR: Lazy<Selector> =
@@ -21,12 +17,12 @@ static SELECTOR_TD_CHILD_2: Lazy<Selector> =
 static SELECTOR_TD_CHILD_3_A: Lazy<Selector> =
     Lazy::new(|| Selector::parse("td:nth-child(3)>a").expect("Valid CSS selector"));
 
-#[derive(Serialize)]
+#[derive(Serialize, Clone)]
 pub struct Archive {
-    id: String,
-    title: String,
-    age: String,
-    format: String,
+    pub(crate) id: String,
+    pub(crate) title: String,
+    pub(crate) age: String,
+    pub(crate) format: String,
 }
 
 impl FromElement for Archive {
@@ -50,18 +46,18 @@ impl FromElement for Archive {
     }
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Clone)]
 pub struct ArchivePage {
-    format: Option<String>,
-    archives: Vec<Archive>,
+    pub(crate) format: Option<String>,
+    pub(crate) archives: Vec<Archive>,
 }
 
 impl FromHtml for ArchivePage {
-    fn from_html(dom: &Html) -> Result<Self, String> {
+    fn from_html(dom: &Html, base_url: &str) -> Result<Self, String> {
         let meta_element = dom.select(&SELECTOR_META_OG_URL).next();
         let format = none_if_empty(
             safe_attr_content(meta_element, "content")
-                .replace(&format!("{URL}/archive"), "")
+                .replace(&format!("{base_url}/archive"), "")
                 .replace("/", ""),
         );
 
@@ -93,6 +89,8 @@ fn none_if_empty(string: String) -> Option<String> {
 mod tests {
     use scraper::{Html, Selector};
 
+    use crate::constants::URL;
+
     use super::*;
 
     #[test]
@@ -146,7 +144,7 @@ mod tests {
         "#,
         );
 
-        let archive_page = ArchivePage::from_html(&dom).expect("Should not error");
+        let archive_page = ArchivePage::from_html(&dom, URL).expect("Should not error");
 
         // Should not panic and should return empty archives
         assert_eq!(archive_page.format, None);
@@ -172,7 +170,7 @@ mod tests {
         "#,
         );
 
-        let archive_page = ArchivePage::from_html(&dom).expect("Should not error");
+        let archive_page = ArchivePage::from_html(&dom, URL).expect("Should not error");
 
         // Should not panic
         assert_eq!(archive_page.format, None);
@@ -1,13 +1,15 @@
 use chrono::DateTime;
 use scraper::{ElementRef, Html};
 
+pub mod account;
 pub mod archive;
 pub mod paste;
 pub mod user;
 pub mod utils;
 
 pub trait FromHtml {
-    fn from_html(dom: &Html) -> Result<Self, String>
+    /// Parses a page fetched from the upstream service at `base_url`
+    fn from_html(dom: &Html, base_url: &str) -> Result<Self, String>
     where
         Self: Sized;
 }
@@ -1,12 +1,10 @@
 use byte_unit::Byte;
 use once_cell::sync::Lazy;
 use scraper::{ElementRef, Html, Selector, selectable::Selectable};
-use serde::Serialize;
-
-use crate::constants::URL;
+use serde::{Deserialize, Serialize};
 
 use super::{FromElement, FromHtml, parse_date, user::SimpleUser};
-use super::utils::{safe_attr_content, safe_select, safe_parse_number};
+use super::utils::{SELECTOR_META_OG_URL, safe_attr_content, safe_parse_number, safe_select};
 
 // Helper function to safely parse dates with fallback to 0
 fn safe_parse_date(date_str: &str) -> i64 {
@@ -31,8 +29,6 @@ static SELECTOR_ARCHIVE_LINK: Lazy<Selector> =
     Lazy::new(|| Selector::parse("a.h_800[href^='/archive/']").expect("Valid CSS selector"));
 static SELECTOR_SOURCE_OL: Lazy<Selector> =
     Lazy::new(|| Selector::parse(".source>ol").expect("Valid CSS selector"));
-static SELECTOR_USERNAME_USER: Lazy<Selector> =
-    Lazy::new(|| Selector::parse(".username>.user").expect("Valid CSS selector"));
 static SELECTOR_DATE_SPAN: Lazy<Selector> =
     Lazy::new(|| Selector::parse(".date>span").expect("Valid CSS selector"));
 static SELECTOR_DATE_SPAN_2: Lazy<Selector> =
@@ -41,8 +37,6 @@ static SELECTOR_HIGHLIGHTED_CODE: Lazy<Selector> =
     Lazy::new(|| Selector::parse(".highlighted-code").expect("Valid CSS selector"));
 static SELECTOR_COMMENTS_LINK: Lazy<Selector> =
     Lazy::new(|| Selector::parse("a[href='#comments']").expect("Valid CSS selector"));
-static SELECTOR_META_OG_URL: Lazy<Selector> =
-    Lazy::new(|| Selector::parse("meta[property='og:url']").expect("Valid CSS selector"));
 static SELECTOR_POST_VIEW: Lazy<Selector> =
     Lazy::new(|| Selector::parse(".post-view").expect("Valid CSS selector"));
 static SELECTOR_INFO_TOP_H1: Lazy<Selector> =
@@ -73,17 +67,19 @@ static SELECTOR_PASSWORD_FORM: Lazy<Selector> =
     Lazy::new(|| Selector::parse("#postpasswordverificationform-password").expect("Valid CSS selector"));
 static SELECTOR_BURN: Lazy<Selector> =
     Lazy::new(|| Selector::parse(".burn, .-burn").expect("Valid CSS selector"));
+static SELECTOR_COMMENT_FORM: Lazy<Selector> =
+    Lazy::new(|| Selector::parse("form[action*='comment']").expect("Valid CSS selector"));
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct PasteContainer {
-    category: Option<String>,
-    size: u64,
-    likes: Option<u32>,
-    dislikes: Option<u32>,
-    id: Option<String>,
-    format: String,
-    format_name: String,
-    content: String,
+    pub(crate) category: Option<String>,
+    pub(crate) size: u64,
+    pub(crate) likes: Option<u32>,
+    pub(crate) dislikes: Option<u32>,
+    pub(crate) id: Option<String>,
+    pub(crate) format: String,
+    pub(crate) format_name: String,
+    pub(crate) content: String,
 }
 
 impl FromElement for PasteContainer {
@@ -103,7 +99,7 @@ impl FromElement for PasteContainer {
                     .map(|(size, _)| size.to_owned())
             })
             .and_then(|size| Byte::parse_str(&size, true).ok())
-            .unwrap_or(Byte::default())
+            .unwrap_or_default()
             .as_u64();
 
         let likes = safe_select(parent, &SELECTOR_LIKE)
@@ -144,13 +140,16 @@ impl FromElement for PasteContainer {
     }
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Comment {
-    author: SimpleUser,
-    date: i64,
-    edit_date: Option<i64>,
-    container: PasteContainer,
-    num_comments: u32,
+    pub(crate) author: SimpleUser,
+    pub(crate) date: i64,
+    pub(crate) edit_date: Option<i64>,
+    pub(crate) container: PasteContainer,
+    pub(crate) num_comments: u32,
+    /// Comments on this comment, fetched from its own page when threads are loaded
+    #[serde(default)]
+    pub(crate) replies: Vec<Comment>,
 }
 
 impl FromElement for Comment {
@@ -159,7 +158,7 @@ impl FromElement for Comment {
 
         let date = safe_select(parent, &SELECTOR_DATE_SPAN)
             .and_then(|el| el.attr("title"))
-            .map(|title| safe_parse_date(title))
+            .map(safe_parse_date)
             .unwrap_or(0);
 
         let edit_date = safe_select(parent, &SELECTOR_DATE_SPAN_2)
@@ -185,34 +184,57 @@ impl FromElement for Comment {
             edit_date,
             container,
             num_comments,
+            replies: Vec::new(),
         })
     }
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Paste {
-    id: String,
-    title: Option<String>,
-    tags: Vec<String>,
-    container: PasteContainer,
-    author: SimpleUser,
-    date: i64,
-    edit_date: Option<i64>,
-    views: u32,
-    rating: f32,
-    expire: String,
-    comment_for: Option<String>,
-    unlisted: bool,
-    num_comments: Option<u32>,
-    comments: Vec<Comment>,
-    locked: bool,
+    pub(crate) id: String,
+    pub(crate) title: Option<String>,
+    pub(crate) tags: Vec<String>,
+    pub(crate) container: PasteContainer,
+    pub(crate) author: SimpleUser,
+    pub(crate) date: i64,
+    pub(crate) edit_date: Option<i64>,
+    pub(crate) views: u32,
+    pub(crate) rating: f32,
+    pub(crate) expire: String,
+    pub(crate) comment_for: Option<String>,
+    pub(crate) unlisted: bool,
+    pub(crate) num_comments: Option<u32>,
+    pub(crate) comments: Vec<Comment>,
+    pub(crate) locked: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) captured_at: Option<i64>,
+}
+
+impl Paste {
+    pub fn content(&self) -> &str {
+        &self.container.content
+    }
+
+    pub fn edit_date(&self) -> Option<i64> {
+        self.edit_date
+    }
+
+    /// When this copy was fetched, if it is a cached copy rather than a live one
+    pub fn captured_at(&self) -> Option<i64> {
+        self.captured_at
+    }
+
+    /// Marks this paste as a cached copy fetched at `timestamp`
+    pub fn set_captured_at(&mut self, timestamp: i64) {
+        self.captured_at = Some(timestamp);
+    }
 }
 
 impl FromHtml for Paste {
-    fn from_html(dom: &Html) -> Result<Self, String> {
+    fn from_html(dom: &Html, base_url: &str) -> Result<Self, String> {
         let meta_element = dom.select(&SELECTOR_META_OG_URL).next();
         let id = safe_attr_content(meta_element, "content")
-            .replace(&format!("{URL}/"), "");
+            .replace(&format!("{base_url}/"), "");
 
         let parent = dom.select(&SELECTOR_POST_VIEW).next()
             .ok_or("Missing .post-view element")?;
@@ -233,7 +255,7 @@ impl FromHtml for Paste {
 
         let date = safe_select(&parent, &SELECTOR_DATE_SPAN)
             .and_then(|el| el.attr("title"))
-            .map(|title| safe_parse_date(title))
+            .map(safe_parse_date)
             .unwrap_or(0);
 
         let edit_date = safe_select(&parent, &SELECTOR_DATE_SPAN_2)
@@ -300,6 +322,7 @@ impl FromHtml for Paste {
             num_comments,
             comments,
             locked,
+            captured_at: None,
         })
     }
 }
@@ -311,6 +334,14 @@ pub fn get_csrftoken(dom: &Html) -> Option<String> {
         .map(|s| s.to_owned())
 }
 
+/// Where the paste page's comment form posts to, absent when comments are closed
+pub fn get_comment_action(dom: &Html) -> Option<String> {
+    dom.select(&SELECTOR_COMMENT_FORM)
+        .next()
+        .and_then(|el| el.value().attr("action"))
+        .map(|s| s.to_owned())
+}
+
 pub fn is_locked(dom: &Html) -> bool {
     dom.select(&SELECTOR_PASSWORD_FORM).next().is_some()
 }
@@ -338,6 +369,20 @@ mod tests {
         assert_eq!(get_csrftoken(&dom), Some("token".to_string()));
     }
 
+    #[test]
+    fn test_get_comment_action() {
+        let dom = Html::parse_document(
+            r#"
+            <form action="/search" method="get"></form>
+            <form id="w1" action="/comment/abc123" method="post"></form>
+        "#,
+        );
+        assert_eq!(get_comment_action(&dom), Some("/comment/abc123".to_string()));
+
+        let dom = Html::parse_document(r#"<form action="/search"></form>"#);
+        assert_eq!(get_comment_action(&dom), None);
+    }
+
     #[test]
     fn test_parse_date() {
         let result = parse_date("Thursday 2nd of May 2024 10:05:29 AM CDT");
@@ -355,7 +400,7 @@ mod tests {
         "#,
         );
 
-        assert_eq!(is_locked(&dom), true);
+        assert!(is_locked(&dom));
     }
 
     #[test]
@@ -366,6 +411,6 @@ mod tests {
         "#,
         );
 
-        assert_eq!(is_burn(&dom), true);
+        assert!(is_burn(&dom));
     }
 }
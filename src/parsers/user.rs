@@ -1,11 +1,12 @@
 use once_cell::sync::Lazy;
 use scraper::{ElementRef, Html, Selector};
-use serde::Serialize;
-
-use crate::constants::URL;
+use serde::{Deserialize, Serialize};
 
 use super::{FromElement, FromHtml, parse_date};
-use super::utils::{safe_text_content, safe_attr_content, safe_select, safe_parse_number};
+use super::utils::{
+    SELECTOR_META_OG_URL, SELECTOR_USER_ICON_IMG, safe_attr_content, safe_parse_number,
+    safe_select, safe_text_content,
+};
 
 // Helper function to safely parse dates with fallback to 0
 fn safe_parse_date(date_str: &str) -> i64 {
@@ -16,12 +17,8 @@ fn safe_parse_date(date_str: &str) -> i64 {
 }
 
 // Pre-compiled selectors to avoid unwrap() calls
-static SELECTOR_META_OG_URL: Lazy<Selector> =
-    Lazy::new(|| Selector::parse("meta[property='og:url']").expect("Valid CSS selector"));
 static SELECTOR_USER_VIEW: Lazy<Selector> =
     Lazy::new(|| Selector::parse(".user-view").expect("Valid CSS selector"));
-static SELECTOR_USER_ICON_IMG: Lazy<Selector> =
-    Lazy::new(|| Selector::parse(".user-icon>img").expect("Valid CSS selector"));
 static SELECTOR_WEB: Lazy<Selector> =
     Lazy::new(|| Selector::parse(".web").expect("Valid CSS selector"));
 static SELECTOR_LOCATION: Lazy<Selector> =
@@ -55,15 +52,15 @@ static SELECTOR_TD_CHILD_5: Lazy<Selector> =
 static SELECTOR_TD_CHILD_6_A: Lazy<Selector> =
     Lazy::new(|| Selector::parse("td:nth-child(6)>a").expect("Valid CSS selector"));
 
-#[derive(Serialize)]
+#[derive(Serialize, Clone)]
 pub struct UserPaste {
-    id: String,
-    title: String,
-    age: String,
-    expires: String,
-    views: u32,
-    num_comments: u32,
-    format: String,
+    pub(crate) id: String,
+    pub(crate) title: String,
+    pub(crate) age: String,
+    pub(crate) expires: String,
+    pub(crate) views: u32,
+    pub(crate) num_comments: u32,
+    pub(crate) format: String,
 }
 
 impl FromElement for UserPaste {
@@ -100,25 +97,25 @@ impl FromElement for UserPaste {
     }
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Clone)]
 pub struct User {
-    username: String,
-    icon_url: String,
-    website: Option<String>,
-    location: Option<String>,
-    profile_views: u32,
-    paste_views: u32,
-    rating: f32,
-    date_joined: i64,
-    pro: bool,
-    pastes: Vec<UserPaste>,
+    pub(crate) username: String,
+    pub(crate) icon_url: String,
+    pub(crate) website: Option<String>,
+    pub(crate) location: Option<String>,
+    pub(crate) profile_views: u32,
+    pub(crate) paste_views: u32,
+    pub(crate) rating: f32,
+    pub(crate) date_joined: i64,
+    pub(crate) pro: bool,
+    pub(crate) pastes: Vec<UserPaste>,
 }
 
 impl FromHtml for User {
-    fn from_html(dom: &Html) -> Result<Self, String> {
+    fn from_html(dom: &Html, base_url: &str) -> Result<Self, String> {
         let meta_element = dom.select(&SELECTOR_META_OG_URL).next();
         let username = safe_attr_content(meta_element, "content")
-            .replace(&format!("{URL}/u/"), "");
+            .replace(&format!("{base_url}/u/"), "");
 
         let parent = match dom.select(&SELECTOR_USER_VIEW).next() {
             Some(p) => p,
@@ -164,7 +161,7 @@ impl FromHtml for User {
 
         let date_joined = safe_select(&parent, &SELECTOR_DATE_TEXT)
             .and_then(|el| el.value().attr("title"))
-            .map(|date_str| safe_parse_date(date_str))
+            .map(safe_parse_date)
             .unwrap_or(0);
 
         let pro = safe_select(&parent, &SELECTOR_PRO).is_some();
@@ -191,12 +188,12 @@ impl FromHtml for User {
     }
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct SimpleUser {
-    username: String,
-    registered: bool,
-    pro: bool,
-    icon_url: String,
+    pub(crate) username: String,
+    pub(crate) registered: bool,
+    pub(crate) pro: bool,
+    pub(crate) icon_url: String,
 }
 
 impl FromElement for SimpleUser {
@@ -226,6 +223,8 @@ impl FromElement for SimpleUser {
 mod tests {
     use scraper::{Html, Selector};
 
+    use crate::constants::URL;
+
     use super::*;
 
     #[test]
@@ -252,8 +251,8 @@ mod tests {
         .expect("Should not error");
 
         assert_eq!(user.username, "user");
-        assert_eq!(user.registered, true);
-        assert_eq!(user.pro, true);
+        assert!(user.registered);
+        assert!(user.pro);
         assert_eq!(user.icon_url, "/imgs/user.png");
     }
 
@@ -279,7 +278,7 @@ mod tests {
 
         // Should not panic and should return default values
         assert_eq!(user.username, "");
-        assert_eq!(user.registered, false);
+        assert!(!user.registered);
         assert_eq!(user.icon_url, "/imgs/user.png");
     }
 
@@ -324,7 +323,7 @@ mod tests {
         "#,
         );
 
-        let user = User::from_html(&dom).expect("Should not error");
+        let user = User::from_html(&dom, URL).expect("Should not error");
 
         // Should not panic and should return default values
         assert_eq!(user.username, "testuser");
@@ -333,8 +332,25 @@ mod tests {
         assert_eq!(user.paste_views, 0);
         assert_eq!(user.rating, 0.0);
         assert_eq!(user.date_joined, 0);
-        assert_eq!(user.pro, false);
-        assert_eq!(user.pastes.len(), 0);
+        assert!(!user.pro);
+        assert!(user.pastes.is_empty());
+    }
+
+    #[test]
+    fn test_parse_user_with_custom_base_url() {
+        let dom = Html::parse_document(
+            r#"
+            <html>
+                <head>
+                    <meta property="og:url" content="http://localhost:8080/u/testuser">
+                </head>
+            </html>
+        "#,
+        );
+
+        let user = User::from_html(&dom, "http://localhost:8080").expect("Should not error");
+
+        assert_eq!(user.username, "testuser");
     }
 
     #[test]
@@ -354,7 +370,7 @@ mod tests {
         "#,
         );
 
-        let user = User::from_html(&dom).expect("Should not error");
+        let user = User::from_html(&dom, URL).expect("Should not error");
 
         // Should not panic, username will be empty or default
         assert_eq!(user.username, "");
@@ -381,7 +397,7 @@ mod tests {
         "#,
         );
 
-        let user = User::from_html(&dom).expect("Should not error");
+        let user = User::from_html(&dom, URL).expect("Should not error");
 
         // Should not panic and should use default values for missing stats
         assert_eq!(user.username, "testuser");
@@ -22,12 +22,6 @@ pub fn safe_attr_content(element: Option<ElementRef>, attr: &str) -> String {
         .to_owned()
 }
 
-/// Helper function to safely parse a CSS selector
-pub fn safe_selector(selector_str: &str) -> Result<Selector, String> {
-    Selector::parse(selector_str)
-        .map_err(|e| format!("Invalid CSS selector '{}': {:?}", selector_str, e))
-}
-
 /// Helper function to safely select the first element matching a selector
 pub fn safe_select<'a>(
     parent: &'a ElementRef,
@@ -37,9 +31,9 @@ pub fn safe_select<'a>(
 }
 
 /// Helper function to safely parse a number from text with a default fallback
-pub fn safe_parse_number<T: std::str::FromStr>(text: &str) -> T
+pub fn safe_parse_number<T>(text: &str) -> T
 where
-    T: Default,
+    T: std::str::FromStr + Default,
 {
     text.trim()
         .replace(",", "")
@@ -0,0 +1,86 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+// Windows are swept once this many visitors are tracked
+const SWEEP_AT: usize = 1024;
+
+/// Allows each visitor `limit` actions per `window`.
+///
+/// Visitors are told apart by a key of the caller's choosing, and forgotten
+/// once their window has passed.
+pub struct RateLimiter {
+    limit: u32,
+    window: Duration,
+    visitors: Mutex<HashMap<String, Window>>,
+}
+
+struct Window {
+    started: Instant,
+    count: u32,
+}
+
+impl RateLimiter {
+    pub fn new(limit: u32, window: Duration) -> Self {
+        Self {
+            limit,
+            window,
+            visitors: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Counts an action by `visitor`, or returns how long until they may act again
+    pub fn check(&self, visitor: &str) -> Result<(), Duration> {
+        let mut visitors = self.visitors.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+
+        if visitors.len() >= SWEEP_AT {
+            visitors.retain(|_, window| now.duration_since(window.started) < self.window);
+        }
+
+        let window = visitors.entry(visitor.to_string()).or_insert(Window {
+            started: now,
+            count: 0,
+        });
+        if now.duration_since(window.started) >= self.window {
+            *window = Window {
+                started: now,
+                count: 0,
+            };
+        }
+
+        if window.count >= self.limit {
+            return Err(self.window - now.duration_since(window.started));
+        }
+
+        window.count += 1;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limits_each_visitor() {
+        let limiter = RateLimiter::new(2, Duration::from_secs(60));
+
+        assert!(limiter.check("alice").is_ok());
+        assert!(limiter.check("alice").is_ok());
+        let remaining = limiter.check("alice").expect_err("Limit reached");
+        assert!(remaining <= Duration::from_secs(60));
+
+        assert!(limiter.check("bob").is_ok());
+    }
+
+    #[test]
+    fn test_window_resets() {
+        let limiter = RateLimiter::new(1, Duration::ZERO);
+
+        assert!(limiter.check("alice").is_ok());
+        assert!(limiter.check("alice").is_ok());
+    }
+}
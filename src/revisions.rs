@@ -0,0 +1,298 @@
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sled::{Db, Tree};
+use std::time::Duration;
+
+use crate::parsers::paste::Paste;
+
+/// A distinct version of a paste's content
+#[derive(Serialize, Deserialize)]
+pub struct Revision {
+    /// When this version was first seen, in milliseconds since the epoch and
+    /// unique among the revisions of a paste
+    pub stamp: i64,
+    /// The edit date upstream reported alongside this version
+    pub edit_date: Option<i64>,
+    pub content: String,
+}
+
+/// Every distinct content version seen for each paste, oldest first.
+///
+/// Revisions older than `max_age` are dropped, as are all but the newest
+/// `max_per_paste` revisions of each paste. Zero disables either limit.
+pub struct RevisionStore {
+    tree: Tree,
+    max_age: i64,
+    max_per_paste: usize,
+}
+
+impl RevisionStore {
+    pub fn new(db: &Db, max_age: Duration, max_per_paste: usize) -> sled::Result<Self> {
+        Ok(Self {
+            tree: db.open_tree("revisions")?,
+            max_age: max_age.as_millis() as i64,
+            max_per_paste,
+        })
+    }
+
+    /// Stores the content of `paste` if it differs from the newest revision of `id`
+    pub fn record(&self, id: &str, paste: &Paste) -> sled::Result<()> {
+        if self
+            .latest(id)
+            .is_some_and(|latest| latest.content == paste.content())
+        {
+            return Ok(());
+        }
+
+        // Stamps only move forward, so versions seen within a millisecond never share a key
+        let newest = self
+            .tree
+            .scan_prefix(prefix(id))
+            .keys()
+            .next_back()
+            .and_then(|key| stamp(&key.ok()?));
+        let now = Utc::now().timestamp_millis();
+
+        let revision = Revision {
+            stamp: newest.map_or(now, |newest| now.max(newest + 1)),
+            edit_date: paste.edit_date(),
+            content: paste.content().to_string(),
+        };
+        let Ok(bytes) = serde_json::to_vec(&revision) else {
+            return Ok(());
+        };
+
+        self.tree.insert(key(id, revision.stamp), bytes)?;
+        self.prune_paste(id)
+    }
+
+    /// All revisions of `id`, newest first
+    pub fn list(&self, id: &str) -> Vec<Revision> {
+        let mut revisions = self
+            .tree
+            .scan_prefix(prefix(id))
+            .values()
+            .filter_map(|value| serde_json::from_slice::<Revision>(&value.ok()?).ok())
+            .filter(|revision| !self.is_expired(revision.stamp))
+            .collect::<Vec<_>>();
+
+        revisions.reverse();
+        revisions
+    }
+
+    pub fn latest(&self, id: &str) -> Option<Revision> {
+        let (_, value) = self.tree.scan_prefix(prefix(id)).next_back()?.ok()?;
+        serde_json::from_slice::<Revision>(&value)
+            .ok()
+            .filter(|revision| !self.is_expired(revision.stamp))
+    }
+
+    /// Drops every revision of `id`, e.g. once its author deleted it
+    pub fn remove(&self, id: &str) -> sled::Result<()> {
+        for key in self.tree.scan_prefix(prefix(id)).keys() {
+            self.tree.remove(key?)?;
+        }
+
+        Ok(())
+    }
+
+    /// Drops every revision older than the retention period and returns how many were removed
+    pub fn prune(&self) -> sled::Result<usize> {
+        if self.max_age == 0 {
+            return Ok(0);
+        }
+
+        let mut removed = 0;
+        for key in self.tree.iter().keys() {
+            let key = key?;
+            if stamp(&key).is_none_or(|at| self.is_expired(at)) {
+                self.tree.remove(key)?;
+                removed += 1;
+            }
+        }
+
+        Ok(removed)
+    }
+
+    fn prune_paste(&self, id: &str) -> sled::Result<()> {
+        let keys = self
+            .tree
+            .scan_prefix(prefix(id))
+            .keys()
+            .collect::<sled::Result<Vec<_>>>()?;
+
+        let excess = match self.max_per_paste {
+            0 => 0,
+            max => keys.len().saturating_sub(max),
+        };
+
+        for (i, key) in keys.into_iter().enumerate() {
+            let expired = stamp(&key).is_none_or(|at| self.is_expired(at));
+            if i < excess || expired {
+                self.tree.remove(key)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn is_expired(&self, stamp: i64) -> bool {
+        self.max_age > 0 && Utc::now().timestamp_millis() - stamp >= self.max_age
+    }
+}
+
+fn prefix(id: &str) -> String {
+    format!("{id}/")
+}
+
+// Zero padded so keys sort by time
+fn key(id: &str, stamp: i64) -> String {
+    format!("{id}/{stamp:020}")
+}
+
+fn stamp(key: &[u8]) -> Option<i64> {
+    let key = std::str::from_utf8(key).ok()?;
+    key.rsplit_once('/')?.1.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn paste(content: &str) -> Paste {
+        serde_json::from_value(json!({
+            "id": "abc",
+            "title": null,
+            "tags": [],
+            "container": {
+                "category": null,
+                "size": 0,
+                "likes": null,
+                "dislikes": null,
+                "id": null,
+                "format": "text",
+                "format_name": "Plain Text",
+                "content": content,
+            },
+            "author": {
+                "username": "Guest",
+                "registered": false,
+                "pro": false,
+                "icon_url": "",
+            },
+            "date": 0,
+            "edit_date": null,
+            "views": 0,
+            "rating": 0.0,
+            "expire": "Never",
+            "comment_for": null,
+            "unlisted": false,
+            "num_comments": 0,
+            "comments": [],
+            "locked": false,
+        }))
+        .expect("Valid paste")
+    }
+
+    fn store(max_age: u64, max_per_paste: usize) -> RevisionStore {
+        let db = sled::Config::default()
+            .temporary(true)
+            .open()
+            .expect("Temporary database");
+        RevisionStore::new(&db, Duration::from_secs(max_age), max_per_paste).expect("Revision tree")
+    }
+
+    #[test]
+    fn test_only_distinct_content_is_recorded() {
+        let store = store(0, 0);
+
+        store.record("abc", &paste("one")).expect("Record");
+        store.record("abc", &paste("one")).expect("Record");
+        assert_eq!(store.list("abc").len(), 1);
+
+        store
+            .tree
+            .insert(
+                key("abc", 0),
+                serde_json::to_vec(&Revision {
+                    stamp: 0,
+                    edit_date: None,
+                    content: "zero".to_string(),
+                })
+                .unwrap(),
+            )
+            .unwrap();
+
+        let contents = store
+            .list("abc")
+            .into_iter()
+            .map(|r| r.content)
+            .collect::<Vec<_>>();
+        assert_eq!(contents, vec!["one", "zero"]);
+    }
+
+    #[test]
+    fn test_versions_seen_in_one_second_are_kept_apart() {
+        let store = store(0, 0);
+
+        store.record("abc", &paste("one")).expect("Record");
+        store.record("abc", &paste("two")).expect("Record");
+
+        let revisions = store.list("abc");
+        let contents = revisions
+            .iter()
+            .map(|r| r.content.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(contents, vec!["two", "one"]);
+        assert!(revisions[0].stamp > revisions[1].stamp);
+    }
+
+    #[test]
+    fn test_oldest_revisions_are_pruned() {
+        let store = store(0, 1);
+
+        store
+            .tree
+            .insert(
+                key("abc", 0),
+                serde_json::to_vec(&Revision {
+                    stamp: 0,
+                    edit_date: None,
+                    content: "old".to_string(),
+                })
+                .unwrap(),
+            )
+            .unwrap();
+        store.record("abc", &paste("new")).expect("Record");
+
+        let revisions = store.list("abc");
+        assert_eq!(revisions.len(), 1);
+        assert_eq!(revisions[0].content, "new");
+    }
+
+    #[test]
+    fn test_expired_revisions_are_pruned() {
+        let store = store(60, 0);
+
+        store
+            .tree
+            .insert(
+                key("abc", 0),
+                serde_json::to_vec(&Revision {
+                    stamp: 0,
+                    edit_date: None,
+                    content: "old".to_string(),
+                })
+                .unwrap(),
+            )
+            .unwrap();
+        store.record("def", &paste("new")).expect("Record");
+        assert!(store.latest("abc").is_none());
+
+        assert_eq!(store.prune().expect("Prune"), 1);
+        assert!(store.tree.get(key("abc", 0)).unwrap().is_none());
+        assert_eq!(store.list("def").len(), 1);
+    }
+}
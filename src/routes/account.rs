@@ -0,0 +1,336 @@
+use axum::{
+    Form, Router,
+    body::Body,
+    extract::{ConnectInfo, Query, State},
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Redirect, Response},
+    routing,
+};
+use serde::Deserialize;
+use std::net::SocketAddr;
+use tera::Context;
+
+use crate::{
+    backends::{Account, BackendError},
+    sessions,
+    state::AppState,
+    templates::TEMPLATES,
+};
+
+use super::actions::is_local;
+use super::error::{self, AppError, Error};
+
+#[derive(Deserialize)]
+struct Login {
+    username: String,
+    password: String,
+    #[serde(default)]
+    next: String,
+}
+
+/// Where to send the visitor once signed in
+#[derive(Deserialize)]
+pub(super) struct Next {
+    #[serde(default)]
+    next: String,
+}
+
+// Only paths on this instance, so the form can't redirect anywhere else
+fn local_path(next: &str) -> &str {
+    if next.starts_with('/') && !next.starts_with("//") && !next.contains('\\') {
+        next
+    } else {
+        "/"
+    }
+}
+
+/// Sends visitors who are not signed in to the login form, and back to `path` after
+pub(super) fn login_redirect(path: &str) -> Redirect {
+    Redirect::to(&format!("/login?next={path}"))
+}
+
+pub fn get_router(state: AppState) -> Router {
+    Router::new()
+        .route("/login", routing::get(login).post(login_submit))
+        .route("/logout", routing::post(logout))
+        .with_state(state)
+}
+
+fn session_id(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .find_map(|pair| {
+            let (name, value) = pair.trim().split_once('=')?;
+            (name == sessions::COOKIE).then_some(value)
+        })
+}
+
+/// The upstream account of the visitor sending `headers`, if they signed in
+pub(super) fn signed_in(state: &AppState, headers: &HeaderMap) -> Option<Account> {
+    let id = session_id(headers)?;
+    state.sessions.get(id).map(|session| session.account)
+}
+
+// Marked `Secure` when a proxy on `peer` reached the instance over HTTPS
+fn session_cookie(headers: &HeaderMap, peer: SocketAddr, value: &str, max_age: u64) -> String {
+    let secure = is_local(peer.ip())
+        && headers
+            .get("X-Forwarded-Proto")
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|proto| proto.eq_ignore_ascii_case("https"));
+
+    format!(
+        "{}={value}; Path=/; Max-Age={max_age}; HttpOnly; SameSite=Lax{}",
+        sessions::COOKIE,
+        if secure { "; Secure" } else { "" }
+    )
+}
+
+/// The error sent for a form posted from another site, if `headers` show one.
+///
+/// Browsers send `Origin` with every form they post, so requests without one
+/// come from other clients and are let through.
+pub(super) fn cross_origin(headers: &HeaderMap) -> Option<Response<Body>> {
+    let origin = headers.get(header::ORIGIN)?;
+    let origin_host = origin
+        .to_str()
+        .ok()
+        .and_then(|origin| origin.split_once("://"))
+        .map(|(_, host)| host);
+
+    let same_origin = origin_host.is_some_and(|origin_host| {
+        [header::HOST.as_str(), "X-Forwarded-Host"]
+            .into_iter()
+            .filter_map(|name| headers.get(name)?.to_str().ok())
+            .filter_map(|value| value.split(',').next())
+            .any(|host| host.trim().eq_ignore_ascii_case(origin_host))
+    });
+    if same_origin {
+        return None;
+    }
+
+    Some(error::render_error(Error::from(AppError::Custom {
+        status: StatusCode::FORBIDDEN.as_u16(),
+        message: "Forms can only be sent from this site".to_string(),
+    })))
+}
+
+fn render_login(context: &Context, status: StatusCode) -> Response<Body> {
+    match TEMPLATES.render("login.html", context) {
+        Ok(html) => (
+            status,
+            [
+                (header::CONTENT_TYPE, "text/html"),
+                (header::CACHE_CONTROL, "private, no-store"),
+            ],
+            html,
+        )
+            .into_response(),
+        Err(e) => error::render_error(Error::from(e)),
+    }
+}
+
+async fn login(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<Next>,
+) -> Response<Body> {
+    let mut context = Context::new();
+    context.insert("next", local_path(&query.next));
+    if let Some(account) = signed_in(&state, &headers) {
+        context.insert("signed_in_as", &account.username);
+    }
+
+    render_login(&context, StatusCode::OK)
+}
+
+async fn login_submit(
+    State(state): State<AppState>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Form(data): Form<Login>,
+) -> Response<Body> {
+    if let Some(response) = cross_origin(&headers) {
+        return response;
+    }
+
+    let account = match state.backend.login(&data.username, &data.password).await {
+        Ok(account) => account,
+        Err(BackendError::Rejected(message)) => {
+            let mut context = Context::new();
+            context.insert("error", &message);
+            context.insert("username", &data.username);
+            context.insert("next", local_path(&data.next));
+            return render_login(&context, StatusCode::UNAUTHORIZED);
+        }
+        Err(err) => return error::construct_error(err),
+    };
+
+    let id = match state.sessions.create(account) {
+        Ok(id) => id,
+        Err(e) => return error::render_error(Error::from(AppError::Io(e))),
+    };
+
+    // Replace any session this browser had before
+    if let Some(old) = session_id(&headers) {
+        state.sessions.remove(old);
+    }
+
+    let cookie = session_cookie(&headers, peer, &id, state.sessions.max_age().as_secs());
+    (
+        [(header::SET_COOKIE, cookie)],
+        Redirect::to(local_path(&data.next)),
+    )
+        .into_response()
+}
+
+async fn logout(
+    State(state): State<AppState>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> Response<Body> {
+    if let Some(response) = cross_origin(&headers) {
+        return response;
+    }
+
+    if let Some(id) = session_id(&headers) {
+        state.sessions.remove(id);
+    }
+
+    let cookie = session_cookie(&headers, peer, "", 0);
+    ([(header::SET_COOKIE, cookie)], Redirect::to("/")).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+    use std::sync::Arc;
+
+    use crate::backends::mock::MockBackend;
+
+    use super::*;
+
+    fn state() -> AppState {
+        AppState::with_backend(Arc::new(MockBackend::default()))
+    }
+
+    fn peer(ip: [u8; 4]) -> SocketAddr {
+        SocketAddr::from((ip, 40000))
+    }
+
+    fn login_form(password: &str) -> Form<Login> {
+        Form(Login {
+            username: "alice".to_string(),
+            password: password.to_string(),
+            next: "/edit/abc123".to_string(),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_login_starts_a_session() {
+        let state = state();
+
+        let response = login_submit(
+            State(state.clone()),
+            ConnectInfo(peer([203, 0, 113, 1])),
+            HeaderMap::new(),
+            login_form("hunter2"),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        assert_eq!(
+            response.headers().get(header::LOCATION),
+            Some(&HeaderValue::from_static("/edit/abc123"))
+        );
+
+        let cookie = response
+            .headers()
+            .get(header::SET_COOKIE)
+            .and_then(|v| v.to_str().ok())
+            .expect("Session cookie");
+        assert!(cookie.contains("HttpOnly"));
+
+        let mut headers = HeaderMap::new();
+        let pair = cookie.split(';').next().expect("Cookie pair");
+        headers.insert(
+            header::COOKIE,
+            HeaderValue::from_str(&format!("theme=dark; {pair}")).expect("Valid header"),
+        );
+        assert_eq!(
+            signed_in(&state, &headers).map(|account| account.username),
+            Some("alice".to_string())
+        );
+
+        logout(
+            State(state.clone()),
+            ConnectInfo(peer([203, 0, 113, 1])),
+            headers.clone(),
+        )
+        .await;
+        assert!(signed_in(&state, &headers).is_none());
+    }
+
+    #[test]
+    fn test_next_stays_on_this_instance() {
+        assert_eq!(local_path("/edit/abc123"), "/edit/abc123");
+        assert_eq!(local_path("//evil.example"), "/");
+        assert_eq!(local_path("/\\evil.example"), "/");
+        assert_eq!(local_path("https://evil.example"), "/");
+        assert_eq!(local_path(""), "/");
+    }
+
+    #[tokio::test]
+    async fn test_rejected_login_shows_the_form_again() {
+        let response = login_submit(
+            State(state()),
+            ConnectInfo(peer([203, 0, 113, 1])),
+            HeaderMap::new(),
+            login_form("wrong"),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert!(response.headers().get(header::SET_COOKIE).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_logins_from_other_sites_are_rejected() {
+        let state = state();
+        let headers = |origin: &'static str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::HOST, HeaderValue::from_static("pasted.example"));
+            headers.insert(header::ORIGIN, HeaderValue::from_static(origin));
+            headers
+        };
+
+        let response = login_submit(
+            State(state.clone()),
+            ConnectInfo(peer([203, 0, 113, 1])),
+            headers("https://evil.example"),
+            login_form("hunter2"),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let response = login_submit(
+            State(state),
+            ConnectInfo(peer([203, 0, 113, 1])),
+            headers("https://pasted.example"),
+            login_form("hunter2"),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    }
+
+    #[test]
+    fn test_secure_cookies_only_follow_local_proxies() {
+        let mut headers = HeaderMap::new();
+        headers.insert("X-Forwarded-Proto", HeaderValue::from_static("https"));
+
+        assert!(session_cookie(&headers, peer([127, 0, 0, 1]), "id", 60).ends_with("; Secure"));
+        assert!(!session_cookie(&headers, peer([203, 0, 113, 1]), "id", 60).contains("Secure"));
+    }
+}
@@ -0,0 +1,302 @@
+use axum::{
+    Form, Router,
+    body::Body,
+    extract::{ConnectInfo, Path, State},
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Redirect, Response},
+    routing,
+};
+use serde::Deserialize;
+use std::net::{IpAddr, SocketAddr};
+use tera::Context;
+
+use crate::{
+    backends::{Account, Rating},
+    state::AppState,
+    templates::TEMPLATES,
+};
+
+use super::account::{cross_origin, signed_in};
+use super::error::{self, AppError, Error};
+
+/// Longest report reason passed on to upstream
+const MAX_REASON_CHARS: usize = 1000;
+
+#[derive(Deserialize)]
+struct Report {
+    reason: String,
+}
+
+pub fn get_router(state: AppState) -> Router {
+    Router::new()
+        .route("/like/{id}", routing::post(like))
+        .route("/dislike/{id}", routing::post(dislike))
+        .route("/report/{id}", routing::get(report).post(report_submit))
+        .with_state(state)
+}
+
+// Addresses of a reverse proxy in front of the instance, the only peers
+// trusted to say who they forward for
+pub(super) fn is_local(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => ip.is_loopback() || ip.is_private(),
+        IpAddr::V6(ip) => ip.is_loopback() || ip.is_unique_local(),
+    }
+}
+
+/// Who an action counts against: the signed-in account, or else the address
+/// the request came from
+fn visitor(account: Option<&Account>, headers: &HeaderMap, peer: SocketAddr) -> String {
+    if let Some(account) = account {
+        return format!("account:{}", account.username);
+    }
+
+    // The nearest proxy appends the address it was reached from
+    let forwarded = is_local(peer.ip())
+        .then(|| headers.get("X-Forwarded-For")?.to_str().ok())
+        .flatten()
+        .and_then(|value| value.rsplit(',').next())
+        .and_then(|ip| ip.trim().parse::<IpAddr>().ok());
+
+    format!("ip:{}", forwarded.unwrap_or(peer.ip()))
+}
+
+/// Counts an action by `visitor`, or renders the error telling them to wait
+fn throttled(state: &AppState, visitor: &str) -> Option<Response<Body>> {
+    state.actions.check(visitor).err().map(|retry_after| {
+        // Round up so visitors never retry before the window ends
+        let retry_after = retry_after.as_secs() + 1;
+        error::render_error(
+            Error::from(AppError::Custom {
+                status: StatusCode::TOO_MANY_REQUESTS.as_u16(),
+                message: format!("Too many actions, try again in {retry_after} seconds"),
+            })
+            .with_retry_after(retry_after),
+        )
+    })
+}
+
+fn render_report(context: &Context) -> Response<Body> {
+    match TEMPLATES.render("report.html", context) {
+        Ok(html) => (
+            StatusCode::OK,
+            [
+                (header::CONTENT_TYPE, "text/html"),
+                (header::CACHE_CONTROL, "private, no-store"),
+            ],
+            html,
+        )
+            .into_response(),
+        Err(e) => error::render_error(Error::from(e)),
+    }
+}
+
+async fn rate(
+    state: AppState,
+    peer: SocketAddr,
+    id: String,
+    headers: HeaderMap,
+    rating: Rating,
+) -> Response<Body> {
+    if let Some(response) = cross_origin(&headers) {
+        return response;
+    }
+
+    let account = signed_in(&state, &headers);
+    if let Some(response) = throttled(&state, &visitor(account.as_ref(), &headers, peer)) {
+        return response;
+    }
+
+    match state
+        .backend
+        .rate_paste(&id, rating, account.as_ref())
+        .await
+    {
+        Ok(()) => Redirect::to(&format!("/{id}")).into_response(),
+        Err(err) => error::construct_error(err),
+    }
+}
+
+async fn like(
+    State(state): State<AppState>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Response<Body> {
+    rate(state, peer, id, headers, Rating::Like).await
+}
+
+async fn dislike(
+    State(state): State<AppState>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Response<Body> {
+    rate(state, peer, id, headers, Rating::Dislike).await
+}
+
+async fn report(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Response<Body> {
+    let mut context = Context::new();
+    context.insert("id", &id);
+    context.insert("max_reason_chars", &MAX_REASON_CHARS);
+    if let Some(account) = signed_in(&state, &headers) {
+        context.insert("signed_in_as", &account.username);
+    }
+
+    render_report(&context)
+}
+
+async fn report_submit(
+    State(state): State<AppState>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    Form(data): Form<Report>,
+) -> Response<Body> {
+    if let Some(response) = cross_origin(&headers) {
+        return response;
+    }
+
+    let reason = data.reason.trim();
+    if reason.is_empty() || reason.chars().count() > MAX_REASON_CHARS {
+        return error::render_error(Error::from(AppError::Custom {
+            status: StatusCode::BAD_REQUEST.as_u16(),
+            message: format!("Reasons must be 1 to {MAX_REASON_CHARS} characters long"),
+        }));
+    }
+
+    let account = signed_in(&state, &headers);
+    if let Some(response) = throttled(&state, &visitor(account.as_ref(), &headers, peer)) {
+        return response;
+    }
+
+    match state
+        .backend
+        .report_paste(&id, reason, account.as_ref())
+        .await
+    {
+        Ok(()) => {
+            let mut context = Context::new();
+            context.insert("id", &id);
+            context.insert("reported", &true);
+            render_report(&context)
+        }
+        Err(err) => error::construct_error(err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+    use std::{sync::Arc, time::Duration};
+
+    use crate::{backends::mock::MockBackend, ratelimit::RateLimiter};
+
+    use super::*;
+
+    fn state(limit: u32) -> AppState {
+        let mut state = AppState::with_backend(Arc::new(MockBackend::default()));
+        state.actions = Arc::new(RateLimiter::new(limit, Duration::from_secs(60)));
+        state
+    }
+
+    fn peer(ip: [u8; 4]) -> SocketAddr {
+        SocketAddr::from((ip, 40000))
+    }
+
+    #[tokio::test]
+    async fn test_likes_are_rate_limited() {
+        let state = state(1);
+        let like = |ip| {
+            like(
+                State(state.clone()),
+                ConnectInfo(peer(ip)),
+                Path("abc123".to_string()),
+                HeaderMap::new(),
+            )
+        };
+
+        let response = like([203, 0, 113, 1]).await;
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        assert_eq!(
+            response.headers().get(header::LOCATION),
+            Some(&HeaderValue::from_static("/abc123"))
+        );
+
+        let response = like([203, 0, 113, 1]).await;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(response.headers().contains_key("Retry-After"));
+
+        let response = like([203, 0, 113, 2]).await;
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    }
+
+    #[tokio::test]
+    async fn test_actions_from_other_sites_are_rejected() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::HOST, HeaderValue::from_static("pasted.example"));
+        headers.insert(
+            header::ORIGIN,
+            HeaderValue::from_static("https://evil.example"),
+        );
+
+        let response = dislike(
+            State(state(10)),
+            ConnectInfo(peer([203, 0, 113, 1])),
+            Path("abc123".to_string()),
+            headers,
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[test]
+    fn test_forwarded_for_is_only_trusted_from_proxies() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "X-Forwarded-For",
+            HeaderValue::from_static("198.51.100.7, 203.0.113.9"),
+        );
+
+        assert_eq!(
+            visitor(None, &headers, peer([127, 0, 0, 1])),
+            "ip:203.0.113.9"
+        );
+        assert_eq!(
+            visitor(None, &headers, peer([198, 51, 100, 1])),
+            "ip:198.51.100.1"
+        );
+        assert_eq!(
+            visitor(None, &HeaderMap::new(), peer([10, 0, 0, 2])),
+            "ip:10.0.0.2"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reports_need_a_reason() {
+        let report = |reason: &str| {
+            report_submit(
+                State(state(10)),
+                ConnectInfo(peer([203, 0, 113, 1])),
+                Path("abc123".to_string()),
+                HeaderMap::new(),
+                Form(Report {
+                    reason: reason.to_string(),
+                }),
+            )
+        };
+
+        assert_eq!(report("  ").await.status(), StatusCode::BAD_REQUEST);
+
+        let response = report("Leaked credentials").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .expect("Body");
+        assert!(String::from_utf8_lossy(&body).contains("Report sent"));
+    }
+}
@@ -0,0 +1,243 @@
+//! Stand-ins for pastebin's developer API (`api_post.php` and `api_raw.php`),
+//! so existing tools can be pointed at Pasted without a developer key.
+//!
+//! Like pastebin, every answer is plain text and failures start with
+//! "Bad API request". Keys (`api_dev_key`, `api_user_key`) are accepted and
+//! ignored.
+
+use axum::{
+    Form,
+    body::Body,
+    extract::{ConnectInfo, State, rejection::FormRejection},
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use std::net::SocketAddr;
+
+use crate::{
+    backends::{BackendError, NewPaste},
+    catalog,
+    state::AppState,
+};
+
+use super::super::post::validate;
+use super::create::origin;
+
+/// Expiration codes accepted by `api_paste_expire_date`
+const EXPIRATIONS: &[&str] = &["N", "10M", "1H", "1D", "1W", "2W", "1M", "6M", "1Y"];
+
+#[derive(Deserialize)]
+pub struct ApiRequest {
+    api_option: Option<String>,
+    api_paste_code: Option<String>,
+    api_paste_name: Option<String>,
+    api_paste_format: Option<String>,
+    api_paste_private: Option<String>,
+    api_paste_expire_date: Option<String>,
+    api_paste_key: Option<String>,
+}
+
+fn text(status: StatusCode, body: String) -> Response<Body> {
+    (status, [(header::CONTENT_TYPE, "text/plain")], body).into_response()
+}
+
+fn bad_request(reason: &str) -> Response<Body> {
+    text(
+        StatusCode::UNPROCESSABLE_ENTITY,
+        format!("Bad API request, {reason}"),
+    )
+}
+
+fn upstream_error(err: BackendError) -> Response<Body> {
+    if err.is_not_found() {
+        return bad_request("invalid permission to view this paste or invalid api_paste_key");
+    }
+
+    text(StatusCode::BAD_GATEWAY, format!("Upstream failed: {err}"))
+}
+
+/// Maps a developer API paste onto the paste form's fields
+fn new_paste(request: ApiRequest) -> Result<NewPaste, &'static str> {
+    let text = request
+        .api_paste_code
+        .filter(|code| !code.trim().is_empty())
+        .ok_or("api_paste_code was empty")?;
+
+    let format = match request.api_paste_format.as_deref() {
+        None | Some("") => catalog::TEXT,
+        Some(slug) => {
+            catalog::format_by_slug(slug)
+                .ok_or("invalid api_paste_format")?
+                .id
+        }
+    };
+
+    // Private pastes need a pastebin account, which guests posting through Pasted don't have
+    let exposure = match request.api_paste_private.as_deref() {
+        None | Some("") | Some("0") => 0,
+        Some("1") => 1,
+        Some("2") => return Err("private pastes need a logged in api_user_key"),
+        Some(_) => return Err("invalid api_paste_private"),
+    };
+
+    let expiration = match request.api_paste_expire_date {
+        None => "N".to_string(),
+        Some(code) => {
+            let code = code.to_uppercase();
+            if !EXPIRATIONS.contains(&code.as_str()) {
+                return Err("invalid api_paste_expire_date");
+            }
+            code
+        }
+    };
+
+    Ok(NewPaste {
+        text,
+        category: 0,
+        tags: String::new(),
+        format,
+        expiration,
+        exposure,
+        password: String::new(),
+        title: request.api_paste_name.unwrap_or_default(),
+    })
+}
+
+async fn show_paste(state: &AppState, request: ApiRequest) -> Response<Body> {
+    let Some(key) = request.api_paste_key.filter(|key| !key.is_empty()) else {
+        return bad_request("invalid api_paste_key");
+    };
+
+    match state.backend.get_raw(&key).await {
+        Ok(raw) => text(StatusCode::OK, raw.text),
+        Err(err) => upstream_error(err),
+    }
+}
+
+/// `POST /api/api_post.php`, supporting `api_option=paste` and `show_paste`
+pub async fn api_post(
+    State(state): State<AppState>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    form: Result<Form<ApiRequest>, FormRejection>,
+) -> Response<Body> {
+    let Ok(Form(request)) = form else {
+        return bad_request("invalid POST parameters");
+    };
+
+    match request.api_option.as_deref() {
+        Some("paste") => {}
+        Some("show_paste") => return show_paste(&state, request).await,
+        _ => return bad_request("invalid api_option"),
+    }
+
+    let paste = match new_paste(request) {
+        Ok(paste) => paste,
+        Err(reason) => return bad_request(reason),
+    };
+
+    // Guests only, as keys are ignored
+    if let Err(err) = validate(&paste, false) {
+        return bad_request(err.message());
+    }
+
+    match state.backend.create_paste(paste, None).await {
+        Ok(id) => text(StatusCode::OK, format!("{}/{id}", origin(&headers, peer))),
+        Err(err) => upstream_error(err),
+    }
+}
+
+/// `POST /api/api_raw.php`, returning the content of `api_paste_key`
+pub async fn api_raw(
+    State(state): State<AppState>,
+    form: Result<Form<ApiRequest>, FormRejection>,
+) -> Response<Body> {
+    let Ok(Form(request)) = form else {
+        return bad_request("invalid POST parameters");
+    };
+
+    match request.api_option.as_deref() {
+        None | Some("show_paste") => show_paste(&state, request).await,
+        _ => bad_request("invalid api_option"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::backends::mock::MockBackend;
+
+    use super::super::super::post::MAX_PASTE_BYTES;
+    use super::*;
+
+    fn request(fields: &[(&str, &str)]) -> ApiRequest {
+        let query = fields
+            .iter()
+            .map(|(k, v)| format!("{k}={v}"))
+            .collect::<Vec<_>>()
+            .join("&");
+        let uri = format!("/?{query}").parse().expect("Valid URI");
+
+        axum::extract::Query::<ApiRequest>::try_from_uri(&uri)
+            .expect("Valid request")
+            .0
+    }
+
+    #[test]
+    fn test_new_paste() {
+        let paste = new_paste(request(&[
+            ("api_paste_code", "print(1)"),
+            ("api_paste_name", "script"),
+            ("api_paste_format", "python"),
+            ("api_paste_private", "1"),
+            ("api_paste_expire_date", "1d"),
+        ]))
+        .expect("Valid paste");
+
+        assert_eq!(paste.format, 42);
+        assert_eq!(paste.exposure, 1);
+        assert_eq!(paste.expiration, "1D");
+        assert_eq!(paste.title, "script");
+    }
+
+    #[test]
+    fn test_new_paste_rejects_invalid_fields() {
+        let reason = |fields: &[(&str, &str)]| new_paste(request(fields)).err();
+
+        assert_eq!(reason(&[]), Some("api_paste_code was empty"));
+        assert_eq!(
+            reason(&[("api_paste_code", "x"), ("api_paste_format", "nope")]),
+            Some("invalid api_paste_format")
+        );
+        assert_eq!(
+            reason(&[("api_paste_code", "x"), ("api_paste_expire_date", "B")]),
+            Some("invalid api_paste_expire_date")
+        );
+        assert_eq!(
+            reason(&[("api_paste_code", "x"), ("api_paste_private", "2")]),
+            Some("private pastes need a logged in api_user_key")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_api_post_validates_pastes() {
+        let mut paste = request(&[("api_option", "paste")]);
+        paste.api_paste_code = Some("x".repeat(MAX_PASTE_BYTES + 1));
+
+        let response = api_post(
+            State(AppState::with_backend(Arc::new(MockBackend::default()))),
+            ConnectInfo(SocketAddr::from(([203, 0, 113, 1], 40000))),
+            HeaderMap::new(),
+            Ok(Form(paste)),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .expect("Body");
+        assert!(String::from_utf8_lossy(&body).starts_with("Bad API request, The paste is 513 KB"));
+    }
+}
@@ -0,0 +1,177 @@
+use crate::{backends::NewPaste, state::AppState};
+use axum::{
+    Json,
+    body::{Body, Bytes},
+    extract::{ConnectInfo, FromRequest, Multipart, Query, Request, State},
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use std::net::SocketAddr;
+
+use super::super::account::signed_in;
+use super::super::actions::is_local;
+use super::super::error::{self, ErrorCode, ErrorSource, accept_quality, is_json};
+use super::super::post::{PasteOptions, read_multipart, resolve_format, validate};
+use super::models::{CreatePaste, Created};
+
+fn bad_request(message: impl Into<String>) -> Response<Body> {
+    error::render_error(
+        error::Error::new(
+            StatusCode::BAD_REQUEST.as_u16(),
+            message.into(),
+            ErrorSource::Internal,
+        )
+        .with_code(ErrorCode::BadRequest),
+    )
+}
+
+fn content_type(headers: &HeaderMap) -> &str {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(';').next())
+        .unwrap_or_default()
+        .trim()
+}
+
+/// Reads a paste from a JSON, multipart or raw body, raw bodies take their
+/// options from the query string
+async fn read_paste(request: Request, state: &AppState) -> Result<NewPaste, Response<Body>> {
+    let options = Query::<PasteOptions>::try_from_uri(request.uri())
+        .map_err(|e| bad_request(e.body_text()))?
+        .0;
+    let media = content_type(request.headers()).to_ascii_lowercase();
+
+    if is_json(&media) {
+        let Json(paste) = Json::<CreatePaste>::from_request(request, state)
+            .await
+            .map_err(|e| bad_request(e.body_text()))?;
+        return Ok(paste.into());
+    }
+
+    if media == "multipart/form-data" {
+        let multipart = Multipart::from_request(request, state)
+            .await
+            .map_err(|e| bad_request(e.body_text()))?;
+        return read_multipart(multipart, options)
+            .await
+            .map_err(error::render_error);
+    }
+
+    let body = Bytes::from_request(request, state)
+        .await
+        .map_err(|e| bad_request(e.body_text()))?;
+    let text = String::from_utf8(body.to_vec())
+        .map_err(|_| bad_request("The paste must be UTF-8 text"))?;
+
+    Ok(options.with_text(text))
+}
+
+// Absolute URLs need the host the client used, paths are returned without one.
+// Only a proxy on `peer` is trusted to say which host and scheme that was.
+pub(super) fn origin(headers: &HeaderMap, peer: SocketAddr) -> String {
+    let header = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.split(',').next())
+            .map(str::trim)
+    };
+    let forwarded = |name: &str| is_local(peer.ip()).then(|| header(name)).flatten();
+
+    match forwarded("X-Forwarded-Host").or_else(|| header("Host")) {
+        Some(host) => format!(
+            "{}://{}",
+            forwarded("X-Forwarded-Proto").unwrap_or("http"),
+            host
+        ),
+        None => String::new(),
+    }
+}
+
+/// Creates a paste from a JSON, multipart or raw body, answering with its
+/// URLs as JSON, or as plain text lines when `Accept` prefers `text/plain`.
+/// Requests carrying a session cookie post under the signed-in account.
+pub async fn create_paste(
+    State(state): State<AppState>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    request: Request,
+) -> Result<Response<Body>, Response<Body>> {
+    let headers = request.headers().clone();
+    let account = signed_in(&state, &headers);
+    let mut paste = read_paste(request, &state).await?;
+
+    resolve_format(&mut paste, None);
+    validate(&paste, account.is_some()).map_err(error::render_error)?;
+
+    let id = state
+        .backend
+        .create_paste(paste, account.as_ref())
+        .await
+        .map_err(error::construct_error)?;
+    let created = Created::new(id, &origin(&headers, peer));
+    let location = [(header::LOCATION, created.url.clone())];
+
+    let text = accept_quality(&headers, |media| media == "text/plain");
+    if text > accept_quality(&headers, is_json) {
+        let body = format!("{}\n{}\n", created.url, created.raw_url);
+        return Ok((StatusCode::CREATED, location, body).into_response());
+    }
+
+    Ok((StatusCode::CREATED, location, Json(created)).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+    use std::sync::Arc;
+
+    use crate::backends::mock::MockBackend;
+
+    use super::*;
+
+    const LOCAL: SocketAddr =
+        SocketAddr::new(std::net::IpAddr::V4(std::net::Ipv4Addr::LOCALHOST), 40000);
+
+    #[tokio::test]
+    async fn test_raw_body_with_text_response() {
+        let state = AppState::with_backend(Arc::new(MockBackend::default()));
+
+        let request = Request::builder()
+            .method("POST")
+            .uri("/api/v1/pastes?title=notes")
+            .header(header::HOST, "localhost")
+            .header(header::ACCEPT, "text/plain")
+            .body(Body::from("hello"))
+            .expect("Valid request");
+        let response = create_paste(State(state), ConnectInfo(LOCAL), request)
+            .await
+            .expect("Created");
+
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .expect("Body");
+        assert_eq!(body, "http://localhost/new5\nhttp://localhost/raw/new5\n");
+    }
+
+    #[test]
+    fn test_origin() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(origin(&headers, LOCAL), "");
+
+        headers.insert(header::HOST, HeaderValue::from_static("localhost:3000"));
+        assert_eq!(origin(&headers, LOCAL), "http://localhost:3000");
+
+        headers.insert(
+            "X-Forwarded-Host",
+            HeaderValue::from_static("paste.example"),
+        );
+        headers.insert("X-Forwarded-Proto", HeaderValue::from_static("https"));
+        assert_eq!(origin(&headers, LOCAL), "https://paste.example");
+
+        // Anyone else could point the returned URLs at another site
+        let remote = SocketAddr::from(([203, 0, 113, 1], 40000));
+        assert_eq!(origin(&headers, remote), "http://localhost:3000");
+    }
+}
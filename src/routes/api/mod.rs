@@ -0,0 +1,98 @@
+use axum::{
+    Json, Router,
+    body::Body,
+    extract::{Path, State},
+    middleware,
+    response::{IntoResponse, Response},
+    routing,
+};
+
+use crate::state::AppState;
+
+use super::error;
+use super::view;
+
+mod compat;
+mod create;
+mod models;
+mod openapi;
+
+type ApiResult<T> = Result<Json<T>, Response<Body>>;
+
+pub fn get_router(state: AppState) -> Router {
+    Router::new()
+        .route("/api_post.php", routing::post(compat::api_post))
+        .route("/api_raw.php", routing::post(compat::api_raw))
+        .with_state(state.clone())
+        .nest("/v1", v1(state))
+        .fallback(error::error_404)
+        .layer(middleware::from_fn(error::json_errors))
+}
+
+fn v1(state: AppState) -> Router {
+    Router::new()
+        .route("/openapi.json", routing::get(openapi))
+        .route("/pastes", routing::post(create::create_paste))
+        .route("/pastes/{id}", routing::get(paste))
+        .route("/pastes/{id}/raw", routing::get(view::view_raw))
+        .route("/pastes/{id}/comments", routing::get(comments))
+        .route("/users/{username}", routing::get(user))
+        .route("/archive", routing::get(archive))
+        .route("/archive/{format}", routing::get(archive_format))
+        .with_state(state)
+}
+
+async fn openapi() -> impl IntoResponse {
+    Json(&*openapi::DOCUMENT)
+}
+
+async fn paste(State(state): State<AppState>, Path(id): Path<String>) -> ApiResult<models::Paste> {
+    let paste = view::fetch_paste(&state, &id).await?;
+    Ok(Json(paste.into()))
+}
+
+async fn comments(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> ApiResult<Vec<models::Comment>> {
+    let paste = view::fetch_paste(&state, &id).await?;
+    Ok(Json(
+        paste
+            .comments
+            .into_iter()
+            .map(models::Comment::from)
+            .collect(),
+    ))
+}
+
+async fn user(
+    State(state): State<AppState>,
+    Path(username): Path<String>,
+) -> ApiResult<models::User> {
+    let user = state
+        .backend
+        .get_user(&username)
+        .await
+        .map_err(error::construct_error)?;
+    Ok(Json(user.into()))
+}
+
+async fn archive(State(state): State<AppState>) -> ApiResult<models::Archive> {
+    fetch_archive(&state, None).await
+}
+
+async fn archive_format(
+    State(state): State<AppState>,
+    Path(format): Path<String>,
+) -> ApiResult<models::Archive> {
+    fetch_archive(&state, Some(&format)).await
+}
+
+async fn fetch_archive(state: &AppState, format: Option<&str>) -> ApiResult<models::Archive> {
+    let page = state
+        .backend
+        .get_archive(format)
+        .await
+        .map_err(error::construct_error)?;
+    Ok(Json(page.into()))
+}
@@ -0,0 +1,268 @@
+//! Stable response and request bodies of the v1 API.
+//!
+//! These are kept apart from the parser structs so upstream markup changes
+//! never reshape the API, every field here is described in [`super::openapi`].
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    backends::NewPaste,
+    parsers::{archive, paste, user},
+};
+
+use super::super::post::{default_expiration, default_exposure, default_format};
+
+#[derive(Serialize)]
+pub struct Author {
+    pub username: String,
+    pub registered: bool,
+    pub pro: bool,
+    pub icon_url: String,
+}
+
+impl From<user::SimpleUser> for Author {
+    fn from(user: user::SimpleUser) -> Self {
+        Self {
+            username: user.username,
+            registered: user.registered,
+            pro: user.pro,
+            icon_url: user.icon_url,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct Paste {
+    pub id: String,
+    pub title: Option<String>,
+    pub author: Author,
+    pub format: String,
+    pub format_name: String,
+    pub category: Option<String>,
+    pub tags: Vec<String>,
+    pub size: u64,
+    pub created_at: i64,
+    pub edited_at: Option<i64>,
+    pub expires: String,
+    pub views: u32,
+    pub rating: f32,
+    pub likes: Option<u32>,
+    pub dislikes: Option<u32>,
+    pub unlisted: bool,
+    /// ID of the paste this one is a comment on
+    pub comment_for: Option<String>,
+    pub num_comments: u32,
+    pub content: String,
+    /// Set when this is a cached copy rather than a fresh one
+    pub captured_at: Option<i64>,
+}
+
+impl From<paste::Paste> for Paste {
+    fn from(paste: paste::Paste) -> Self {
+        Self {
+            id: paste.id,
+            title: paste.title,
+            author: paste.author.into(),
+            format: paste.container.format,
+            format_name: paste.container.format_name,
+            category: paste.container.category,
+            tags: paste.tags,
+            size: paste.container.size,
+            created_at: paste.date,
+            edited_at: paste.edit_date,
+            expires: paste.expire,
+            views: paste.views,
+            rating: paste.rating,
+            likes: paste.container.likes,
+            dislikes: paste.container.dislikes,
+            unlisted: paste.unlisted,
+            comment_for: paste.comment_for,
+            num_comments: paste.num_comments.unwrap_or(paste.comments.len() as u32),
+            content: paste.container.content,
+            captured_at: paste.captured_at,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct Comment {
+    /// ID of the comment, which is itself a paste
+    pub id: Option<String>,
+    pub author: Author,
+    pub format: String,
+    pub format_name: String,
+    pub size: u64,
+    pub created_at: i64,
+    pub edited_at: Option<i64>,
+    pub likes: Option<u32>,
+    pub dislikes: Option<u32>,
+    pub num_comments: u32,
+    pub content: String,
+    /// Replies loaded so far, which may be fewer than `num_comments` deep in a thread
+    pub replies: Vec<Comment>,
+}
+
+impl From<paste::Comment> for Comment {
+    fn from(comment: paste::Comment) -> Self {
+        Self {
+            id: comment.container.id,
+            author: comment.author.into(),
+            format: comment.container.format,
+            format_name: comment.container.format_name,
+            size: comment.container.size,
+            created_at: comment.date,
+            edited_at: comment.edit_date,
+            likes: comment.container.likes,
+            dislikes: comment.container.dislikes,
+            num_comments: comment.num_comments,
+            content: comment.container.content,
+            replies: comment.replies.into_iter().map(Comment::from).collect(),
+        }
+    }
+}
+
+/// A paste listed on a user's profile
+#[derive(Serialize)]
+pub struct UserPaste {
+    pub id: String,
+    pub title: String,
+    /// Upstream's relative age, e.g. "2 days ago"
+    pub age: String,
+    pub expires: String,
+    pub views: u32,
+    pub num_comments: u32,
+    pub format: String,
+}
+
+impl From<user::UserPaste> for UserPaste {
+    fn from(paste: user::UserPaste) -> Self {
+        Self {
+            id: paste.id,
+            title: paste.title,
+            age: paste.age,
+            expires: paste.expires,
+            views: paste.views,
+            num_comments: paste.num_comments,
+            format: paste.format,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct User {
+    pub username: String,
+    pub icon_url: String,
+    pub website: Option<String>,
+    pub location: Option<String>,
+    pub profile_views: u32,
+    pub paste_views: u32,
+    pub rating: f32,
+    pub joined_at: i64,
+    pub pro: bool,
+    pub pastes: Vec<UserPaste>,
+}
+
+impl From<user::User> for User {
+    fn from(user: user::User) -> Self {
+        Self {
+            username: user.username,
+            icon_url: user.icon_url,
+            website: user.website,
+            location: user.location,
+            profile_views: user.profile_views,
+            paste_views: user.paste_views,
+            rating: user.rating,
+            joined_at: user.date_joined,
+            pro: user.pro,
+            pastes: user.pastes.into_iter().map(UserPaste::from).collect(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct ArchiveEntry {
+    pub id: String,
+    pub title: String,
+    /// Upstream's relative age, e.g. "5 min ago"
+    pub age: String,
+    pub format: String,
+}
+
+#[derive(Serialize)]
+pub struct Archive {
+    /// The format the archive is filtered by, if any
+    pub format: Option<String>,
+    pub pastes: Vec<ArchiveEntry>,
+}
+
+impl From<archive::ArchivePage> for Archive {
+    fn from(page: archive::ArchivePage) -> Self {
+        Self {
+            format: page.format,
+            pastes: page
+                .archives
+                .into_iter()
+                .map(|entry| ArchiveEntry {
+                    id: entry.id,
+                    title: entry.title,
+                    age: entry.age,
+                    format: entry.format,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Body of `POST /api/v1/pastes`, defaults match the paste form
+#[derive(Deserialize)]
+pub struct CreatePaste {
+    pub text: String,
+    #[serde(default)]
+    pub title: String,
+    #[serde(default = "default_format")]
+    pub format: u16,
+    #[serde(default)]
+    pub category: u8,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default = "default_expiration")]
+    pub expiration: String,
+    #[serde(default = "default_exposure")]
+    pub exposure: u8,
+    #[serde(default)]
+    pub password: String,
+}
+
+impl From<CreatePaste> for NewPaste {
+    fn from(paste: CreatePaste) -> Self {
+        Self {
+            text: paste.text,
+            category: paste.category,
+            tags: paste.tags.join(","),
+            format: paste.format,
+            expiration: paste.expiration,
+            exposure: paste.exposure,
+            password: paste.password,
+            title: paste.title,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct Created {
+    pub id: String,
+    pub url: String,
+    pub raw_url: String,
+}
+
+impl Created {
+    /// URLs are built on `origin`, e.g. `https://paste.example`, or left as
+    /// paths when it is empty
+    pub fn new(id: String, origin: &str) -> Self {
+        Self {
+            url: format!("{origin}/{id}"),
+            raw_url: format!("{origin}/raw/{id}"),
+            id,
+        }
+    }
+}
@@ -0,0 +1,548 @@
+use once_cell::sync::Lazy;
+use serde_json::{Value, json};
+
+/// OpenAPI 3.1 description of the v1 API, served at `/api/v1/openapi.json`.
+///
+/// Written by hand, the tests below check every schema against what the
+/// models in [`super::models`] actually serialize.
+pub static DOCUMENT: Lazy<Value> = Lazy::new(|| {
+    json!({
+        "openapi": "3.1.0",
+        "info": {
+            "title": "Pasted API",
+            "version": env!("CARGO_PKG_VERSION"),
+            "description": "Read and create pastes through this Pasted instance. Errors are returned as `Error` objects with a stable `code`."
+        },
+        "servers": [{ "url": "/api/v1" }],
+        "paths": {
+            "/pastes": {
+                "post": {
+                    "summary": "Create a paste",
+                    "description": "Takes a JSON `CreatePaste`, a multipart form with the paste in a `text` or `file` part and options in the other parts, or any other body as the paste itself with options in the query string, e.g. `curl --data-binary @main.py '/api/v1/pastes?format=42'`.",
+                    "operationId": "createPaste",
+                    "parameters": [
+                        query_parameter("title", "string", "Title, defaults to the uploaded file's name"),
+                        query_parameter("format", "integer", "Pastebin format ID, 0 detects it"),
+                        query_parameter("category", "integer", "Pastebin category ID"),
+                        query_parameter("tags", "string", "Comma separated tags"),
+                        query_parameter("expiration", "string", "Expiration code, e.g. `1D`"),
+                        query_parameter("exposure", "integer", "0 is public, 1 is unlisted, 2 is private and needs a signed-in session"),
+                        query_parameter("password", "string", "Password protecting the paste")
+                    ],
+                    "requestBody": {
+                        "required": true,
+                        "content": {
+                            "application/json": { "schema": schema_ref("CreatePaste") },
+                            "multipart/form-data": {
+                                "schema": {
+                                    "type": "object",
+                                    "properties": {
+                                        "text": { "type": "string" },
+                                        "file": { "type": "string", "format": "binary" }
+                                    }
+                                }
+                            },
+                            "text/plain": { "schema": { "type": "string" } }
+                        }
+                    },
+                    "responses": {
+                        "201": {
+                            "description": "The paste was created. The body is plain text, its URL and raw URL on separate lines, when `Accept` prefers `text/plain`",
+                            "headers": {
+                                "Location": {
+                                    "description": "URL of the paste",
+                                    "schema": { "type": "string" }
+                                }
+                            },
+                            "content": {
+                                "application/json": { "schema": schema_ref("Created") },
+                                "text/plain": { "schema": { "type": "string" } }
+                            }
+                        },
+                        "400": error_response("The body is not a valid paste"),
+                        "403": error_response("A private paste was requested without a signed-in session (`forbidden`)"),
+                        "413": error_response("The paste is larger than pastebin's 512 KB limit (`too_large`)"),
+                        "default": error_response("Upstream rejected the paste or failed")
+                    }
+                }
+            },
+            "/pastes/{id}": {
+                "get": {
+                    "summary": "Get a paste",
+                    "operationId": "getPaste",
+                    "parameters": [path_parameter("id", "Paste ID")],
+                    "responses": {
+                        "200": json_response("The paste", "Paste"),
+                        "403": error_response("The paste is password protected (`locked`) or burnt after reading (`burn`)"),
+                        "default": error_response("The paste could not be fetched")
+                    }
+                }
+            },
+            "/pastes/{id}/raw": {
+                "get": {
+                    "summary": "Get a paste's content as plain text",
+                    "operationId": "getRawPaste",
+                    "parameters": [
+                        path_parameter("id", "Paste ID"),
+                        {
+                            "name": "lines",
+                            "in": "query",
+                            "required": false,
+                            "description": "Only return these 1-based lines, e.g. `42`, `10-20` or `1-3,7`",
+                            "schema": { "type": "string" }
+                        }
+                    ],
+                    "responses": {
+                        "200": {
+                            "description": "The paste's content",
+                            "headers": {
+                                "X-Captured-At": {
+                                    "description": "Set when upstream failed and this is an older copy, as a Unix timestamp",
+                                    "schema": { "type": "integer" }
+                                }
+                            },
+                            "content": { "text/plain": { "schema": { "type": "string" } } }
+                        },
+                        "400": error_response("`lines` is not a valid selection"),
+                        "default": error_response("The paste could not be fetched")
+                    }
+                }
+            },
+            "/pastes/{id}/comments": {
+                "get": {
+                    "summary": "List the comments on a paste",
+                    "operationId": "getComments",
+                    "parameters": [path_parameter("id", "Paste ID")],
+                    "responses": {
+                        "200": {
+                            "description": "The comments, oldest first, each with its replies nested",
+                            "content": {
+                                "application/json": {
+                                    "schema": { "type": "array", "items": schema_ref("Comment") }
+                                }
+                            }
+                        },
+                        "403": error_response("The paste is password protected (`locked`) or burnt after reading (`burn`)"),
+                        "default": error_response("The paste could not be fetched")
+                    }
+                }
+            },
+            "/users/{username}": {
+                "get": {
+                    "summary": "Get a user's profile and pastes",
+                    "operationId": "getUser",
+                    "parameters": [path_parameter("username", "Username")],
+                    "responses": {
+                        "200": json_response("The user", "User"),
+                        "default": error_response("The user could not be fetched")
+                    }
+                }
+            },
+            "/archive": {
+                "get": {
+                    "summary": "List the most recent public pastes",
+                    "operationId": "getArchive",
+                    "responses": {
+                        "200": json_response("The archive", "Archive"),
+                        "default": error_response("The archive could not be fetched")
+                    }
+                }
+            },
+            "/archive/{format}": {
+                "get": {
+                    "summary": "List the most recent public pastes in a format",
+                    "operationId": "getArchiveByFormat",
+                    "parameters": [path_parameter("format", "Format slug, e.g. `python`")],
+                    "responses": {
+                        "200": json_response("The archive", "Archive"),
+                        "default": error_response("The archive could not be fetched")
+                    }
+                }
+            }
+        },
+        "components": {
+            "schemas": {
+                "Error": object(
+                    json!({
+                        "status": { "type": "integer" },
+                        "code": {
+                            "type": "string",
+                            "enum": [
+                                "bad_request", "forbidden", "not_found", "upstream_not_found",
+                                "upstream_error", "upstream_timeout", "upstream_unavailable",
+                                "locked", "burn", "parse_failure", "too_large", "rate_limited",
+                                "internal"
+                            ]
+                        },
+                        "message": { "type": "string" },
+                        "details": nullable("string"),
+                        "stack_trace": { "type": "string" },
+                        "source": { "type": "string", "enum": ["Upstream", "Internal"] },
+                        "retry_after": { "type": "integer", "description": "Seconds to wait before retrying" }
+                    }),
+                    &["status", "code", "message", "details", "source"]
+                ),
+                "Author": object(
+                    json!({
+                        "username": { "type": "string" },
+                        "registered": { "type": "boolean" },
+                        "pro": { "type": "boolean" },
+                        "icon_url": { "type": "string" }
+                    }),
+                    &["username", "registered", "pro", "icon_url"]
+                ),
+                "Paste": object(
+                    json!({
+                        "id": { "type": "string" },
+                        "title": nullable("string"),
+                        "author": schema_ref("Author"),
+                        "format": { "type": "string", "description": "Format slug, e.g. `python`" },
+                        "format_name": { "type": "string" },
+                        "category": nullable("string"),
+                        "tags": { "type": "array", "items": { "type": "string" } },
+                        "size": { "type": "integer", "description": "Size in bytes" },
+                        "created_at": { "type": "integer", "description": "Unix timestamp" },
+                        "edited_at": nullable("integer"),
+                        "expires": { "type": "string" },
+                        "views": { "type": "integer" },
+                        "rating": { "type": "number" },
+                        "likes": nullable("integer"),
+                        "dislikes": nullable("integer"),
+                        "unlisted": { "type": "boolean" },
+                        "comment_for": {
+                            "type": ["string", "null"],
+                            "description": "ID of the paste this one is a comment on"
+                        },
+                        "num_comments": { "type": "integer" },
+                        "content": { "type": "string" },
+                        "captured_at": {
+                            "type": ["integer", "null"],
+                            "description": "Set when this is a cached copy rather than a fresh one"
+                        }
+                    }),
+                    &[
+                        "id", "title", "author", "format", "format_name", "category", "tags",
+                        "size", "created_at", "edited_at", "expires", "views", "rating", "likes",
+                        "dislikes", "unlisted", "comment_for", "num_comments", "content",
+                        "captured_at"
+                    ]
+                ),
+                "Comment": object(
+                    json!({
+                        "id": nullable("string"),
+                        "author": schema_ref("Author"),
+                        "format": { "type": "string" },
+                        "format_name": { "type": "string" },
+                        "size": { "type": "integer" },
+                        "created_at": { "type": "integer" },
+                        "edited_at": nullable("integer"),
+                        "likes": nullable("integer"),
+                        "dislikes": nullable("integer"),
+                        "num_comments": { "type": "integer" },
+                        "content": { "type": "string" },
+                        "replies": {
+                            "type": "array",
+                            "description": "Replies to this comment, nested up to the instance's thread depth",
+                            "items": schema_ref("Comment")
+                        }
+                    }),
+                    &[
+                        "id", "author", "format", "format_name", "size", "created_at",
+                        "edited_at", "likes", "dislikes", "num_comments", "content", "replies"
+                    ]
+                ),
+                "UserPaste": object(
+                    json!({
+                        "id": { "type": "string" },
+                        "title": { "type": "string" },
+                        "age": { "type": "string", "description": "Relative age, e.g. `2 days ago`" },
+                        "expires": { "type": "string" },
+                        "views": { "type": "integer" },
+                        "num_comments": { "type": "integer" },
+                        "format": { "type": "string" }
+                    }),
+                    &["id", "title", "age", "expires", "views", "num_comments", "format"]
+                ),
+                "User": object(
+                    json!({
+                        "username": { "type": "string" },
+                        "icon_url": { "type": "string" },
+                        "website": nullable("string"),
+                        "location": nullable("string"),
+                        "profile_views": { "type": "integer" },
+                        "paste_views": { "type": "integer" },
+                        "rating": { "type": "number" },
+                        "joined_at": { "type": "integer", "description": "Unix timestamp" },
+                        "pro": { "type": "boolean" },
+                        "pastes": { "type": "array", "items": schema_ref("UserPaste") }
+                    }),
+                    &[
+                        "username", "icon_url", "website", "location", "profile_views",
+                        "paste_views", "rating", "joined_at", "pro", "pastes"
+                    ]
+                ),
+                "ArchiveEntry": object(
+                    json!({
+                        "id": { "type": "string" },
+                        "title": { "type": "string" },
+                        "age": { "type": "string" },
+                        "format": { "type": "string" }
+                    }),
+                    &["id", "title", "age", "format"]
+                ),
+                "Archive": object(
+                    json!({
+                        "format": nullable("string"),
+                        "pastes": { "type": "array", "items": schema_ref("ArchiveEntry") }
+                    }),
+                    &["format", "pastes"]
+                ),
+                "CreatePaste": object(
+                    json!({
+                        "text": { "type": "string" },
+                        "title": { "type": "string", "default": "" },
+                        "format": { "type": "integer", "default": 1, "description": "Pastebin format ID, 1 is plain text and 0 detects the format from the content" },
+                        "category": { "type": "integer", "default": 0 },
+                        "tags": { "type": "array", "items": { "type": "string" }, "default": [] },
+                        "expiration": {
+                            "type": "string",
+                            "default": "N",
+                            "enum": ["N", "B", "10M", "1H", "1D", "1W", "2W", "1M", "6M", "1Y"]
+                        },
+                        "exposure": {
+                            "type": "integer",
+                            "default": 1,
+                            "description": "0 is public, 1 is unlisted, 2 is private and needs a signed-in session"
+                        },
+                        "password": { "type": "string", "default": "" }
+                    }),
+                    &["text"]
+                ),
+                "Created": object(
+                    json!({
+                        "id": { "type": "string" },
+                        "url": { "type": "string", "description": "URL of the paste on this instance" },
+                        "raw_url": { "type": "string" }
+                    }),
+                    &["id", "url", "raw_url"]
+                )
+            }
+        }
+    })
+});
+
+fn schema_ref(name: &str) -> Value {
+    json!({ "$ref": format!("#/components/schemas/{name}") })
+}
+
+fn nullable(kind: &str) -> Value {
+    json!({ "type": [kind, "null"] })
+}
+
+fn object(properties: Value, required: &[&str]) -> Value {
+    json!({ "type": "object", "properties": properties, "required": required })
+}
+
+fn path_parameter(name: &str, description: &str) -> Value {
+    json!({
+        "name": name,
+        "in": "path",
+        "required": true,
+        "description": description,
+        "schema": { "type": "string" }
+    })
+}
+
+fn query_parameter(name: &str, kind: &str, description: &str) -> Value {
+    json!({
+        "name": name,
+        "in": "query",
+        "required": false,
+        "description": description,
+        "schema": { "type": kind }
+    })
+}
+
+fn json_response(description: &str, schema: &str) -> Value {
+    json!({
+        "description": description,
+        "content": { "application/json": { "schema": schema_ref(schema) } }
+    })
+}
+
+fn error_response(description: &str) -> Value {
+    json_response(description, "Error")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use serde::Serialize;
+
+    use super::super::models::*;
+    use super::*;
+
+    // The serialized keys of `value` must be exactly the documented properties
+    fn assert_documented(name: &str, value: impl Serialize) {
+        let schema = &DOCUMENT["components"]["schemas"][name];
+        let documented = schema["properties"]
+            .as_object()
+            .unwrap_or_else(|| panic!("No schema for {}", name))
+            .keys()
+            .cloned()
+            .collect::<BTreeSet<_>>();
+        let serialized = serde_json::to_value(value)
+            .expect("Serializable")
+            .as_object()
+            .expect("An object")
+            .keys()
+            .cloned()
+            .collect::<BTreeSet<_>>();
+
+        assert_eq!(documented, serialized, "Schema of {} is out of date", name);
+    }
+
+    fn author() -> Author {
+        Author {
+            username: "Guest".to_string(),
+            registered: false,
+            pro: false,
+            icon_url: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_schemas_match_models() {
+        assert_documented("Author", author());
+        assert_documented(
+            "Paste",
+            Paste {
+                id: "abc".to_string(),
+                title: None,
+                author: author(),
+                format: "text".to_string(),
+                format_name: "Plain Text".to_string(),
+                category: None,
+                tags: Vec::new(),
+                size: 0,
+                created_at: 0,
+                edited_at: None,
+                expires: "Never".to_string(),
+                views: 0,
+                rating: 0.0,
+                likes: None,
+                dislikes: None,
+                unlisted: false,
+                comment_for: None,
+                num_comments: 0,
+                content: String::new(),
+                captured_at: None,
+            },
+        );
+        assert_documented(
+            "Comment",
+            Comment {
+                id: None,
+                author: author(),
+                format: "text".to_string(),
+                format_name: "Plain Text".to_string(),
+                size: 0,
+                created_at: 0,
+                edited_at: None,
+                likes: None,
+                dislikes: None,
+                num_comments: 0,
+                content: String::new(),
+                replies: Vec::new(),
+            },
+        );
+        assert_documented(
+            "User",
+            User {
+                username: "user".to_string(),
+                icon_url: String::new(),
+                website: None,
+                location: None,
+                profile_views: 0,
+                paste_views: 0,
+                rating: 0.0,
+                joined_at: 0,
+                pro: false,
+                pastes: Vec::new(),
+            },
+        );
+        assert_documented(
+            "UserPaste",
+            UserPaste {
+                id: "abc".to_string(),
+                title: String::new(),
+                age: String::new(),
+                expires: String::new(),
+                views: 0,
+                num_comments: 0,
+                format: "text".to_string(),
+            },
+        );
+        assert_documented(
+            "ArchiveEntry",
+            ArchiveEntry {
+                id: "abc".to_string(),
+                title: String::new(),
+                age: String::new(),
+                format: "text".to_string(),
+            },
+        );
+        assert_documented(
+            "Archive",
+            Archive {
+                format: None,
+                pastes: Vec::new(),
+            },
+        );
+        assert_documented("Created", Created::new("abc".to_string(), ""));
+    }
+
+    #[test]
+    fn test_create_paste_defaults() {
+        let paste: CreatePaste =
+            serde_json::from_value(json!({ "text": "hello" })).expect("Valid body");
+
+        assert_eq!(paste.format, 1);
+        assert_eq!(paste.expiration, "N");
+        assert_eq!(paste.exposure, 1);
+        assert!(
+            DOCUMENT["components"]["schemas"]["CreatePaste"]["properties"]
+                .as_object()
+                .is_some_and(|properties| properties.len() == 8)
+        );
+    }
+
+    #[test]
+    fn test_schema_references_resolve() {
+        fn refs(value: &Value, found: &mut Vec<String>) {
+            match value {
+                Value::Object(map) => {
+                    if let Some(Value::String(target)) = map.get("$ref") {
+                        found.push(target.clone());
+                    }
+                    map.values().for_each(|v| refs(v, found));
+                }
+                Value::Array(items) => items.iter().for_each(|v| refs(v, found)),
+                _ => {}
+            }
+        }
+
+        let mut found = Vec::new();
+        refs(&DOCUMENT, &mut found);
+
+        for target in found {
+            let name = target.trim_start_matches("#/components/schemas/");
+            assert!(
+                DOCUMENT["components"]["schemas"].get(name).is_some(),
+                "Dangling reference {}",
+                target
+            );
+        }
+    }
+}
@@ -1,18 +1,15 @@
 use axum::{
     Json, Router,
     body::Body,
-    extract::{Path, State},
-    response::{IntoResponse, Response},
+    extract::{Path, Query, State},
+    middleware,
+    response::{IntoResponse, Redirect, Response},
     routing,
 };
+use serde::Deserialize;
 use tera::Context;
 
-use crate::{
-    constants::URL,
-    parsers::{FromHtml, archive::ArchivePage},
-    state::AppState,
-    templates::TEMPLATES,
-};
+use crate::{catalog, state::AppState, templates::TEMPLATES};
 
 use super::error::{self, AppError, Error as PasteError};
 
@@ -21,10 +18,10 @@ fn safe_render_template<T: serde::Serialize>(
     template_name: &str,
     context: &T,
 ) -> Result<String, AppError> {
-    let ctx = Context::from_serialize(context).map_err(|e| AppError::Template(e))?;
+    let ctx = Context::from_serialize(context).map_err(AppError::Template)?;
     TEMPLATES
         .render(template_name, &ctx)
-        .map_err(|e| AppError::Template(e))
+        .map_err(AppError::Template)
 }
 
 // Helper function to create HTML responses
@@ -40,41 +37,45 @@ pub fn get_router(state: AppState) -> Router {
     Router::new()
         .route("/", routing::get(archive))
         .route("/{format}", routing::get(archive))
-        .route("/json", routing::get(archive_json))
-        .route("/json/{format}", routing::get(archive_json))
+        .route(
+            "/json",
+            routing::get(archive_json).layer(middleware::from_fn(error::json_errors)),
+        )
+        .route(
+            "/json/{format}",
+            routing::get(archive_json).layer(middleware::from_fn(error::json_errors)),
+        )
         .with_state(state)
 }
 
-fn get_url(format: Option<Path<String>>) -> String {
-    if let Some(format) = format {
-        let format = format.0;
-        format!("{URL}/archive/{format}")
-    } else {
-        format!("{URL}/archive")
-    }
+/// The archive page's format picker, which submits `?format={slug}`
+#[derive(Deserialize)]
+struct Filter {
+    format: Option<String>,
 }
 
-async fn archive(State(state): State<AppState>, format: Option<Path<String>>) -> impl IntoResponse {
-    let dom = state.client.get_html(&get_url(format));
+async fn archive(
+    State(state): State<AppState>,
+    format: Option<Path<String>>,
+    Query(filter): Query<Filter>,
+) -> impl IntoResponse {
+    if let Some(slug) = filter.format {
+        return match catalog::format_by_slug(&slug) {
+            Some(format) => Redirect::to(&format!("/archive/{}", format.slug)).into_response(),
+            None => Redirect::to("/archive").into_response(),
+        };
+    }
+
+    let format = format.map(|format| format.0);
 
-    match dom {
-        Ok(dom) => {
-            let archive_page = match ArchivePage::from_html(&dom) {
-                Ok(page) => page,
-                Err(e) => return error::render_error(PasteError::new(
-                    500,
-                    format!("Failed to parse archive page: {}", e),
-                    error::ErrorSource::Internal,
-                )),
-            };
-            match safe_render_template("archive.html", &archive_page) {
-                Ok(rendered) => match create_html_response(rendered, 200) {
-                    Ok(response) => response,
-                    Err(app_err) => error::render_error(PasteError::from(app_err)),
-                },
+    match state.backend.get_archive(format.as_deref()).await {
+        Ok(archive_page) => match safe_render_template("archive.html", &archive_page) {
+            Ok(rendered) => match create_html_response(rendered, 200) {
+                Ok(response) => response,
                 Err(app_err) => error::render_error(PasteError::from(app_err)),
-            }
-        }
+            },
+            Err(app_err) => error::render_error(PasteError::from(app_err)),
+        },
         Err(err) => error::construct_error(err),
     }
 }
@@ -83,15 +84,10 @@ async fn archive_json(
     State(state): State<AppState>,
     format: Option<Path<String>>,
 ) -> impl IntoResponse {
-    match state.client.get_html(&get_url(format)) {
-        Ok(dom) => match ArchivePage::from_html(&dom) {
-            Ok(archive_page) => Json(archive_page).into_response(),
-            Err(e) => error::render_error(error::Error::new(
-                500,
-                format!("Failed to parse archive page: {}", e),
-                error::ErrorSource::Internal,
-            )),
-        },
+    let format = format.map(|format| format.0);
+
+    match state.backend.get_archive(format.as_deref()).await {
+        Ok(archive_page) => Json(archive_page).into_response(),
         Err(err) => error::construct_error(err),
     }
 }
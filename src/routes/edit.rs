@@ -0,0 +1,255 @@
+use axum::{
+    Router,
+    body::Body,
+    extract::{Path, Request, State},
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Redirect, Response},
+    routing,
+};
+use serde_json::json;
+use tera::Context;
+
+use crate::{catalog, parsers::account::EditForm, state::AppState, templates::TEMPLATES};
+
+use super::account::{cross_origin, login_redirect, signed_in};
+use super::error::{self, Error};
+use super::post::read_paste_form;
+
+pub fn get_router(state: AppState) -> Router {
+    Router::new()
+        .route("/edit/{id}", routing::get(edit).post(edit_submit))
+        .route("/delete/{id}", routing::get(delete).post(delete_submit))
+        .with_state(state)
+}
+
+// Both pages show what only the signed-in author may see
+fn render_page(template: &str, context: &Context) -> Response<Body> {
+    match TEMPLATES.render(template, context) {
+        Ok(html) => (
+            StatusCode::OK,
+            [
+                (header::CONTENT_TYPE, "text/html"),
+                (header::CACHE_CONTROL, "private, no-store"),
+            ],
+            html,
+        )
+            .into_response(),
+        Err(e) => error::render_error(Error::from(e)),
+    }
+}
+
+/// Fills `post.html` the way the clone page does, from upstream's edit form
+fn edit_context(id: &str, form: EditForm, username: &str) -> Context {
+    let mut context = Context::new();
+    context.insert("edit_id", id);
+    context.insert("signed_in_as", username);
+    context.insert(
+        "container",
+        &json!({
+            "content": form.text,
+            "category": catalog::category(form.category).map(|category| category.name),
+            "format": catalog::format(form.format).map(|format| format.slug),
+        }),
+    );
+    context.insert(
+        "tags",
+        &form
+            .tags
+            .split(',')
+            .map(str::trim)
+            .filter(|tag| !tag.is_empty())
+            .collect::<Vec<_>>(),
+    );
+    context.insert("title", &form.title);
+    if let Some(expiration) = &form.expiration {
+        context.insert("expiration", expiration);
+    }
+    context.insert("exposure", &form.exposure);
+    context
+}
+
+async fn edit(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Response<Body> {
+    let Some(account) = signed_in(&state, &headers) else {
+        return login_redirect(&format!("/edit/{id}")).into_response();
+    };
+
+    match state.backend.get_edit_form(&id, &account).await {
+        Ok(form) => render_page("post.html", &edit_context(&id, form, &account.username)),
+        Err(err) => error::construct_error(err),
+    }
+}
+
+async fn edit_submit(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    request: Request,
+) -> Result<Response<Body>, Response<Body>> {
+    if let Some(response) = cross_origin(request.headers()) {
+        return Err(response);
+    }
+
+    let Some(account) = signed_in(&state, request.headers()) else {
+        return Ok(login_redirect(&format!("/edit/{id}")).into_response());
+    };
+    let paste = read_paste_form(request, &state, true).await?;
+
+    state
+        .backend
+        .edit_paste(&id, paste, &account)
+        .await
+        .map_err(error::construct_error)?;
+
+    Ok(Redirect::to(&format!("/{id}")).into_response())
+}
+
+async fn delete(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Response<Body> {
+    let Some(account) = signed_in(&state, &headers) else {
+        return login_redirect(&format!("/delete/{id}")).into_response();
+    };
+
+    // Only the author gets the edit form, so fetching it checks ownership
+    match state.backend.get_edit_form(&id, &account).await {
+        Ok(form) => {
+            let mut context = Context::new();
+            context.insert("id", &id);
+            context.insert("title", &form.title);
+            render_page("delete.html", &context)
+        }
+        Err(err) => error::construct_error(err),
+    }
+}
+
+async fn delete_submit(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Response<Body> {
+    if let Some(response) = cross_origin(&headers) {
+        return response;
+    }
+
+    let Some(account) = signed_in(&state, &headers) else {
+        return login_redirect(&format!("/delete/{id}")).into_response();
+    };
+
+    match state.backend.delete_paste(&id, &account).await {
+        Ok(()) => Redirect::to(&format!("/u/{}", account.username)).into_response(),
+        Err(err) => error::construct_error(err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+    use std::sync::Arc;
+
+    use crate::{
+        backends::{
+            Account,
+            mock::{MockBackend, OWNER},
+        },
+        sessions,
+    };
+
+    use super::*;
+
+    fn state() -> AppState {
+        AppState::with_backend(Arc::new(MockBackend::default()))
+    }
+
+    fn signed_in_as(state: &AppState, username: &str) -> HeaderMap {
+        let id = state
+            .sessions
+            .create(Account {
+                username: username.to_string(),
+                cookies: Default::default(),
+            })
+            .expect("Session");
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::COOKIE,
+            HeaderValue::from_str(&format!("{}={id}", sessions::COOKIE)).expect("Valid header"),
+        );
+        headers
+    }
+
+    #[tokio::test]
+    async fn test_guests_are_sent_to_login() {
+        let response = edit(State(state()), Path("abc123".to_string()), HeaderMap::new()).await;
+
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        assert_eq!(
+            response.headers().get(header::LOCATION),
+            Some(&HeaderValue::from_static("/login?next=/edit/abc123"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_edit_form_is_prefilled() {
+        let state = state();
+        let headers = signed_in_as(&state, OWNER);
+
+        let response = edit(State(state), Path("abc123".to_string()), headers).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .expect("Body");
+        let html = String::from_utf8_lossy(&body);
+        assert!(html.contains(r#"action="/edit/abc123""#));
+        assert!(html.contains("abc123 text</textarea>"));
+        assert!(html.contains(r#"<option value="1" selected>Unlisted</option>"#));
+    }
+
+    #[tokio::test]
+    async fn test_only_the_author_may_delete() {
+        let state = state();
+
+        let response = delete(
+            State(state.clone()),
+            Path("abc123".to_string()),
+            signed_in_as(&state, "mallory"),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let headers = signed_in_as(&state, OWNER);
+        let response = delete(
+            State(state.clone()),
+            Path("abc123".to_string()),
+            headers.clone(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = delete_submit(State(state), Path("abc123".to_string()), headers).await;
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        assert_eq!(
+            response.headers().get(header::LOCATION),
+            Some(&HeaderValue::from_static("/u/alice"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_deletes_from_other_sites_are_rejected() {
+        let state = state();
+        let mut headers = signed_in_as(&state, OWNER);
+        headers.insert(header::HOST, HeaderValue::from_static("pasted.example"));
+        headers.insert(
+            header::ORIGIN,
+            HeaderValue::from_static("https://evil.example"),
+        );
+
+        let response = delete_submit(State(state), Path("abc123".to_string()), headers).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+}
@@ -1,28 +1,77 @@
 use axum::{
     body::Body,
-    http::{StatusCode, HeaderValue},
+    extract::Request,
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    middleware::Next,
     response::{IntoResponse, Response},
 };
 use serde::Serialize;
 use tera::Context;
 
+use crate::backends::BackendError;
 use crate::client::ClientError;
 use crate::templates::TEMPLATES;
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
 pub enum ErrorSource {
     Upstream,
     Internal,
 }
 
-#[derive(Serialize, Debug)]
+/// Stable, machine-readable reason for an error, serialized in snake case
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    BadRequest,
+    Forbidden,
+    NotFound,
+    /// Upstream has no paste, user or page by that name
+    UpstreamNotFound,
+    /// Upstream answered with an unexpected status
+    UpstreamError,
+    UpstreamTimeout,
+    /// Upstream could not be reached, or the circuit breaker is open
+    UpstreamUnavailable,
+    /// The paste needs a password
+    Locked,
+    /// The paste is deleted once read
+    Burn,
+    /// The upstream page could not be parsed
+    ParseFailure,
+    /// The paste is larger than upstream accepts
+    TooLarge,
+    /// The visitor sent too many actions upstream recently
+    RateLimited,
+    Internal,
+}
+
+impl ErrorCode {
+    fn for_status(status: u16, source: ErrorSource) -> Self {
+        match (source, status) {
+            (ErrorSource::Upstream, 404) => ErrorCode::UpstreamNotFound,
+            (ErrorSource::Upstream, 504) => ErrorCode::UpstreamTimeout,
+            (ErrorSource::Upstream, 502 | 503) => ErrorCode::UpstreamUnavailable,
+            (ErrorSource::Upstream, _) => ErrorCode::UpstreamError,
+            (ErrorSource::Internal, 400) => ErrorCode::BadRequest,
+            (ErrorSource::Internal, 401 | 403) => ErrorCode::Forbidden,
+            (ErrorSource::Internal, 404) => ErrorCode::NotFound,
+            (ErrorSource::Internal, 429) => ErrorCode::RateLimited,
+            (ErrorSource::Internal, _) => ErrorCode::Internal,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
 pub struct Error {
     status: u16,
+    code: ErrorCode,
     message: String,
     details: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     stack_trace: Option<String>,
     source: ErrorSource,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    retry_after: Option<u64>,
 }
 
 // Comprehensive application error type
@@ -33,7 +82,6 @@ pub enum AppError {
     Database(sled::Error),
     Io(std::io::Error),
     Server(String),
-    Parser(String),
     DateParse(chrono::ParseError),
     Custom { status: u16, message: String },
 }
@@ -46,7 +94,6 @@ impl std::fmt::Display for AppError {
             AppError::Database(err) => write!(f, "Database error: {}", err),
             AppError::Io(err) => write!(f, "IO error: {}", err),
             AppError::Server(msg) => write!(f, "Server error: {}", msg),
-            AppError::Parser(msg) => write!(f, "Parser error: {}", msg),
             AppError::DateParse(err) => write!(f, "Date parsing error: {}", err),
             AppError::Custom { message, .. } => write!(f, "{}", message),
         }
@@ -100,6 +147,8 @@ impl From<AppError> for Error {
                     None
                 },
                 source: ErrorSource::Internal,
+                retry_after: None,
+                code: ErrorCode::Internal,
             },
             AppError::Io(io_err) => Error {
                 status: StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
@@ -111,6 +160,8 @@ impl From<AppError> for Error {
                     None
                 },
                 source: ErrorSource::Internal,
+                retry_after: None,
+                code: ErrorCode::Internal,
             },
             AppError::Server(msg) => Error {
                 status: StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
@@ -118,13 +169,8 @@ impl From<AppError> for Error {
                 details: None,
                 stack_trace: None,
                 source: ErrorSource::Internal,
-            },
-            AppError::Parser(msg) => Error {
-                status: StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
-                message: format!("Parsing failed: {}", msg),
-                details: None,
-                stack_trace: None,
-                source: ErrorSource::Internal,
+                retry_after: None,
+                code: ErrorCode::Internal,
             },
             AppError::DateParse(parse_err) => Error {
                 status: StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
@@ -136,6 +182,8 @@ impl From<AppError> for Error {
                     None
                 },
                 source: ErrorSource::Internal,
+                retry_after: None,
+                code: ErrorCode::Internal,
             },
             AppError::Custom { status, message } => Error {
                 status,
@@ -143,6 +191,8 @@ impl From<AppError> for Error {
                 details: None,
                 stack_trace: None,
                 source: ErrorSource::Internal,
+                retry_after: None,
+                code: ErrorCode::for_status(status, ErrorSource::Internal),
             },
         }
     }
@@ -156,23 +206,65 @@ impl Error {
             details: None,
             stack_trace: None,
             source,
+            retry_after: None,
+            code: ErrorCode::for_status(status, source),
         }
     }
+
+    pub fn with_code(mut self, code: ErrorCode) -> Self {
+        self.code = code;
+        self
+    }
+
+    pub fn with_retry_after(mut self, seconds: u64) -> Self {
+        self.retry_after = Some(seconds);
+        self
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
 }
 
 impl From<ClientError> for Error {
     fn from(err: ClientError) -> Self {
         match err {
             ClientError::UreqError(error) => {
-                let (status, message) = match error {
-                    ureq::Error::StatusCode(code) => (code, "".to_string()),
-                    ureq::Error::Http(transport) => {
-                        (StatusCode::BAD_GATEWAY.as_u16(), transport.to_string())
-                    }
-                    _ => (0, "Unknown".to_string()),
+                let (status, message) = match &*error {
+                    ureq::Error::StatusCode(code) => (*code, "".to_string()),
+                    ureq::Error::Timeout(_) => (
+                        StatusCode::GATEWAY_TIMEOUT.as_u16(),
+                        "Upstream timed out".to_string(),
+                    ),
+                    error => (StatusCode::BAD_GATEWAY.as_u16(), error.to_string()),
                 };
                 Error::new(status, message, ErrorSource::Upstream)
             }
+            ClientError::CircuitOpen { retry_after } => {
+                // Round up so clients never retry before the breaker closes
+                let retry_after = retry_after.as_secs() + 1;
+                Error {
+                    status: StatusCode::SERVICE_UNAVAILABLE.as_u16(),
+                    message: "Upstream unavailable".to_string(),
+                    details: Some(format!(
+                        "Too many recent upstream failures, try again in {} seconds",
+                        retry_after
+                    )),
+                    stack_trace: None,
+                    source: ErrorSource::Upstream,
+                    retry_after: Some(retry_after),
+                    code: ErrorCode::UpstreamUnavailable,
+                }
+            }
+            ClientError::Task(message) => Error {
+                status: StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+                message: "Internal Server Error".to_string(),
+                details: Some(message),
+                stack_trace: None,
+                source: ErrorSource::Internal,
+                retry_after: None,
+                code: ErrorCode::Internal,
+            },
             ClientError::IoError(error) => Error {
                 status: StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
                 message: "Internal Server Error".to_string(),
@@ -183,11 +275,33 @@ impl From<ClientError> for Error {
                     None
                 },
                 source: ErrorSource::Internal,
+                retry_after: None,
+                code: ErrorCode::Internal,
             },
         }
     }
 }
 
+impl From<BackendError> for Error {
+    fn from(err: BackendError) -> Self {
+        match err {
+            BackendError::Client(client_err) => Error::from(client_err),
+            BackendError::Parser(msg) => Error::new(
+                StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+                msg,
+                ErrorSource::Internal,
+            )
+            .with_code(ErrorCode::ParseFailure),
+            BackendError::Rejected(msg) => Error::new(
+                StatusCode::FORBIDDEN.as_u16(),
+                msg,
+                ErrorSource::Upstream,
+            )
+            .with_code(ErrorCode::Forbidden),
+        }
+    }
+}
+
 impl From<tera::Error> for Error {
     fn from(err: tera::Error) -> Self {
         Error {
@@ -200,6 +314,8 @@ impl From<tera::Error> for Error {
                 None
             },
             source: ErrorSource::Internal,
+            retry_after: None,
+            code: ErrorCode::Internal,
         }
     }
 }
@@ -208,10 +324,9 @@ impl From<tera::Error> for Error {
 pub fn create_fallback_response(message: &str) -> Response<Body> {
     let mut response = Response::new(Body::from(message.to_string()));
     *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
-    response.headers_mut().insert(
-        "Content-Type",
-        HeaderValue::from_static("text/html")
-    );
+    response
+        .headers_mut()
+        .insert("Content-Type", HeaderValue::from_static("text/html"));
     response
 }
 
@@ -233,14 +348,99 @@ pub fn render_error(error: Error) -> Response<Body> {
         }
     };
 
-    Response::builder()
+    let mut builder = Response::builder()
         .status(error.status)
-        .header("Content-Type", "text/html")
+        .header("Content-Type", "text/html");
+
+    if let Some(retry_after) = error.retry_after {
+        builder = builder.header("Retry-After", retry_after);
+    }
+
+    let mut response = builder
         .body(Body::new(body))
         .unwrap_or_else(|err| {
             eprintln!("Failed to build error response: {}", err);
             create_fallback_response("Internal server error")
+        });
+
+    // Kept so the JSON middleware can render the same error for API clients
+    response.extensions_mut().insert(error);
+    response
+}
+
+pub fn render_json_error(error: Error) -> Response<Body> {
+    let body = serde_json::to_string(&error).unwrap_or_else(|err| {
+        eprintln!("Failed to serialize error: {}", err);
+        format!(
+            r#"{{"status":{},"code":"internal","message":"Internal server error"}}"#,
+            error.status
+        )
+    });
+
+    let mut builder = Response::builder()
+        .status(error.status)
+        .header("Content-Type", "application/json");
+
+    if let Some(retry_after) = error.retry_after {
+        builder = builder.header("Retry-After", retry_after);
+    }
+
+    builder.body(Body::new(body)).unwrap_or_else(|err| {
+        eprintln!("Failed to build error response: {}", err);
+        create_fallback_response("Internal server error")
+    })
+}
+
+// Swaps an HTML error page for its JSON form, leaving other responses alone
+fn into_json_error(mut response: Response<Body>) -> Response<Body> {
+    match response.extensions_mut().remove::<Error>() {
+        Some(error) => render_json_error(error),
+        None => response,
+    }
+}
+
+/// Highest quality the `Accept` header gives to a media type matching `matches`,
+/// zero when none does
+pub(super) fn accept_quality(headers: &HeaderMap, matches: impl Fn(&str) -> bool) -> f32 {
+    let Some(accept) = headers.get(header::ACCEPT).and_then(|v| v.to_str().ok()) else {
+        return 0.0;
+    };
+
+    accept
+        .split(',')
+        .filter_map(|range| {
+            let mut params = range.split(';');
+            let media = params.next().unwrap_or_default().trim().to_ascii_lowercase();
+            let quality = params
+                .filter_map(|param| param.trim().strip_prefix("q="))
+                .find_map(|q| q.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+
+            matches(&media).then_some(quality)
         })
+        .fold(0.0, f32::max)
+}
+
+pub(super) fn is_json(media: &str) -> bool {
+    media == "application/json" || media.ends_with("+json")
+}
+
+/// Whether the `Accept` header ranks JSON above HTML
+fn prefers_json(headers: &HeaderMap) -> bool {
+    accept_quality(headers, is_json) > accept_quality(headers, |media| media == "text/html")
+}
+
+/// Middleware for JSON routes, so scripts always get errors they can parse
+pub async fn json_errors(request: Request, next: Next) -> Response<Body> {
+    into_json_error(next.run(request).await)
+}
+
+/// Middleware rendering errors as JSON when the request's `Accept` header prefers it
+pub async fn negotiate_errors(request: Request, next: Next) -> Response<Body> {
+    let json = prefers_json(request.headers());
+    let response = next.run(request).await;
+
+    if json { into_json_error(response) } else { response }
 }
 
 pub async fn error_404() -> impl IntoResponse {
@@ -251,8 +451,8 @@ pub async fn error_404() -> impl IntoResponse {
     ))
 }
 
-pub fn construct_error(error: ClientError) -> Response<Body> {
-    render_error(Error::from(error))
+pub fn construct_error(error: impl Into<Error>) -> Response<Body> {
+    render_error(error.into())
 }
 
 #[cfg(test)]
@@ -264,4 +464,77 @@ mod tests {
         let response = error_404().await;
         assert_eq!(response.into_response().status(), StatusCode::NOT_FOUND);
     }
+
+    #[test]
+    fn test_circuit_open_sets_retry_after() {
+        let response = construct_error(ClientError::CircuitOpen {
+            retry_after: std::time::Duration::from_secs(10),
+        });
+
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(
+            response
+                .headers()
+                .get("Retry-After")
+                .and_then(|v| v.to_str().ok()),
+            Some("11")
+        );
+    }
+
+    #[test]
+    fn test_error_codes() {
+        let not_found = Error::from(ClientError::UreqError(std::sync::Arc::new(
+            ureq::Error::StatusCode(404),
+        )));
+        assert_eq!(not_found.code, ErrorCode::UpstreamNotFound);
+
+        let timeout = Error::from(ClientError::UreqError(std::sync::Arc::new(ureq::Error::Timeout(
+            ureq::Timeout::Global,
+        ))));
+        assert_eq!(timeout.code, ErrorCode::UpstreamTimeout);
+
+        let parse = Error::from(BackendError::Parser("No title".to_string()));
+        assert_eq!(parse.code, ErrorCode::ParseFailure);
+
+        let missing = Error::new(404, "Page not found".to_string(), ErrorSource::Internal);
+        assert_eq!(missing.code, ErrorCode::NotFound);
+    }
+
+    #[test]
+    fn test_prefers_json() {
+        let accept = |value: &'static str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::ACCEPT, HeaderValue::from_static(value));
+            prefers_json(&headers)
+        };
+
+        assert!(accept("application/json"));
+        assert!(accept("text/html;q=0.5, application/json"));
+        assert!(!accept("text/html,application/xhtml+xml,*/*;q=0.8"));
+        assert!(!accept("*/*"));
+        assert!(!prefers_json(&HeaderMap::new()));
+    }
+
+    #[tokio::test]
+    async fn test_json_error_body() {
+        let response = into_json_error(construct_error(ClientError::CircuitOpen {
+            retry_after: std::time::Duration::from_secs(10),
+        }));
+
+        assert_eq!(
+            response
+                .headers()
+                .get("Content-Type")
+                .and_then(|v| v.to_str().ok()),
+            Some("application/json")
+        );
+        assert!(response.headers().contains_key("Retry-After"));
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .expect("Body");
+        let json: serde_json::Value = serde_json::from_slice(&body).expect("JSON body");
+        assert_eq!(json["code"], "upstream_unavailable");
+        assert_eq!(json["status"], 503);
+    }
 }
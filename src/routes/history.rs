@@ -0,0 +1,125 @@
+use axum::{
+    Router,
+    body::Body,
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing,
+};
+use serde::{Deserialize, Serialize};
+use tera::Context;
+
+use crate::{diff::Diff, revisions::Revision, state::AppState, templates::TEMPLATES};
+
+use super::error::{self, AppError, Error as PasteError};
+
+// Helper function to create HTML responses
+fn create_html_response(content: String, status: u16) -> Result<Response<Body>, AppError> {
+    Response::builder()
+        .status(status)
+        .header("Content-Type", "text/html")
+        .body(Body::from(content))
+        .map_err(|e| AppError::Server(format!("Failed to build response: {}", e)))
+}
+
+#[derive(Deserialize)]
+struct Compare {
+    from: Option<i64>,
+    to: Option<i64>,
+    layout: Option<String>,
+}
+
+#[derive(Serialize)]
+struct RevisionSummary {
+    stamp: i64,
+    seen_at: i64,
+    edit_date: Option<i64>,
+    size: usize,
+}
+
+#[derive(Serialize)]
+struct History {
+    id: String,
+    revisions: Vec<RevisionSummary>,
+    from: Option<i64>,
+    to: Option<i64>,
+    split: bool,
+    diff: Option<Diff>,
+}
+
+pub fn get_router(state: AppState) -> Router {
+    Router::new()
+        .route("/{id}", routing::get(history))
+        .with_state(state)
+}
+
+async fn history(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(compare): Query<Compare>,
+) -> impl IntoResponse {
+    match render_history(&state, id, compare) {
+        Ok(response) => response,
+        Err(app_err) => error::render_error(PasteError::from(app_err)),
+    }
+}
+
+fn render_history(
+    state: &AppState,
+    id: String,
+    compare: Compare,
+) -> Result<Response<Body>, AppError> {
+    let store = state.revisions.as_deref().ok_or_else(|| AppError::Custom {
+        status: StatusCode::NOT_FOUND.as_u16(),
+        message: "Revision history is disabled on this instance".to_string(),
+    })?;
+
+    let revisions = store.list(&id);
+
+    // Compare the two newest revisions unless asked otherwise
+    let to = compare.to.or(revisions.first().map(|r| r.stamp));
+    let from = compare.from.or(revisions.get(1).map(|r| r.stamp));
+
+    let find = |stamp: Option<i64>| -> Result<Option<&Revision>, AppError> {
+        match stamp {
+            Some(stamp) => revisions
+                .iter()
+                .find(|r| r.stamp == stamp)
+                .map(Some)
+                .ok_or_else(|| AppError::Custom {
+                    status: StatusCode::NOT_FOUND.as_u16(),
+                    message: "Revision not found".to_string(),
+                }),
+            None => Ok(None),
+        }
+    };
+
+    let diff = match (find(from)?, find(to)?) {
+        (Some(old), Some(new)) => Some(Diff::new(&old.content, &new.content)),
+        _ => None,
+    };
+
+    let history = History {
+        revisions: revisions
+            .iter()
+            .map(|r| RevisionSummary {
+                stamp: r.stamp,
+                seen_at: r.stamp.div_euclid(1000),
+                edit_date: r.edit_date,
+                size: r.content.len(),
+            })
+            .collect(),
+        id,
+        from,
+        to,
+        split: compare.layout.as_deref() == Some("split"),
+        diff,
+    };
+
+    let ctx = Context::from_serialize(&history).map_err(AppError::Template)?;
+    let rendered = TEMPLATES
+        .render("history.html", &ctx)
+        .map_err(AppError::Template)?;
+
+    create_html_response(rendered, 200)
+}
@@ -7,7 +7,7 @@ use axum::{
     routing,
 };
 
-use crate::{constants::URL, state::AppState};
+use crate::state::AppState;
 
 use super::error::{Error, ErrorSource, render_error};
 
@@ -63,10 +63,7 @@ async fn icon(
                 Ok(Some(data)) => data.to_vec(),
                 Ok(None) => {
                     // Race condition - key was deleted between check and get
-                    match state
-                        .client
-                        .get_bytes(format!("{URL}/cache/img/{path}.jpg").as_str())
-                    {
+                    match state.backend.get_icon(&path).await {
                         Ok(icon_data) => {
                             let save_icon = icon_data.clone();
                             tokio::spawn(async move {
@@ -93,10 +90,7 @@ async fn icon(
             }
         }
         Ok(false) => {
-            match state
-                .client
-                .get_bytes(format!("{URL}/cache/img/{path}.jpg").as_str())
-            {
+            match state.backend.get_icon(&path).await {
                 Ok(icon_data) => {
                     let save_icon = icon_data.clone();
                     tokio::spawn(async move {
@@ -1,9 +1,9 @@
-use axum::{Json, Router, body::Body, extract::State, response::Response, routing};
+use axum::{Json, Router, body::Body, extract::State, middleware, response::Response, routing};
 use serde::{Deserialize, Serialize};
 use std::sync::OnceLock;
 use tera::Context;
 
-use super::error::{Error, render_error, create_fallback_response};
+use super::error::{self, Error, render_error, create_fallback_response};
 use crate::{state::AppState, templates::TEMPLATES};
 
 pub static DEPLOY_DATE: OnceLock<String> = OnceLock::new();
@@ -19,6 +19,8 @@ struct InstanceInfo {
     build_date: &'static str,
     deploy_date: &'static str,
     static_templates: bool,
+    backend: &'static str,
+    upstream_url: String,
 }
 
 fn get_info(state: AppState) -> InstanceInfo {
@@ -50,13 +52,18 @@ fn get_info(state: AppState) -> InstanceInfo {
         build_date,
         deploy_date,
         static_templates: cfg!(feature = "include_templates"),
+        backend: state.backend.name(),
+        upstream_url: state.upstream_url.to_string(),
     }
 }
 
 pub fn get_router(state: AppState) -> Router {
     Router::new()
         .route("/", routing::get(info))
-        .route("/json", routing::get(info_json))
+        .route(
+            "/json",
+            routing::get(info_json).layer(middleware::from_fn(error::json_errors)),
+        )
         .with_state(state)
 }
 
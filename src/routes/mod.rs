@@ -1,24 +1,37 @@
-use axum::Router;
+use axum::{Router, middleware};
 
 use crate::state::AppState;
 
+mod account;
+mod actions;
+mod api;
 mod archive;
+mod edit;
 mod error;
+mod history;
 mod imgs;
 pub mod info;
 mod post;
 mod public;
+mod snapshots;
 mod users;
 mod view;
 
 pub fn get_router(state: AppState) -> Router {
     Router::new()
+        .nest("/api", api::get_router(state.clone()))
         .nest("/info", info::get_router(state.clone()))
         .nest("/archive", archive::get_router(state.clone()))
         .nest("/u", users::get_router(state.clone()))
         .nest("/imgs", imgs::get_router(state.clone()))
+        .nest("/snapshots", snapshots::get_router(state.clone()))
+        .nest("/history", history::get_router(state.clone()))
+        .merge(account::get_router(state.clone()))
+        .merge(actions::get_router(state.clone()))
+        .merge(edit::get_router(state.clone()))
         .merge(post::get_router(state.clone()))
         .merge(public::get_router())
         .merge(view::get_router(state.clone()))
         .fallback(error::error_404)
+        .layer(middleware::from_fn(error::negotiate_errors))
 }
@@ -1,12 +1,34 @@
 use axum::{
-    Form, Router, body::Body, extract::State, http::StatusCode, response::Response, routing,
+    Form, Router,
+    body::Body,
+    extract::{
+        FromRequest, Multipart, Path, Request, State,
+        multipart::{Field, MultipartError},
+    },
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Redirect, Response},
+    routing,
 };
 use serde::Deserialize;
+use std::str::FromStr;
 use tera::Context;
 
-use crate::{constants::URL, parsers::paste, state::AppState, templates::TEMPLATES};
+use crate::{backends::NewPaste, catalog, detect, state::AppState, templates::TEMPLATES};
 
-use super::error::{self, Error, ErrorSource, render_error, create_fallback_response};
+use super::account::{cross_origin, signed_in};
+use super::error::{self, Error, ErrorCode, ErrorSource, create_fallback_response, render_error};
+
+/// Largest paste pastebin accepts from guests and free accounts
+pub(super) const MAX_PASTE_BYTES: usize = 512 * 1024;
+
+/// Exposure of comments, which are listed under their paste for everyone
+const PUBLIC_EXPOSURE: u8 = 0;
+
+/// Exposure of pastes only their signed-in author can see
+pub(super) const PRIVATE_EXPOSURE: u8 = 2;
+
+/// Format of the form's "Auto" option, replaced by a detected format before posting
+pub(super) const AUTO_FORMAT: u16 = 0;
 
 #[derive(Deserialize)]
 struct Post {
@@ -20,20 +42,260 @@ struct Post {
     title: String,
 }
 
+/// The reply form under a paste, which only offers the format and expiration
+#[derive(Deserialize)]
+struct Reply {
+    text: String,
+    format: u16,
+    expiration: String,
+}
+
+impl From<Post> for NewPaste {
+    fn from(data: Post) -> Self {
+        NewPaste {
+            text: data.text,
+            category: data.category,
+            tags: data.tags,
+            format: data.format,
+            expiration: data.expiration,
+            exposure: data.exposure,
+            password: data.password,
+            title: data.title,
+        }
+    }
+}
+
+pub(super) fn default_format() -> u16 {
+    catalog::TEXT
+}
+
+pub(super) fn default_expiration() -> String {
+    "N".to_string()
+}
+
+pub(super) fn default_exposure() -> u8 {
+    1
+}
+
+/// The paste form's fields other than the paste itself, all optional, as sent
+/// in multipart bodies or query strings
+#[derive(Deserialize, Default)]
+pub(super) struct PasteOptions {
+    title: Option<String>,
+    format: Option<u16>,
+    category: Option<u8>,
+    /// Comma separated
+    tags: Option<String>,
+    expiration: Option<String>,
+    exposure: Option<u8>,
+    password: Option<String>,
+}
+
+fn number<T: FromStr>(name: &str, value: &str) -> Result<T, String> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| format!("`{name}` must be a number"))
+}
+
+impl PasteOptions {
+    pub(super) fn set(&mut self, name: &str, value: String) -> Result<(), String> {
+        match name {
+            "title" => self.title = Some(value),
+            "format" => self.format = Some(number(name, &value)?),
+            "category" => self.category = Some(number(name, &value)?),
+            "tags" => self.tags = Some(value),
+            "expiration" => self.expiration = Some(value),
+            "exposure" => self.exposure = Some(number(name, &value)?),
+            "password" => self.password = Some(value),
+            _ => return Err(format!("Unknown field `{name}`")),
+        }
+
+        Ok(())
+    }
+
+    pub(super) fn with_text(self, text: String) -> NewPaste {
+        NewPaste {
+            text,
+            category: self.category.unwrap_or_default(),
+            tags: self
+                .tags
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|tag| !tag.is_empty())
+                .collect::<Vec<_>>()
+                .join(","),
+            format: self.format.unwrap_or_else(default_format),
+            expiration: self.expiration.unwrap_or_else(default_expiration),
+            exposure: self.exposure.unwrap_or_else(default_exposure),
+            password: self.password.unwrap_or_default(),
+            title: self.title.unwrap_or_default(),
+        }
+    }
+}
+
+fn bad_request(message: String) -> Error {
+    Error::new(
+        StatusCode::BAD_REQUEST.as_u16(),
+        message,
+        ErrorSource::Internal,
+    )
+    .with_code(ErrorCode::BadRequest)
+}
+
+// Bodies over the request limit are refused like pastes over upstream's
+fn multipart_error(e: MultipartError) -> Error {
+    if e.status() != StatusCode::PAYLOAD_TOO_LARGE {
+        return bad_request(e.body_text());
+    }
+
+    Error::new(
+        StatusCode::PAYLOAD_TOO_LARGE.as_u16(),
+        format!(
+            "The upload is too large, pastebin accepts at most {} KB",
+            MAX_PASTE_BYTES / 1024
+        ),
+        ErrorSource::Internal,
+    )
+    .with_code(ErrorCode::TooLarge)
+}
+
+async fn field_text(field: Field<'_>, name: &str) -> Result<String, Error> {
+    let bytes = field.bytes().await.map_err(multipart_error)?;
+
+    String::from_utf8(bytes.to_vec()).map_err(|_| match name {
+        "file" => bad_request("The file must be UTF-8 text".to_string()),
+        _ => bad_request(format!("`{name}` must be UTF-8 text")),
+    })
+}
+
+/// Reads a paste from a multipart body, taken from its `file` part when a file
+/// was attached and its `text` part otherwise. Uploaded files name the paste
+/// and pick its format from their extension unless those were given.
+pub(super) async fn read_multipart(
+    mut multipart: Multipart,
+    mut options: PasteOptions,
+) -> Result<NewPaste, Error> {
+    let mut text = None;
+    let mut file = None;
+
+    while let Some(field) = multipart.next_field().await.map_err(multipart_error)? {
+        let name = field.name().unwrap_or_default().to_string();
+        let filename = field.file_name().unwrap_or_default().to_string();
+        let value = field_text(field, &name).await?;
+
+        match name.as_str() {
+            "text" => text = Some(value),
+            // Browsers send an empty part when no file was picked
+            "file" if filename.is_empty() && value.is_empty() => {}
+            "file" => file = Some((filename, value)),
+            _ => options.set(&name, value).map_err(bad_request)?,
+        }
+    }
+
+    let mut filename = None;
+    if let Some((name, content)) = file {
+        if !name.is_empty() {
+            if options.title.as_deref().is_none_or(str::is_empty) {
+                options.title = Some(name.clone());
+            }
+            if options
+                .format
+                .is_none_or(|format| format == default_format())
+            {
+                options.format = catalog::format_by_filename(&name)
+                    .map(|format| format.id)
+                    .or(options.format);
+            }
+            filename = Some(name);
+        }
+        text = Some(content);
+    }
+
+    let text = text.ok_or_else(|| bad_request("Missing `text` or `file` field".to_string()))?;
+    let mut paste = options.with_text(text);
+    resolve_format(&mut paste, filename.as_deref());
+    Ok(paste)
+}
+
+/// Replaces the "Auto" format with one detected from the paste and its file name
+pub(super) fn resolve_format(paste: &mut NewPaste, filename: Option<&str>) {
+    if paste.format == AUTO_FORMAT {
+        paste.format = detect::format_id(&paste.text, filename);
+    }
+}
+
+/// Rejects pastes upstream would refuse, before sending them
+pub(super) fn validate(paste: &NewPaste, signed_in: bool) -> Result<(), Error> {
+    let bad_request = |message: String| {
+        Err(Error::new(
+            StatusCode::BAD_REQUEST.as_u16(),
+            message,
+            ErrorSource::Internal,
+        ))
+    };
+
+    if paste.text.trim().is_empty() {
+        return bad_request("The paste is empty".to_string());
+    }
+
+    if catalog::format(paste.format).is_none() {
+        return bad_request(format!("Unknown format {}", paste.format));
+    }
+
+    if catalog::category(paste.category).is_none() {
+        return bad_request(format!("Unknown category {}", paste.category));
+    }
+
+    if paste.exposure == PRIVATE_EXPOSURE && !signed_in {
+        return Err(Error::new(
+            StatusCode::FORBIDDEN.as_u16(),
+            "Log in to create private pastes".to_string(),
+            ErrorSource::Internal,
+        ));
+    }
+
+    if paste.text.len() > MAX_PASTE_BYTES {
+        return Err(Error::new(
+            StatusCode::PAYLOAD_TOO_LARGE.as_u16(),
+            format!(
+                "The paste is {} KB, pastebin accepts at most {} KB",
+                paste.text.len().div_ceil(1024),
+                MAX_PASTE_BYTES / 1024
+            ),
+            ErrorSource::Internal,
+        )
+        .with_code(ErrorCode::TooLarge));
+    }
+
+    Ok(())
+}
+
 pub fn get_router(state: AppState) -> Router {
     Router::new()
         .route("/", routing::get(post).post(post_create))
+        .route("/comment/{id}", routing::post(comment_create))
         .with_state(state)
 }
 
-async fn post() -> Result<Response<Body>, Response<Body>> {
+async fn post(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Response<Body>, Response<Body>> {
+    let mut context = Context::new();
+    if let Some(account) = signed_in(&state, &headers) {
+        context.insert("signed_in_as", &account.username);
+    }
+
     TEMPLATES
-        .render("post.html", &Context::new())
+        .render("post.html", &context)
         .map(|html| {
             Response::builder()
                 .status(200)
                 .header("Content-Type", "text/html")
-                .header("Cache-Control", "public, max-age=31536000, immutable")
+                // The form depends on who is signed in
+                .header("Cache-Control", "private, no-cache")
                 .body(Body::new(html))
                 .unwrap_or_else(|e| {
                     eprintln!("Failed to build post response: {}", e);
@@ -43,79 +305,52 @@ async fn post() -> Result<Response<Body>, Response<Body>> {
         .map_err(|e| render_error(Error::from(e)))
 }
 
+/// Reads and checks a paste sent from `post.html`, as a multipart or a plain form
+pub(super) async fn read_paste_form(
+    request: Request,
+    state: &AppState,
+    signed_in: bool,
+) -> Result<NewPaste, Response<Body>> {
+    let is_multipart = request
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("multipart/form-data"));
+
+    let mut paste = if is_multipart {
+        let multipart = Multipart::from_request(request, state)
+            .await
+            .map_err(|e| render_error(bad_request(e.body_text())))?;
+        read_multipart(multipart, PasteOptions::default())
+            .await
+            .map_err(render_error)?
+    } else {
+        let Form(data) = Form::<Post>::from_request(request, state)
+            .await
+            .map_err(|e| render_error(bad_request(e.body_text())))?;
+        data.into()
+    };
+
+    resolve_format(&mut paste, None);
+    validate(&paste, signed_in).map_err(render_error)?;
+    Ok(paste)
+}
+
 async fn post_create(
     State(state): State<AppState>,
-    Form(data): Form<Post>,
+    request: Request,
 ) -> Result<Response<Body>, Response<Body>> {
-    let csrf = state
-        .client
-        .get_html(format!("{URL}/").as_str())
-        .map_err(|e| error::construct_error(e))?;
-
-    let csrf = paste::get_csrftoken(&csrf).unwrap_or_default();
-
-    let form: Vec<(String, String)> = vec![
-        ("_csrf-frontend".to_string(), csrf),
-        ("PostForm[text]".to_string(), data.text),
-        (
-            "PostForm[category_id]".to_string(),
-            data.category.to_string(),
-        ),
-        ("PostForm[tag]".to_string(), data.tags),
-        ("PostForm[format]".to_string(), data.format.to_string()),
-        (
-            "PostForm[expiration]".to_string(),
-            data.expiration.to_string(),
-        ),
-        ("PostForm[status]".to_string(), data.exposure.to_string()),
-        (
-            "PostForm[is_password_enabled]".to_string(),
-            (if data.password.is_empty() { "0" } else { "1" }).to_string(),
-        ),
-        ("PostForm[password]".to_string(), data.password),
-        (
-            "PostForm[is_burn]".to_string(),
-            (if data.expiration == "B" { "1" } else { "0" }).to_string(),
-        ),
-        ("PostForm[name]".to_string(), data.title),
-        ("PostForm[is_guest]".to_string(), "1".to_string()),
-    ];
+    let account = signed_in(&state, request.headers());
+    let paste = read_paste_form(request, &state, account.is_some()).await?;
 
-    let response = state
-        .client
-        .post_response(format!("{URL}/").as_str(), form)
-        .map_err(|e| error::construct_error(e))?;
+    let paste_id = state
+        .backend
+        .create_paste(paste, account.as_ref())
+        .await
+        .map_err(error::construct_error)?;
 
-    let paste_id = response
-        .headers()
-        .get("Location")
-        .ok_or_else(|| {
-            render_error(Error::new(
-                StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
-                "Missing Location header".to_string(),
-                ErrorSource::Internal,
-            ))
-        })?
-        .to_str()
-        .map_err(|e| {
-            render_error(Error::new(
-                StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
-                format!("Invalid Location header encoding: {}", e),
-                ErrorSource::Internal,
-            ))
-        })?
-        .split("/")
-        .last()
-        .ok_or_else(|| {
-            render_error(Error::new(
-                StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
-                "Invalid Location header".to_string(),
-                ErrorSource::Internal,
-            ))
-        })?;
-
-    Ok(Response::builder()
-        .status(response.status())
+    Response::builder()
+        .status(StatusCode::FOUND)
         .header("Location", format!("/{paste_id}"))
         .header("Content-Type", "text/html")
         .body(Body::empty())
@@ -125,5 +360,248 @@ async fn post_create(
                 format!("Failed to build redirect response: {}", e),
                 ErrorSource::Internal,
             ))
-        })?)
+        })
+}
+
+async fn comment_create(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    Form(reply): Form<Reply>,
+) -> Result<Response<Body>, Response<Body>> {
+    if let Some(response) = cross_origin(&headers) {
+        return Err(response);
+    }
+
+    let account = signed_in(&state, &headers);
+    let options = PasteOptions {
+        format: Some(reply.format),
+        expiration: Some(reply.expiration),
+        exposure: Some(PUBLIC_EXPOSURE),
+        ..Default::default()
+    };
+    let mut comment = options.with_text(reply.text);
+
+    resolve_format(&mut comment, None);
+    validate(&comment, account.is_some()).map_err(render_error)?;
+
+    state
+        .backend
+        .create_comment(&id, comment, account.as_ref())
+        .await
+        .map_err(error::construct_error)?;
+
+    Ok(Redirect::to(&format!("/{id}#comments")).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::backends::mock::MockBackend;
+
+    use super::*;
+
+    #[test]
+    fn test_options_fill_defaults() {
+        let mut options = PasteOptions::default();
+        options.set("format", "42".to_string()).expect("Valid");
+        options.set("tags", "a, b,,c".to_string()).expect("Valid");
+
+        let paste = options.with_text("print()".to_string());
+        assert_eq!(paste.format, 42);
+        assert_eq!(paste.tags, "a,b,c");
+        assert_eq!(paste.expiration, "N");
+        assert_eq!(paste.exposure, 1);
+
+        let mut options = PasteOptions::default();
+        assert!(options.set("format", "python".to_string()).is_err());
+        assert!(options.set("unknown", String::new()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_uploaded_file_names_the_paste() {
+        let body = concat!(
+            "--X\r\n",
+            "Content-Disposition: form-data; name=\"text\"\r\n\r\n",
+            "\r\n",
+            "--X\r\n",
+            "Content-Disposition: form-data; name=\"format\"\r\n\r\n",
+            "1\r\n",
+            "--X\r\n",
+            "Content-Disposition: form-data; name=\"file\"; filename=\"main.rs\"\r\n",
+            "Content-Type: application/octet-stream\r\n\r\n",
+            "fn main() {}\r\n",
+            "--X--\r\n",
+        );
+        let request = Request::builder()
+            .header(header::CONTENT_TYPE, "multipart/form-data; boundary=X")
+            .body(Body::from(body))
+            .expect("Valid request");
+        let multipart = Multipart::from_request(request, &())
+            .await
+            .expect("Multipart body");
+
+        let paste = read_multipart(multipart, PasteOptions::default())
+            .await
+            .expect("Valid paste");
+        assert_eq!(paste.text, "fn main() {}");
+        assert_eq!(paste.title, "main.rs");
+        assert_eq!(paste.format, 248);
+    }
+
+    async fn upload(content: Vec<u8>) -> Result<NewPaste, Error> {
+        let mut body = concat!(
+            "--X\r\n",
+            "Content-Disposition: form-data; name=\"file\"; filename=\"upload.bin\"\r\n",
+            "Content-Type: application/octet-stream\r\n\r\n",
+        )
+        .as_bytes()
+        .to_vec();
+        body.extend(content);
+        body.extend(b"\r\n--X--\r\n");
+
+        let request = Request::builder()
+            .header(header::CONTENT_TYPE, "multipart/form-data; boundary=X")
+            .body(Body::from(body))
+            .expect("Valid request");
+        let multipart = Multipart::from_request(request, &())
+            .await
+            .expect("Multipart body");
+
+        read_multipart(multipart, PasteOptions::default()).await
+    }
+
+    #[tokio::test]
+    async fn test_uploads_over_the_body_limit_are_too_large() {
+        // Above axum's default body limit of 2 MB
+        let error = upload(vec![b'x'; 3 * 1024 * 1024])
+            .await
+            .err()
+            .expect("Too large");
+        let error = serde_json::to_value(error).expect("Serializable error");
+
+        assert_eq!(error["status"], 413);
+        assert_eq!(error["code"], "too_large");
+    }
+
+    #[tokio::test]
+    async fn test_binary_uploads_are_rejected() {
+        let error = upload(vec![0xff, 0xfe, 0x00])
+            .await
+            .err()
+            .expect("Not text");
+        let error = serde_json::to_value(error).expect("Serializable error");
+
+        assert_eq!(error["status"], 400);
+        assert_eq!(error["message"], "The file must be UTF-8 text");
+    }
+
+    #[test]
+    fn test_auto_format_is_detected() {
+        let mut options = PasteOptions::default();
+        options
+            .set("format", AUTO_FORMAT.to_string())
+            .expect("Valid");
+        let mut paste = options.with_text("#!/usr/bin/env python3\nprint(1)".to_string());
+
+        resolve_format(&mut paste, None);
+        assert_eq!(paste.format, 42);
+    }
+
+    #[test]
+    fn test_validate_format_and_category() {
+        let mut options = PasteOptions::default();
+        options.set("format", "999".to_string()).expect("Valid");
+        assert!(validate(&options.with_text("x".to_string()), false).is_err());
+
+        let mut options = PasteOptions::default();
+        options.set("category", "1".to_string()).expect("Valid");
+        assert!(validate(&options.with_text("x".to_string()), false).is_err());
+
+        let mut options = PasteOptions::default();
+        options.set("category", "27".to_string()).expect("Valid");
+        assert!(validate(&options.with_text("x".to_string()), false).is_ok());
+    }
+
+    #[test]
+    fn test_private_pastes_need_an_account() {
+        let mut options = PasteOptions::default();
+        options.set("exposure", "2".to_string()).expect("Valid");
+        let paste = options.with_text("x".to_string());
+
+        assert!(validate(&paste, false).is_err());
+        assert!(validate(&paste, true).is_ok());
+    }
+
+    #[test]
+    fn test_validate_size_limit() {
+        let paste = PasteOptions::default().with_text("a".repeat(MAX_PASTE_BYTES + 1));
+        assert!(validate(&paste, false).is_err());
+
+        let paste = PasteOptions::default().with_text("a".repeat(MAX_PASTE_BYTES));
+        assert!(validate(&paste, false).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_comments_redirect_to_their_paste() {
+        let state = AppState::with_backend(Arc::new(MockBackend::default()));
+        let reply = |text: &str| {
+            Form(Reply {
+                text: text.to_string(),
+                format: AUTO_FORMAT,
+                expiration: "1D".to_string(),
+            })
+        };
+
+        let response = comment_create(
+            State(state.clone()),
+            Path("abc123".to_string()),
+            HeaderMap::new(),
+            reply("Thanks!"),
+        )
+        .await
+        .expect("Comment created");
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        assert_eq!(
+            response.headers().get(header::LOCATION),
+            Some(&"/abc123#comments".parse().expect("Valid header"))
+        );
+
+        let response = comment_create(
+            State(state),
+            Path("abc123".to_string()),
+            HeaderMap::new(),
+            reply(""),
+        )
+        .await;
+        assert!(response.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_comments_from_other_sites_are_rejected() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::HOST,
+            "pasted.example".parse().expect("Valid header"),
+        );
+        headers.insert(
+            header::ORIGIN,
+            "https://evil.example".parse().expect("Valid header"),
+        );
+
+        let response = comment_create(
+            State(AppState::with_backend(Arc::new(MockBackend::default()))),
+            Path("abc123".to_string()),
+            headers,
+            Form(Reply {
+                text: "Thanks!".to_string(),
+                format: AUTO_FORMAT,
+                expiration: "1D".to_string(),
+            }),
+        )
+        .await
+        .expect_err("Rejected");
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
 }
@@ -0,0 +1,104 @@
+use axum::{
+    Router,
+    body::Body,
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing,
+};
+use serde::Serialize;
+use tera::Context;
+
+use crate::{
+    snapshots::{Snapshot, SnapshotStore},
+    state::AppState,
+    templates::TEMPLATES,
+};
+
+use super::error::{self, AppError, Error as PasteError};
+
+// Helper function to create HTML responses
+fn create_html_response(content: String, status: u16) -> Result<Response<Body>, AppError> {
+    Response::builder()
+        .status(status)
+        .header("Content-Type", "text/html")
+        .body(Body::from(content))
+        .map_err(|e| AppError::Server(format!("Failed to build response: {}", e)))
+}
+
+// Helper function to get the snapshot store, which only exists when snapshots are enabled
+fn store(state: &AppState) -> Result<&SnapshotStore, AppError> {
+    state.snapshots.as_deref().ok_or_else(|| AppError::Custom {
+        status: StatusCode::NOT_FOUND.as_u16(),
+        message: "Snapshots are disabled on this instance".to_string(),
+    })
+}
+
+#[derive(Serialize)]
+struct SnapshotList {
+    id: String,
+    snapshots: Vec<Snapshot>,
+}
+
+pub fn get_router(state: AppState) -> Router {
+    Router::new()
+        .route("/{id}", routing::get(snapshots))
+        .route("/{id}/{stamp}", routing::get(snapshot))
+        .with_state(state)
+}
+
+async fn snapshots(State(state): State<AppState>, Path(id): Path<String>) -> impl IntoResponse {
+    let store = match store(&state) {
+        Ok(store) => store,
+        Err(app_err) => return error::render_error(PasteError::from(app_err)),
+    };
+
+    let list = SnapshotList {
+        snapshots: store.list(&id),
+        id,
+    };
+
+    let rendered = Context::from_serialize(&list)
+        .and_then(|ctx| TEMPLATES.render("snapshots.html", &ctx))
+        .map_err(AppError::Template);
+
+    match rendered.and_then(|rendered| create_html_response(rendered, 200)) {
+        Ok(response) => response,
+        Err(app_err) => error::render_error(PasteError::from(app_err)),
+    }
+}
+
+async fn snapshot(
+    State(state): State<AppState>,
+    Path((id, stamp)): Path<(String, i64)>,
+) -> impl IntoResponse {
+    let store = match store(&state) {
+        Ok(store) => store,
+        Err(app_err) => return error::render_error(PasteError::from(app_err)),
+    };
+
+    let Some(Snapshot {
+        captured_at,
+        mut paste,
+        ..
+    }) = store.get(&id, stamp)
+    else {
+        return error::render_error(PasteError::from(AppError::Custom {
+            status: StatusCode::NOT_FOUND.as_u16(),
+            message: "Snapshot not found".to_string(),
+        }));
+    };
+    paste.set_captured_at(captured_at);
+
+    let rendered = Context::from_serialize(&paste)
+        .and_then(|mut ctx| {
+            ctx.insert("snapshot", &true);
+            TEMPLATES.render("view.html", &ctx)
+        })
+        .map_err(AppError::Template);
+
+    match rendered.and_then(|rendered| create_html_response(rendered, 200)) {
+        Ok(response) => response,
+        Err(app_err) => error::render_error(PasteError::from(app_err)),
+    }
+}
@@ -2,17 +2,13 @@ use axum::{
     Json, Router,
     body::Body,
     extract::{Path, State},
+    middleware,
     response::{IntoResponse, Response},
     routing,
 };
 use tera::Context;
 
-use crate::{
-    constants::URL,
-    parsers::{FromHtml as _, user::User},
-    state::AppState,
-    templates::TEMPLATES,
-};
+use crate::{parsers::user::User, state::AppState, templates::TEMPLATES};
 
 use super::error::{self, AppError, Error, render_error};
 
@@ -21,10 +17,10 @@ fn safe_render_template<T: serde::Serialize>(
     template_name: &str,
     context: &T,
 ) -> Result<String, AppError> {
-    let ctx = Context::from_serialize(context).map_err(|e| AppError::Template(e))?;
+    let ctx = Context::from_serialize(context).map_err(AppError::Template)?;
     TEMPLATES
         .render(template_name, &ctx)
-        .map_err(|e| AppError::Template(e))
+        .map_err(AppError::Template)
 }
 
 // Helper function to create HTML responses
@@ -39,30 +35,22 @@ fn create_html_response(content: String, status: u16) -> Result<Response<Body>,
 pub fn get_router(state: AppState) -> Router {
     Router::new()
         .route("/{username}", routing::get(user))
-        .route("/json/{username}", routing::get(json_user))
+        .route(
+            "/json/{username}",
+            routing::get(json_user).layer(middleware::from_fn(error::json_errors)),
+        )
         .with_state(state)
 }
 
 async fn user(State(state): State<AppState>, Path(username): Path<String>) -> impl IntoResponse {
-    let dom = state.client.get_html(&format!("{URL}/u/{username}"));
-    match dom {
-        Ok(dom) => {
-            let user = match User::from_html(&dom) {
-                Ok(u) => u,
-                Err(e) => return render_error(Error::new(
-                    500,
-                    format!("Failed to parse user page: {}", e),
-                    error::ErrorSource::Internal,
-                )),
-            };
-            match safe_render_template("user.html", &user) {
-                Ok(rendered) => match create_html_response(rendered, 200) {
-                    Ok(response) => response,
-                    Err(app_err) => render_error(Error::from(app_err)),
-                },
+    match state.backend.get_user(&username).await {
+        Ok(user) => match safe_render_template("user.html", &user) {
+            Ok(rendered) => match create_html_response(rendered, 200) {
+                Ok(response) => response,
                 Err(app_err) => render_error(Error::from(app_err)),
-            }
-        }
+            },
+            Err(app_err) => render_error(Error::from(app_err)),
+        },
         Err(err) => error::construct_error(err),
     }
 }
@@ -71,18 +59,11 @@ async fn json_user(
     State(state): State<AppState>,
     Path(username): Path<String>,
 ) -> Result<Json<User>, Response<Body>> {
-    let dom = state
-        .client
-        .get_html(&format!("{URL}/u/{username}"))
-        .map_err(|e| error::construct_error(e))?;
+    let user = state
+        .backend
+        .get_user(&username)
+        .await
+        .map_err(error::construct_error)?;
 
-    let user = User::from_html(&dom)
-        .map_err(|e| {
-            error::render_error(error::Error::new(
-                500,
-                format!("Failed to parse user page: {}", e),
-                error::ErrorSource::Internal,
-            ))
-        })?;
     Ok(Json(user))
 }
@@ -1,33 +1,36 @@
 use crate::{
-    constants::URL,
-    parsers::{
-        FromHtml as _,
-        paste::{self, Paste},
-    },
+    backends::PasteView,
+    catalog::{self, Format},
+    lines::LineRanges,
+    parsers::paste::Paste,
     state::AppState,
+    tar::Tar,
     templates::TEMPLATES,
 };
 use axum::{
     Form, Json, Router,
     body::Body,
-    extract::{Path, State},
-    response::{IntoResponse, Response},
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode, response},
+    middleware,
+    response::{IntoResponse, Redirect, Response},
     routing,
 };
 use serde::{Deserialize, Serialize};
 use tera::Context;
 
-use super::error::{self, AppError, Error as PasteError, ErrorSource};
+use super::account::signed_in;
+use super::error::{self, AppError, Error as PasteError, ErrorCode, ErrorSource};
 
 // Helper function to render templates safely
 fn safe_render_template<T: serde::Serialize>(
     template_name: &str,
     context: &T,
 ) -> Result<String, AppError> {
-    let ctx = Context::from_serialize(context).map_err(|e| AppError::Template(e))?;
+    let ctx = Context::from_serialize(context).map_err(AppError::Template)?;
     TEMPLATES
         .render(template_name, &ctx)
-        .map_err(|e| AppError::Template(e))
+        .map_err(AppError::Template)
 }
 
 // Helper function to create HTML responses
@@ -39,17 +42,41 @@ fn create_html_response(content: String, status: u16) -> Result<Response<Body>,
         .map_err(|e| AppError::Server(format!("Failed to build response: {}", e)))
 }
 
-// Helper function to safely parse paste from HTML
-fn parse_paste_safe(dom: &scraper::Html) -> Result<Paste, Response<Body>> {
-    Paste::from_html(dom).map_err(|e| {
-        error::render_error(PasteError::new(
-            500,
-            format!("Failed to parse paste: {}", e),
-            ErrorSource::Internal,
-        ))
-    })
+// Helper function to fetch a paste that can be shown without unlocking it
+pub(super) async fn fetch_paste(state: &AppState, id: &str) -> Result<Paste, Response<Body>> {
+    match state.backend.get_paste(id).await {
+        Ok(PasteView::Paste(paste)) => Ok(*paste),
+        Ok(PasteView::Locked { .. }) => {
+            Err(error::render_error(
+                PasteError::from(AppError::Custom {
+                    status: 403,
+                    message: "This paste is password protected".to_string(),
+                })
+                .with_code(ErrorCode::Locked),
+            ))
+        }
+        Ok(PasteView::Burn) => Err(error::render_error(
+            PasteError::from(AppError::Custom {
+                status: 403,
+                message: "This paste will be burnt after read".to_string(),
+            })
+            .with_code(ErrorCode::Burn),
+        )),
+        Err(err) => Err(error::construct_error(err)),
+    }
 }
 
+// Helper function to flag copies served from cache rather than fresh from upstream
+fn with_captured_at(builder: response::Builder, captured_at: Option<i64>) -> response::Builder {
+    match captured_at {
+        Some(timestamp) => builder.header("X-Captured-At", timestamp),
+        None => builder,
+    }
+}
+
+/// Longest title kept in download names
+const MAX_FILENAME_CHARS: usize = 64;
+
 #[derive(Serialize)]
 struct Page {
     id: String,
@@ -61,6 +88,24 @@ struct LockScreen {
     burn: bool,
 }
 
+#[derive(Deserialize)]
+pub(super) struct LineSelection {
+    lines: Option<String>,
+}
+
+// Old pastebin links put the ID in a query parameter, e.g. `/raw.php?i=abc`
+#[derive(Deserialize)]
+struct LegacyRaw {
+    i: String,
+}
+
+#[derive(Deserialize)]
+struct DownloadOptions {
+    /// Bundle the paste and its comments into a tar archive
+    #[serde(default)]
+    comments: bool,
+}
+
 #[derive(Deserialize)]
 struct Unlock {
     password: Option<String>,
@@ -69,7 +114,11 @@ struct Unlock {
 pub fn get_router(state: AppState) -> Router {
     Router::new()
         .route("/raw/{id}", routing::get(view_raw))
-        .route("/json/{id}", routing::get(view_json))
+        .route("/raw.php", routing::get(view_raw_php))
+        .route(
+            "/json/{id}",
+            routing::get(view_json).layer(middleware::from_fn(error::json_errors)),
+        )
         .route("/dl/{id}", routing::get(view_download))
         .route("/print/{id}", routing::get(view_print))
         .route("/clone/{id}", routing::get(view_clone))
@@ -80,14 +129,36 @@ pub fn get_router(state: AppState) -> Router {
         .with_state(state)
 }
 
-async fn view_raw(State(state): State<AppState>, Path(id): Path<String>) -> impl IntoResponse {
-    let content = state.client.get_string(format!("{URL}/raw/{id}").as_str());
+pub(super) async fn view_raw(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(selection): Query<LineSelection>,
+) -> impl IntoResponse {
+    let ranges = match selection.lines.as_deref().map(str::parse::<LineRanges>) {
+        Some(Ok(ranges)) => Some(ranges),
+        Some(Err(e)) => {
+            return error::render_error(
+                PasteError::new(
+                    StatusCode::BAD_REQUEST.as_u16(),
+                    e.to_string(),
+                    ErrorSource::Internal,
+                )
+                .with_code(ErrorCode::BadRequest),
+            );
+        }
+        None => None,
+    };
+
+    let content = state.backend.get_raw(&id).await;
 
     match content {
-        Ok(content) => match Response::builder()
+        Ok(raw) => match with_captured_at(Response::builder(), raw.captured_at)
             .status(200)
             .header("Content-Type", "text/plain")
-            .body(Body::from(content))
+            .body(Body::from(match ranges {
+                Some(ranges) => ranges.slice(&raw.text),
+                None => raw.text,
+            }))
         {
             Ok(response) => response,
             Err(e) => error::render_error(PasteError::new(
@@ -100,73 +171,174 @@ async fn view_raw(State(state): State<AppState>, Path(id): Path<String>) -> impl
     }
 }
 
+async fn view_raw_php(Query(legacy): Query<LegacyRaw>) -> impl IntoResponse {
+    Redirect::permanent(&format!("/raw/{}", legacy.i))
+}
+
 async fn view_json(State(state): State<AppState>, Path(id): Path<String>) -> impl IntoResponse {
-    match state.client.get_html(format!("{URL}/{id}").as_str()) {
-        Ok(dom) => {
-            let paste = match parse_paste_safe(&dom) { Ok(p) => p, Err(e) => return e };
-            Json(paste).into_response()
+    match fetch_paste(&state, &id).await {
+        Ok(paste) => Json(paste).into_response(),
+        Err(err) => err,
+    }
+}
+
+// Keeps names to characters every file system and header accepts
+fn sanitize_filename(name: &str) -> String {
+    let mut clean = String::new();
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() || matches!(c, '-' | '.') {
+            clean.push(c);
+        } else if !clean.ends_with('_') {
+            clean.push('_');
         }
-        Err(err) => error::construct_error(err),
     }
+
+    clean
+        .trim_matches(['.', '_'])
+        .chars()
+        .take(MAX_FILENAME_CHARS)
+        .collect()
 }
 
-async fn view_download(State(state): State<AppState>, Path(id): Path<String>) -> impl IntoResponse {
-    let content = state.client.get_string(format!("{URL}/raw/{id}").as_str());
+/// Downloads are named after the paste's title, or its ID when it has none
+fn file_stem(title: Option<&str>, id: &str) -> String {
+    title
+        .map(sanitize_filename)
+        .filter(|stem| !stem.is_empty())
+        .unwrap_or_else(|| sanitize_filename(id))
+}
 
-    match content {
-        Ok(content) => match Response::builder()
-            .status(200)
-            .header("Content-Type", "text/plain")
-            .header(
-                "Content-Disposition",
-                format!("attachment; filename=\"{id}.txt\""),
-            )
-            .body(Body::from(content))
-        {
-            Ok(response) => response,
-            Err(e) => error::render_error(PasteError::new(
-                500,
-                format!("Failed to build download response: {}", e),
-                ErrorSource::Internal,
-            )),
-        },
-        Err(err) => error::construct_error(err),
+// Titles like `main.rs` already end in their format's extension
+fn file_name(stem: &str, format: &Format) -> String {
+    let has_extension = stem.rsplit_once('.').is_some_and(|(_, ext)| {
+        format
+            .extensions
+            .contains(&ext.to_ascii_lowercase().as_str())
+    });
+
+    if has_extension {
+        stem.to_string()
+    } else {
+        format!("{stem}.{}", format.extension())
     }
 }
 
-async fn view_print(State(state): State<AppState>, Path(id): Path<String>) -> impl IntoResponse {
-    let dom = state.client.get_html(format!("{URL}/{id}").as_str());
+// The paste with each comment in a `comments` folder, numbered in page order
+fn comments_archive(name: &str, text: &str, paste: &Paste) -> Vec<u8> {
+    let mut tar = Tar::default();
+    tar.append(name, text.as_bytes(), paste.edit_date.unwrap_or(paste.date));
 
-    match dom {
-        Ok(dom) => {
-            let paste = match parse_paste_safe(&dom) { Ok(p) => p, Err(e) => return e };
-            match safe_render_template("print.html", &paste) {
-                Ok(rendered) => match create_html_response(rendered, 200) {
-                    Ok(response) => response,
-                    Err(app_err) => error::render_error(PasteError::from(app_err)),
-                },
+    for (i, comment) in paste.comments.iter().enumerate() {
+        let format =
+            catalog::format_by_slug(&comment.container.format).unwrap_or(catalog::PLAIN_TEXT);
+        let stem = format!(
+            "{:03}-{}",
+            i + 1,
+            sanitize_filename(&comment.author.username)
+        );
+        tar.append(
+            &format!("comments/{}", file_name(&stem, format)),
+            comment.container.content.as_bytes(),
+            comment.edit_date.unwrap_or(comment.date),
+        );
+    }
+
+    tar.finish()
+}
+
+async fn view_download(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(options): Query<DownloadOptions>,
+) -> impl IntoResponse {
+    let (raw, paste) = tokio::join!(state.backend.get_raw(&id), fetch_paste(&state, &id));
+    let raw = match raw {
+        Ok(raw) => raw,
+        Err(err) => return error::construct_error(err),
+    };
+
+    // Without its details the paste is still served, as plain text named by its ID
+    let paste = match paste {
+        Ok(paste) => Some(paste),
+        Err(err) if options.comments => return err,
+        Err(_) => None,
+    };
+
+    let format = paste
+        .as_ref()
+        .and_then(|paste| catalog::format_by_slug(&paste.container.format))
+        .unwrap_or(catalog::PLAIN_TEXT);
+    let stem = file_stem(paste.as_ref().and_then(|paste| paste.title.as_deref()), &id);
+    let name = file_name(&stem, format);
+
+    let (content_type, filename, body) = match paste {
+        Some(paste) if options.comments => (
+            "application/x-tar".to_string(),
+            format!("{stem}.tar"),
+            comments_archive(&name, &raw.text, &paste),
+        ),
+        _ => (
+            format!("{}; charset=utf-8", format.mime),
+            name,
+            raw.text.into_bytes(),
+        ),
+    };
+
+    match with_captured_at(Response::builder(), raw.captured_at)
+        .status(200)
+        .header("Content-Type", content_type)
+        .header(
+            "Content-Disposition",
+            format!("attachment; filename=\"{filename}\""),
+        )
+        .body(Body::from(body))
+    {
+        Ok(response) => response,
+        Err(e) => error::render_error(PasteError::new(
+            500,
+            format!("Failed to build download response: {}", e),
+            ErrorSource::Internal,
+        )),
+    }
+}
+
+async fn view_print(State(state): State<AppState>, Path(id): Path<String>) -> impl IntoResponse {
+    match fetch_paste(&state, &id).await {
+        Ok(paste) => match safe_render_template("print.html", &paste) {
+            Ok(rendered) => match create_html_response(rendered, 200) {
+                Ok(response) => response,
                 Err(app_err) => error::render_error(PasteError::from(app_err)),
-            }
-        }
-        Err(err) => error::construct_error(err),
+            },
+            Err(app_err) => error::render_error(PasteError::from(app_err)),
+        },
+        Err(err) => err,
     }
 }
 
-async fn view_clone(State(state): State<AppState>, Path(id): Path<String>) -> impl IntoResponse {
-    let dom = state.client.get_html(format!("{URL}/{id}").as_str());
+async fn view_clone(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let account = signed_in(&state, &headers);
 
-    match dom {
-        Ok(dom) => {
-            let paste = match parse_paste_safe(&dom) { Ok(p) => p, Err(e) => return e };
-            match safe_render_template("post.html", &paste) {
-                Ok(rendered) => match create_html_response(rendered, 200) {
-                    Ok(response) => response,
-                    Err(app_err) => error::render_error(PasteError::from(app_err)),
-                },
+    match fetch_paste(&state, &id).await {
+        Ok(paste) => match Context::from_serialize(&paste)
+            .and_then(|mut ctx| {
+                if let Some(account) = account {
+                    ctx.insert("signed_in_as", &account.username);
+                }
+                TEMPLATES.render("post.html", &ctx)
+            })
+            .map_err(AppError::Template)
+        {
+            Ok(rendered) => match create_html_response(rendered, 200) {
+                Ok(response) => response,
                 Err(app_err) => error::render_error(PasteError::from(app_err)),
-            }
-        }
-        Err(err) => error::construct_error(err),
+            },
+            Err(app_err) => error::render_error(PasteError::from(app_err)),
+        },
+        Err(err) => err,
     }
 }
 
@@ -182,32 +354,26 @@ async fn view_embed(Path(id): Path<String>) -> impl IntoResponse {
 }
 
 async fn view_embed_js(State(state): State<AppState>, Path(id): Path<String>) -> impl IntoResponse {
-    let dom = state.client.get_html(format!("{URL}/{id}").as_str());
-
-    match dom {
-        Ok(dom) => {
-            let paste = match parse_paste_safe(&dom) { Ok(p) => p, Err(e) => return e };
-            match safe_render_template("embed_iframe.html", &paste) {
-                Ok(rendered) => {
-                    let js_content =
-                        format!("document.write('{}');", rendered.replace('\'', "\\'"));
-                    match Response::builder()
-                        .status(200)
-                        .header("Content-Type", "text/javascript")
-                        .body(Body::from(js_content))
-                    {
-                        Ok(response) => response,
-                        Err(e) => error::render_error(PasteError::new(
-                            500,
-                            format!("Failed to build JS response: {}", e),
-                            ErrorSource::Internal,
-                        )),
-                    }
+    match fetch_paste(&state, &id).await {
+        Ok(paste) => match safe_render_template("embed_iframe.html", &paste) {
+            Ok(rendered) => {
+                let js_content = format!("document.write('{}');", rendered.replace('\'', "\\'"));
+                match Response::builder()
+                    .status(200)
+                    .header("Content-Type", "text/javascript")
+                    .body(Body::from(js_content))
+                {
+                    Ok(response) => response,
+                    Err(e) => error::render_error(PasteError::new(
+                        500,
+                        format!("Failed to build JS response: {}", e),
+                        ErrorSource::Internal,
+                    )),
                 }
-                Err(app_err) => error::render_error(PasteError::from(app_err)),
             }
-        }
-        Err(err) => error::construct_error(err),
+            Err(app_err) => error::render_error(PasteError::from(app_err)),
+        },
+        Err(err) => err,
     }
 }
 
@@ -215,20 +381,15 @@ async fn view_embed_iframe(
     State(state): State<AppState>,
     Path(id): Path<String>,
 ) -> impl IntoResponse {
-    let dom = state.client.get_html(format!("{URL}/{id}").as_str());
-
-    match dom {
-        Ok(dom) => {
-            let paste = match parse_paste_safe(&dom) { Ok(p) => p, Err(e) => return e };
-            match safe_render_template("embed_iframe.html", &paste) {
-                Ok(rendered) => match create_html_response(rendered, 200) {
-                    Ok(response) => response,
-                    Err(app_err) => error::render_error(PasteError::from(app_err)),
-                },
+    match fetch_paste(&state, &id).await {
+        Ok(paste) => match safe_render_template("embed_iframe.html", &paste) {
+            Ok(rendered) => match create_html_response(rendered, 200) {
+                Ok(response) => response,
                 Err(app_err) => error::render_error(PasteError::from(app_err)),
-            }
-        }
-        Err(err) => error::construct_error(err),
+            },
+            Err(app_err) => error::render_error(PasteError::from(app_err)),
+        },
+        Err(err) => err,
     }
 }
 
@@ -237,64 +398,60 @@ async fn view_locked(
     Path(id): Path<String>,
     Form(data): Form<Unlock>,
 ) -> impl IntoResponse {
-    let csrf = match state.client.get_html(format!("{URL}/{id}").as_str()) {
-        Ok(dom) => paste::get_csrftoken(&dom).unwrap_or_default(),
-        Err(err) => return error::construct_error(err),
-    };
+    let paste = state
+        .backend
+        .unlock_paste(&id, &data.password.unwrap_or_default())
+        .await;
 
-    let form = vec![
-        ("_csrf-frontend".to_string(), csrf),
-        (
-            "PostPasswordVerificationForm[password]".to_string(),
-            data.password.unwrap_or_default(),
-        ),
-        ("is_burn".to_string(), "1".to_string()),
-    ];
-
-    let dom = state.client.post_html(format!("{URL}/{id}").as_str(), form);
-
-    match dom {
-        Ok(dom) => {
-            let paste = match parse_paste_safe(&dom) { Ok(p) => p, Err(e) => return e };
-            match safe_render_template("view.html", &paste) {
-                Ok(rendered) => match create_html_response(rendered, 200) {
-                    Ok(response) => response,
-                    Err(app_err) => error::render_error(PasteError::from(app_err)),
-                },
+    match paste {
+        Ok(paste) => match safe_render_template("view.html", &paste) {
+            Ok(rendered) => match create_html_response(rendered, 200) {
+                Ok(response) => response,
                 Err(app_err) => error::render_error(PasteError::from(app_err)),
-            }
-        }
+            },
+            Err(app_err) => error::render_error(PasteError::from(app_err)),
+        },
         Err(err) => error::construct_error(err),
     }
 }
 
-async fn view(State(state): State<AppState>, Path(id): Path<String>) -> impl IntoResponse {
-    let dom = match state.client.get_html(format!("{URL}/{id}").as_str()) {
-        Ok(dom) => dom,
+async fn view(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(selection): Query<LineSelection>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let account = signed_in(&state, &headers);
+
+    let view = match state.backend.get_paste(&id).await {
+        Ok(view) => view,
         Err(err) => return error::construct_error(err),
     };
 
-    let rendered = if paste::is_locked(&dom) {
-        let lock_screen = LockScreen {
-            id,
-            burn: paste::is_burn(&dom),
-        };
-        match safe_render_template("locked.html", &lock_screen) {
-            Ok(content) => content,
-            Err(app_err) => return error::render_error(PasteError::from(app_err)),
-        }
-    } else if paste::is_burn(&dom) {
-        let page = Page { id };
-        match safe_render_template("burn.html", &page) {
-            Ok(content) => content,
-            Err(app_err) => return error::render_error(PasteError::from(app_err)),
+    let rendered = match view {
+        PasteView::Locked { burn } => {
+            let lock_screen = LockScreen { id, burn };
+            safe_render_template("locked.html", &lock_screen)
         }
-    } else {
-        let paste = match parse_paste_safe(&dom) { Ok(p) => p, Err(e) => return e };
-        match safe_render_template("view.html", &paste) {
-            Ok(content) => content,
-            Err(app_err) => return error::render_error(PasteError::from(app_err)),
+        PasteView::Burn => {
+            let page = Page { id };
+            safe_render_template("burn.html", &page)
         }
+        PasteView::Paste(paste) => Context::from_serialize(&paste)
+            .and_then(|mut ctx| {
+                ctx.insert("lines", &selection.lines.unwrap_or_default());
+                ctx.insert("history", &state.revisions.is_some());
+                if let Some(account) = account {
+                    ctx.insert("signed_in_as", &account.username);
+                }
+                TEMPLATES.render("view.html", &ctx)
+            })
+            .map_err(AppError::Template),
+    };
+
+    let rendered = match rendered {
+        Ok(content) => content,
+        Err(app_err) => return error::render_error(PasteError::from(app_err)),
     };
 
     match create_html_response(rendered, 200) {
@@ -302,3 +459,96 @@ async fn view(State(state): State<AppState>, Path(id): Path<String>) -> impl Int
         Err(app_err) => error::render_error(PasteError::from(app_err)),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::backends::{mock::MockBackend, threaded::ThreadedBackend};
+
+    use super::*;
+
+    #[test]
+    fn test_download_names() {
+        let python = catalog::format_by_slug("python").expect("Known format");
+
+        assert_eq!(file_stem(Some("My script: v2!"), "abc"), "My_script_v2");
+        assert_eq!(file_stem(Some("../../etc/passwd"), "abc"), "etc_passwd");
+        assert_eq!(file_stem(Some("日本語"), "abc"), "abc");
+        assert_eq!(file_stem(None, "abc"), "abc");
+
+        assert_eq!(file_name("script", python), "script.py");
+        assert_eq!(file_name("script.PY", python), "script.PY");
+        assert_eq!(file_name("v1.2", catalog::PLAIN_TEXT), "v1.2.txt");
+    }
+
+    #[tokio::test]
+    async fn test_comment_threads_are_nested() {
+        let backend = ThreadedBackend::new(Arc::new(MockBackend::default()), 1);
+        let state = AppState::with_backend(Arc::new(backend));
+
+        let response = view(
+            State(state),
+            Path("abc".to_string()),
+            Query(LineSelection { lines: None }),
+            HeaderMap::new(),
+        )
+        .await
+        .into_response();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .expect("Body");
+        let html = String::from_utf8_lossy(&body);
+        assert_eq!(html.matches(r#"<ul class="replies">"#).count(), 1);
+        // The reply's own reply is past the depth, so it is only linked
+        assert!(html.contains(r#"<a href="/abc-r-r#comments">1 reply</a>"#));
+    }
+
+    #[tokio::test]
+    async fn test_embeds_have_no_rating_forms() {
+        let state = AppState::with_backend(Arc::new(MockBackend::default()));
+        let body = |response: Response<Body>| async {
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .expect("Body");
+            String::from_utf8_lossy(&body).into_owned()
+        };
+
+        let embed = view_embed_iframe(State(state.clone()), Path("abc".to_string()))
+            .await
+            .into_response();
+        assert!(!body(embed).await.contains(r#"action="/like/"#));
+
+        let page = view(
+            State(state),
+            Path("abc".to_string()),
+            Query(LineSelection { lines: None }),
+            HeaderMap::new(),
+        )
+        .await
+        .into_response();
+        assert!(body(page).await.contains(r#"action="/like/abc""#));
+    }
+
+    #[tokio::test]
+    async fn test_malformed_line_ranges_are_bad_requests() {
+        let state = AppState::with_backend(Arc::new(MockBackend::default()));
+
+        for lines in ["abc", "5-2"] {
+            let response = view_raw(
+                State(state.clone()),
+                Path("abc".to_string()),
+                Query(LineSelection {
+                    lines: Some(lines.to_string()),
+                }),
+            )
+            .await
+            .into_response();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+            let error = response.extensions().get::<PasteError>().expect("Error");
+            let error = serde_json::to_value(error).expect("Serializable error");
+            assert_eq!(error["code"], "bad_request");
+        }
+    }
+}
@@ -0,0 +1,171 @@
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sled::{Db, Tree};
+use std::{io, time::Duration};
+
+use crate::backends::Account;
+
+/// Name of the cookie holding a visitor's session ID
+pub const COOKIE: &str = "pasted_session";
+
+/// A visitor signed in to an upstream account
+#[derive(Serialize, Deserialize)]
+pub struct Session {
+    pub account: Account,
+    pub created_at: i64,
+}
+
+/// Signed-in visitors keyed by an opaque random ID, the only thing their
+/// browser is given. Upstream cookies never leave the server.
+pub struct SessionStore {
+    tree: Tree,
+    max_age: Duration,
+}
+
+// 256 random bits, hex encoded
+fn new_id() -> io::Result<String> {
+    let mut bytes = [0u8; 32];
+    getrandom::getrandom(&mut bytes).map_err(|e| io::Error::other(e.to_string()))?;
+    Ok(bytes.iter().map(|b| format!("{b:02x}")).collect())
+}
+
+impl SessionStore {
+    pub fn new(db: &Db, max_age: Duration) -> sled::Result<Self> {
+        Ok(Self {
+            tree: db.open_tree("sessions")?,
+            max_age,
+        })
+    }
+
+    /// Starts a session for `account` and returns its ID
+    pub fn create(&self, account: Account) -> io::Result<String> {
+        let id = new_id()?;
+        let session = Session {
+            account,
+            created_at: Utc::now().timestamp(),
+        };
+
+        self.tree.insert(&id, serde_json::to_vec(&session)?)?;
+        Ok(id)
+    }
+
+    /// The session with this ID, unless it expired
+    pub fn get(&self, id: &str) -> Option<Session> {
+        let value = self.tree.get(id).ok()??;
+        let session = serde_json::from_slice::<Session>(&value).ok()?;
+
+        if self.is_expired(&session, Utc::now().timestamp()) {
+            self.remove(id);
+            return None;
+        }
+
+        Some(session)
+    }
+
+    pub fn remove(&self, id: &str) {
+        if let Err(e) = self.tree.remove(id) {
+            eprintln!("Failed to remove session: {}", e);
+        }
+    }
+
+    /// Drops every expired session and returns how many were removed.
+    ///
+    /// Lookups already drop expired sessions, this catches the abandoned ones
+    /// so their upstream cookies don't stay on disk.
+    pub fn prune(&self) -> sled::Result<usize> {
+        let now = Utc::now().timestamp();
+
+        let mut removed = 0;
+        for entry in self.tree.iter() {
+            let (key, value) = entry?;
+            let expired = serde_json::from_slice::<Session>(&value)
+                .map_or(true, |session| self.is_expired(&session, now));
+
+            if expired {
+                self.tree.remove(key)?;
+                removed += 1;
+            }
+        }
+
+        Ok(removed)
+    }
+
+    fn is_expired(&self, session: &Session, now: i64) -> bool {
+        now - session.created_at > self.max_age.as_secs() as i64
+    }
+
+    /// How long sessions last, for the cookie's `Max-Age`
+    pub fn max_age(&self) -> Duration {
+        self.max_age
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store(max_age: Duration) -> SessionStore {
+        let db = sled::Config::default()
+            .temporary(true)
+            .open()
+            .expect("Temporary database");
+        SessionStore::new(&db, max_age).expect("Session tree")
+    }
+
+    fn account() -> Account {
+        Account {
+            username: "alice".to_string(),
+            cookies: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_sessions_are_found_by_id() {
+        let store = store(Duration::from_secs(60));
+        let id = store.create(account()).expect("Session");
+
+        assert_eq!(id.len(), 64);
+        assert_eq!(
+            store.get(&id).map(|session| session.account.username),
+            Some("alice".to_string())
+        );
+        assert!(store.get("unknown").is_none());
+
+        store.remove(&id);
+        assert!(store.get(&id).is_none());
+    }
+
+    #[test]
+    fn test_expired_sessions_are_dropped() {
+        let store = store(Duration::ZERO);
+        let session = Session {
+            account: account(),
+            created_at: Utc::now().timestamp() - 1,
+        };
+        store
+            .tree
+            .insert("old", serde_json::to_vec(&session).unwrap())
+            .unwrap();
+
+        assert!(store.get("old").is_none());
+        assert!(store.tree.get("old").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_prune_drops_abandoned_sessions() {
+        let store = store(Duration::from_secs(60));
+        let id = store.create(account()).expect("Session");
+        let session = Session {
+            account: account(),
+            created_at: Utc::now().timestamp() - 61,
+        };
+        store
+            .tree
+            .insert("old", serde_json::to_vec(&session).unwrap())
+            .unwrap();
+
+        assert_eq!(store.prune().expect("Prune"), 1);
+        assert!(store.tree.get("old").unwrap().is_none());
+        assert!(store.get(&id).is_some());
+    }
+}
@@ -0,0 +1,133 @@
+use std::{collections::HashMap, future::Future, sync::Mutex};
+use tokio::sync::broadcast;
+
+/// Deduplicates concurrent work for the same key.
+///
+/// The first caller for a key runs the work while later callers wait for its
+/// result. If the first caller is cancelled before finishing, the waiters run
+/// the work themselves instead of hanging.
+pub struct SingleFlight<T> {
+    inflight: Mutex<HashMap<String, broadcast::Sender<T>>>,
+}
+
+/// Removes the in-flight entry if the leading caller is dropped before finishing
+struct Leader<'a, T> {
+    group: &'a SingleFlight<T>,
+    key: &'a str,
+    finished: bool,
+}
+
+impl<T> Leader<'_, T> {
+    fn finish(mut self) -> Option<broadcast::Sender<T>> {
+        self.finished = true;
+        self.group
+            .inflight
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(self.key)
+    }
+}
+
+impl<T> Drop for Leader<'_, T> {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.group
+                .inflight
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .remove(self.key);
+        }
+    }
+}
+
+impl<T: Clone> SingleFlight<T> {
+    pub fn new() -> Self {
+        Self {
+            inflight: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub async fn run<F, Fut>(&self, key: &str, work: F) -> T
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = T>,
+    {
+        let waiting = {
+            let mut inflight = self.inflight.lock().unwrap_or_else(|e| e.into_inner());
+            match inflight.get(key) {
+                Some(sender) => Some(sender.subscribe()),
+                None => {
+                    inflight.insert(key.to_string(), broadcast::channel(1).0);
+                    None
+                }
+            }
+        };
+
+        if let Some(mut receiver) = waiting {
+            return match receiver.recv().await {
+                Ok(value) => value,
+                Err(_) => work().await,
+            };
+        }
+
+        let leader = Leader {
+            group: self,
+            key,
+            finished: false,
+        };
+        let value = work().await;
+
+        if let Some(sender) = leader.finish() {
+            // Nobody waiting is not an error
+            sender.send(value.clone()).ok();
+        }
+
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_concurrent_calls_share_work() {
+        let group = SingleFlight::new();
+        let calls = AtomicUsize::new(0);
+
+        let work = || async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            tokio::task::yield_now().await;
+            "done"
+        };
+
+        let results = tokio::join!(
+            group.run("key", work),
+            group.run("key", work),
+            group.run("key", work)
+        );
+
+        assert_eq!(results, ("done", "done", "done"));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_sequential_calls_run_again() {
+        let group = SingleFlight::new();
+
+        assert_eq!(group.run("key", || async { 1 }).await, 1);
+        assert_eq!(group.run("key", || async { 2 }).await, 2);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_leader_does_not_block_waiters() {
+        let group = SingleFlight::new();
+
+        let leader = group.run("key", std::future::pending::<u8>);
+        let _ = tokio::time::timeout(std::time::Duration::from_millis(10), leader).await;
+
+        assert_eq!(group.run("key", || async { 1 }).await, 1);
+    }
+}
@@ -0,0 +1,299 @@
+use chrono::Utc;
+use serde::Serialize;
+use serde_json::Value;
+use sled::{Db, Tree};
+use std::time::Duration;
+
+use crate::parsers::paste::Paste;
+
+/// Fields that change on every view and do not make a paste worth archiving again
+const VOLATILE_FIELDS: [&str; 4] = ["views", "rating", "expire", "captured_at"];
+
+/// A copy of a paste as it looked at `captured_at`
+#[derive(Serialize)]
+pub struct Snapshot {
+    /// Milliseconds since the epoch, unique among the snapshots of a paste
+    pub stamp: i64,
+    pub captured_at: i64,
+    pub paste: Paste,
+}
+
+/// Immutable copies of viewed pastes, kept so they can still be read once
+/// upstream deletes them or they expire.
+///
+/// A new snapshot is only stored when the paste changed since the last one.
+/// Snapshots older than `max_age` are dropped, as are all but the newest
+/// `max_per_paste` snapshots of each paste. Zero disables either limit.
+pub struct SnapshotStore {
+    tree: Tree,
+    max_age: i64,
+    max_per_paste: usize,
+}
+
+impl SnapshotStore {
+    pub fn new(db: &Db, max_age: Duration, max_per_paste: usize) -> sled::Result<Self> {
+        Ok(Self {
+            tree: db.open_tree("snapshots")?,
+            max_age: max_age.as_millis() as i64,
+            max_per_paste,
+        })
+    }
+
+    /// Archives `paste` unless it matches the newest snapshot of `id`
+    pub fn record(&self, id: &str, paste: &Paste) -> sled::Result<()> {
+        let Ok(bytes) = serde_json::to_vec(paste) else {
+            return Ok(());
+        };
+
+        if let Some(latest) = self.latest(id)
+            && same_content(&latest.paste, paste)
+        {
+            return Ok(());
+        }
+
+        // Stamps only move forward, so captures within a millisecond never share a key
+        let newest = self
+            .tree
+            .scan_prefix(prefix(id))
+            .keys()
+            .next_back()
+            .and_then(|key| stamp(&key.ok()?));
+        let now = Utc::now().timestamp_millis();
+        let stamp = newest.map_or(now, |newest| now.max(newest + 1));
+
+        self.tree.insert(key(id, stamp), bytes)?;
+        self.prune_paste(id)
+    }
+
+    /// All retained snapshots of `id`, newest first
+    pub fn list(&self, id: &str) -> Vec<Snapshot> {
+        let mut snapshots = self
+            .tree
+            .scan_prefix(prefix(id))
+            .filter_map(|entry| entry.ok())
+            .filter_map(|(key, value)| decode(&key, &value))
+            .filter(|snapshot| !self.is_expired(snapshot.stamp))
+            .collect::<Vec<_>>();
+
+        snapshots.reverse();
+        snapshots
+    }
+
+    pub fn get(&self, id: &str, stamp: i64) -> Option<Snapshot> {
+        let key = key(id, stamp);
+        let value = self.tree.get(&key).ok()??;
+
+        decode(key.as_bytes(), &value).filter(|snapshot| !self.is_expired(snapshot.stamp))
+    }
+
+    pub fn latest(&self, id: &str) -> Option<Snapshot> {
+        let (key, value) = self.tree.scan_prefix(prefix(id)).next_back()?.ok()?;
+
+        decode(&key, &value).filter(|snapshot| !self.is_expired(snapshot.stamp))
+    }
+
+    /// Drops every snapshot of `id`, e.g. once its author deleted it
+    pub fn remove(&self, id: &str) -> sled::Result<()> {
+        for key in self.tree.scan_prefix(prefix(id)).keys() {
+            self.tree.remove(key?)?;
+        }
+
+        Ok(())
+    }
+
+    /// Drops every snapshot older than the retention period and returns how many were removed
+    pub fn prune(&self) -> sled::Result<usize> {
+        if self.max_age == 0 {
+            return Ok(0);
+        }
+
+        let mut removed = 0;
+        for entry in self.tree.iter() {
+            let (key, _) = entry?;
+            let expired = stamp(&key).is_none_or(|at| self.is_expired(at));
+
+            if expired {
+                self.tree.remove(key)?;
+                removed += 1;
+            }
+        }
+
+        Ok(removed)
+    }
+
+    fn prune_paste(&self, id: &str) -> sled::Result<()> {
+        let keys = self
+            .tree
+            .scan_prefix(prefix(id))
+            .keys()
+            .collect::<sled::Result<Vec<_>>>()?;
+
+        let excess = match self.max_per_paste {
+            0 => 0,
+            max => keys.len().saturating_sub(max),
+        };
+
+        for (i, key) in keys.into_iter().enumerate() {
+            let expired = stamp(&key).is_none_or(|at| self.is_expired(at));
+            if i < excess || expired {
+                self.tree.remove(key)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn is_expired(&self, stamp: i64) -> bool {
+        self.max_age > 0 && Utc::now().timestamp_millis() - stamp >= self.max_age
+    }
+}
+
+fn prefix(id: &str) -> String {
+    format!("{id}/")
+}
+
+// Zero padded so keys sort by capture time
+fn key(id: &str, stamp: i64) -> String {
+    format!("{id}/{stamp:020}")
+}
+
+fn stamp(key: &[u8]) -> Option<i64> {
+    let key = std::str::from_utf8(key).ok()?;
+    key.rsplit_once('/')?.1.parse().ok()
+}
+
+fn decode(key: &[u8], value: &[u8]) -> Option<Snapshot> {
+    let stamp = stamp(key)?;
+
+    Some(Snapshot {
+        stamp,
+        captured_at: stamp.div_euclid(1000),
+        paste: serde_json::from_slice(value).ok()?,
+    })
+}
+
+fn same_content(a: &Paste, b: &Paste) -> bool {
+    let strip = |paste: &Paste| {
+        let mut value = serde_json::to_value(paste).ok()?;
+        if let Value::Object(fields) = &mut value {
+            for field in VOLATILE_FIELDS {
+                fields.remove(field);
+            }
+        }
+        Some(value)
+    };
+
+    match (strip(a), strip(b)) {
+        (Some(a), Some(b)) => a == b,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn paste(content: &str, views: u32) -> Paste {
+        serde_json::from_value(json!({
+            "id": "abc",
+            "title": "Title",
+            "tags": [],
+            "container": {
+                "category": null,
+                "size": 0,
+                "likes": null,
+                "dislikes": null,
+                "id": null,
+                "format": "text",
+                "format_name": "Plain Text",
+                "content": content,
+            },
+            "author": {
+                "username": "Guest",
+                "registered": false,
+                "pro": false,
+                "icon_url": "",
+            },
+            "date": 0,
+            "edit_date": null,
+            "views": views,
+            "rating": 0.0,
+            "expire": "Never",
+            "comment_for": null,
+            "unlisted": false,
+            "num_comments": 0,
+            "comments": [],
+            "locked": false,
+        }))
+        .expect("Valid paste")
+    }
+
+    fn store(max_age: u64, max_per_paste: usize) -> SnapshotStore {
+        let db = sled::Config::default()
+            .temporary(true)
+            .open()
+            .expect("Temporary database");
+        SnapshotStore::new(&db, Duration::from_secs(max_age), max_per_paste).expect("Snapshot tree")
+    }
+
+    #[test]
+    fn test_unchanged_pastes_are_not_recorded_again() {
+        let store = store(0, 0);
+
+        store.record("abc", &paste("hello", 1)).expect("Record");
+        store.record("abc", &paste("hello", 2)).expect("Record");
+
+        assert_eq!(store.list("abc").len(), 1);
+        assert_eq!(
+            store.latest("abc").map(|s| s.paste.content().to_string()),
+            Some("hello".to_string())
+        );
+    }
+
+    #[test]
+    fn test_captures_in_one_second_are_kept_apart() {
+        let store = store(0, 0);
+
+        store.record("abc", &paste("one", 0)).expect("Record");
+        store.record("abc", &paste("two", 0)).expect("Record");
+        store.record("abc", &paste("three", 0)).expect("Record");
+
+        let snapshots = store.list("abc");
+        let contents = snapshots
+            .iter()
+            .map(|snapshot| snapshot.paste.content())
+            .collect::<Vec<_>>();
+        assert_eq!(contents, ["three", "two", "one"]);
+        assert!(store.get("abc", snapshots[1].stamp).is_some());
+    }
+
+    #[test]
+    fn test_old_snapshots_are_pruned_per_paste() {
+        let store = store(0, 1);
+
+        store
+            .tree
+            .insert(key("abc", 1), serde_json::to_vec(&paste("old", 0)).unwrap())
+            .unwrap();
+        store.record("abc", &paste("new", 0)).expect("Record");
+
+        let snapshots = store.list("abc");
+        assert_eq!(snapshots.len(), 1);
+        assert_eq!(snapshots[0].paste.content(), "new");
+    }
+
+    #[test]
+    fn test_expired_snapshots_are_hidden_and_pruned() {
+        let store = store(60, 0);
+
+        store
+            .tree
+            .insert(key("abc", 1), serde_json::to_vec(&paste("old", 0)).unwrap())
+            .unwrap();
+
+        assert!(store.latest("abc").is_none());
+        assert_eq!(store.prune().ok(), Some(1));
+    }
+}
@@ -1,38 +1,192 @@
 use sled::{Config, Db, Mode};
+use std::{env, error::Error, str::FromStr, sync::Arc, time::Duration};
 
-use crate::client::Client;
+use crate::{
+    backends::{
+        self, Backend, cached::CachedBackend, coalesced::CoalescedBackend,
+        revisions::RevisionBackend, snapshot::SnapshotBackend, threaded::ThreadedBackend,
+    },
+    client::{Client, ClientConfig},
+    constants::URL,
+    ratelimit::RateLimiter,
+    revisions::RevisionStore,
+    sessions::SessionStore,
+    snapshots::SnapshotStore,
+};
+
+/// Reads a setting from the environment, falling back to `default` when unset or invalid
+fn env_or<T: FromStr>(name: &str, default: T) -> T {
+    env::var(name)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
 
 #[derive(Clone)]
 pub struct AppState {
-    pub client: Client,
+    pub backend: Arc<dyn Backend>,
+    pub upstream_url: Arc<str>,
     pub db: Db,
+    /// Archive of viewed pastes, only present when snapshots are enabled
+    pub snapshots: Option<Arc<SnapshotStore>>,
+    /// Content history of viewed pastes, only present when revisions are enabled
+    pub revisions: Option<Arc<RevisionStore>>,
+    /// Visitors signed in to an upstream account
+    pub sessions: Arc<SessionStore>,
+    /// Likes, dislikes and reports each visitor may still send upstream
+    pub actions: Arc<RateLimiter>,
+    /// The paste cache, only present when caching or the fallback is enabled
+    pub cache: Option<Arc<CachedBackend>>,
 }
 
 impl AppState {
-    pub fn new(client: Client, db: Db) -> Self {
-        Self { client, db }
+    pub fn new(
+        backend: Arc<dyn Backend>,
+        upstream_url: &str,
+        db: Db,
+        snapshots: Option<Arc<SnapshotStore>>,
+        revisions: Option<Arc<RevisionStore>>,
+        sessions: Arc<SessionStore>,
+        actions: Arc<RateLimiter>,
+    ) -> Self {
+        Self {
+            backend,
+            upstream_url: upstream_url.into(),
+            db,
+            snapshots,
+            revisions,
+            sessions,
+            actions,
+            cache: None,
+        }
     }
 
+    #[cfg(test)]
     pub fn default() -> Self {
-        let client = Client::new();
+        Self::with_backend(
+            backends::from_name("pastebin", Client::default(), URL).expect("Known backend"),
+        )
+    }
+
+    /// State around `backend`, with a temporary database
+    #[cfg(test)]
+    pub fn with_backend(backend: Arc<dyn Backend>) -> Self {
         let db = Config::default()
-            .path("cache")
-            .flush_every_ms(Some(1000))
-            .mode(Mode::HighThroughput)
+            .temporary(true)
             .open()
-            .unwrap();
+            .expect("Failed to open temporary database");
+        let sessions = Arc::new(
+            SessionStore::new(&db, Duration::from_secs(60)).expect("Failed to open sessions"),
+        );
+        let actions = Arc::new(RateLimiter::new(10, Duration::from_secs(600)));
 
-        Self::new(client, db)
+        Self::new(backend, URL, db, None, None, sessions, actions)
     }
 
-    pub fn try_default() -> Result<Self, sled::Error> {
-        let client = Client::new();
+    pub fn try_default() -> Result<Self, Box<dyn Error>> {
+        let name = env::var("BACKEND").unwrap_or("pastebin".to_string());
+        let upstream_url = env::var("UPSTREAM_URL").unwrap_or(URL.to_string());
+        let upstream_url = upstream_url.trim_end_matches('/');
+        let defaults = ClientConfig::default();
+        let client = Client::with_config(ClientConfig {
+            max_concurrent: env_or("UPSTREAM_CONCURRENCY", defaults.max_concurrent),
+            timeout: Duration::from_secs(env_or("UPSTREAM_TIMEOUT", defaults.timeout.as_secs())),
+            retries: env_or("UPSTREAM_RETRIES", defaults.retries),
+            backoff: Duration::from_millis(env_or(
+                "UPSTREAM_BACKOFF_MS",
+                defaults.backoff.as_millis() as u64,
+            )),
+            breaker_threshold: env_or("UPSTREAM_BREAKER_THRESHOLD", defaults.breaker_threshold),
+            breaker_cooldown: Duration::from_secs(env_or(
+                "UPSTREAM_BREAKER_COOLDOWN",
+                defaults.breaker_cooldown.as_secs(),
+            )),
+        });
+        let backend = backends::from_name(&name, client, upstream_url)
+            .ok_or_else(|| format!("Unknown backend '{}'", name))?;
+        let mut backend: Arc<dyn Backend> = Arc::new(CoalescedBackend::new(backend));
+
         let db = Config::default()
             .path("cache")
             .flush_every_ms(Some(1000))
             .mode(Mode::HighThroughput)
             .open()?;
 
-        Ok(Self::new(client, db))
+        let revisions = if env_or("REVISIONS", false) {
+            let store = Arc::new(RevisionStore::new(
+                &db,
+                Duration::from_secs(env_or("REVISION_MAX_AGE", 90) * 24 * 60 * 60),
+                env_or("REVISION_MAX_PER_PASTE", 20),
+            )?);
+            backend = Arc::new(RevisionBackend::new(backend, store.clone()));
+            Some(store)
+        } else {
+            None
+        };
+
+        let snapshots = if env_or("SNAPSHOTS", false) {
+            let store = Arc::new(SnapshotStore::new(
+                &db,
+                Duration::from_secs(env_or("SNAPSHOT_MAX_AGE", 90) * 24 * 60 * 60),
+                env_or("SNAPSHOT_MAX_PER_PASTE", 10),
+            )?);
+            backend = Arc::new(SnapshotBackend::new(backend, store.clone()));
+            Some(store)
+        } else {
+            None
+        };
+
+        // Without a TTL every request goes upstream, but the last known copy
+        // is still served when that fails
+        let cache_ttl = env_or("CACHE_TTL", 300);
+        let cache_stale = if cache_ttl > 0 {
+            env_or("CACHE_STALE", 3600)
+        } else {
+            0
+        };
+        // Off unless asked for, as it keeps serving pastes their authors deleted
+        let cache_fallback = env_or("CACHE_FALLBACK", 0);
+        let cache = if cache_ttl > 0 || cache_fallback > 0 {
+            let cache = Arc::new(CachedBackend::new(
+                backend,
+                &db,
+                Duration::from_secs(cache_ttl),
+                Duration::from_secs(cache_stale),
+                Duration::from_secs(cache_fallback),
+            )?);
+            backend = cache.clone();
+            Some(cache)
+        } else {
+            None
+        };
+
+        // Outside the cache, so each comment page is cached on its own
+        let comment_depth = env_or("COMMENT_DEPTH", 3);
+        if comment_depth > 0 {
+            backend = Arc::new(ThreadedBackend::new(backend, comment_depth));
+        }
+
+        let sessions = Arc::new(SessionStore::new(
+            &db,
+            Duration::from_secs(env_or("SESSION_MAX_AGE", 30) * 24 * 60 * 60),
+        )?);
+
+        let actions = Arc::new(RateLimiter::new(
+            env_or("ACTION_LIMIT", 10),
+            Duration::from_secs(env_or("ACTION_WINDOW", 600)),
+        ));
+
+        Ok(Self {
+            cache,
+            ..Self::new(
+                backend,
+                upstream_url,
+                db,
+                snapshots,
+                revisions,
+                sessions,
+                actions,
+            )
+        })
     }
 }
@@ -0,0 +1,90 @@
+//! A minimal writer for uncompressed ustar archives, enough to bundle a paste
+//! with its comments into one download.
+
+const BLOCK: usize = 512;
+
+/// An archive built in memory, entry by entry
+#[derive(Default)]
+pub struct Tar {
+    data: Vec<u8>,
+}
+
+// Numeric header fields are zero padded octal, terminated by a NUL
+fn octal(field: &mut [u8], value: u64) {
+    let width = field.len() - 1;
+    let digits = format!("{value:0width$o}");
+    let start = digits.len().saturating_sub(width);
+    field[..width].copy_from_slice(&digits.as_bytes()[start..]);
+    field[width] = 0;
+}
+
+impl Tar {
+    /// Adds a regular file, `path` is cut to the 100 bytes ustar names allow
+    pub fn append(&mut self, path: &str, content: &[u8], modified: i64) {
+        let mut header = [0u8; BLOCK];
+
+        let end = path.floor_char_boundary(100);
+        header[..end].copy_from_slice(&path.as_bytes()[..end]);
+        octal(&mut header[100..108], 0o644);
+        octal(&mut header[108..116], 0);
+        octal(&mut header[116..124], 0);
+        octal(&mut header[124..136], content.len() as u64);
+        octal(&mut header[136..148], modified.max(0) as u64);
+        header[156] = b'0';
+        header[257..263].copy_from_slice(b"ustar\0");
+        header[263..265].copy_from_slice(b"00");
+
+        // The checksum is computed with its own field filled with spaces
+        header[148..156].fill(b' ');
+        let checksum = header.iter().map(|&b| u64::from(b)).sum::<u64>();
+        octal(&mut header[148..155], checksum);
+
+        self.data.extend_from_slice(&header);
+        self.data.extend_from_slice(content);
+        let padding = (BLOCK - content.len() % BLOCK) % BLOCK;
+        self.data.resize(self.data.len() + padding, 0);
+    }
+
+    /// Ends the archive with the two empty blocks readers expect
+    pub fn finish(mut self) -> Vec<u8> {
+        self.data.resize(self.data.len() + 2 * BLOCK, 0);
+        self.data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layout() {
+        let mut tar = Tar::default();
+        tar.append("hello.txt", b"hello", 1_700_000_000);
+        tar.append("empty.txt", b"", 0);
+        let data = tar.finish();
+
+        // Header and one padded block, a lone header, then the end of archive
+        assert_eq!(data.len(), 5 * BLOCK);
+        assert_eq!(&data[..9], b"hello.txt");
+        assert_eq!(&data[124..136], b"00000000005\0");
+        assert_eq!(&data[257..263], b"ustar\0");
+        assert_eq!(&data[BLOCK..BLOCK + 5], b"hello");
+        assert_eq!(&data[2 * BLOCK..2 * BLOCK + 9], b"empty.txt");
+        assert!(data[3 * BLOCK..].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn test_checksum() {
+        let mut tar = Tar::default();
+        tar.append("a", b"a", 0);
+        let data = tar.finish();
+
+        let mut header = data[..BLOCK].to_vec();
+        let stored = std::str::from_utf8(&header[148..154]).expect("Octal digits");
+        let stored = u64::from_str_radix(stored, 8).expect("Octal checksum");
+        assert_eq!(header[154], 0);
+
+        header[148..156].fill(b' ');
+        assert_eq!(stored, header.iter().map(|&b| u64::from(b)).sum::<u64>());
+    }
+}
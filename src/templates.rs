@@ -4,6 +4,8 @@ use once_cell::sync::Lazy;
 use std::{collections::HashMap, env, process};
 use tera::{Error, Result, Tera, Value};
 
+use crate::{catalog, highlight, lines::LineRanges, markdown};
+
 pub static BANNER: Lazy<String> = Lazy::new(|| env::var("BANNER").unwrap_or_default());
 
 #[cfg(feature = "include_templates")]
@@ -45,7 +47,13 @@ pub static TEMPLATES: Lazy<Tera> = Lazy::new(|| {
     tera.register_filter("format_date", format_date);
     tera.register_filter("format_date_user", format_date_user);
     tera.register_filter("format_bytes", format_bytes);
+    tera.register_filter("highlight", highlight);
+    tera.register_filter("markdown", markdown);
+    tera.register_filter("format_name", format_name);
     tera.register_function("get_banner", get_banner);
+    tera.register_function("highlight_css", highlight_css);
+    tera.register_function("formats", formats);
+    tera.register_function("categories", categories);
     tera
 });
 
@@ -104,6 +112,99 @@ fn format_bytes(value: &Value, _: &HashMap<String, Value>) -> Result<Value> {
     }
 }
 
+fn highlight(value: &Value, args: &HashMap<String, Value>) -> Result<Value> {
+    let content = value
+        .as_str()
+        .ok_or_else(|| Error::msg("Filter `highlight` was used on a value that isn't a string."))?;
+    let format = args.get("format").and_then(Value::as_str).unwrap_or("text");
+
+    let html = highlight::highlight(content, format).unwrap_or_else(|| tera::escape_html(content));
+    let html = match args.get("anchors").and_then(Value::as_bool) {
+        Some(true) => {
+            let selected = args
+                .get("selected")
+                .and_then(Value::as_str)
+                .and_then(|lines| lines.parse().ok())
+                .unwrap_or_default();
+            number_lines(&html, &selected)
+        }
+        _ => html,
+    };
+
+    match tera::to_value(html) {
+        Ok(v) => Ok(v),
+        Err(e) => Err(Error::msg(format!(
+            "Failed to serialize highlighted code: {}",
+            e
+        ))),
+    }
+}
+
+// Wraps each line in an anchor that links to it as a highlighted range
+fn number_lines(html: &str, selected: &LineRanges) -> String {
+    highlight::split_lines(html)
+        .iter()
+        .enumerate()
+        .map(|(i, line)| {
+            let n = i + 1;
+            let class = if selected.contains(n) {
+                "line selected"
+            } else {
+                "line"
+            };
+            format!(
+                "<span class=\"{class}\" id=\"L{n}\"><a class=\"line-number\" href=\"?lines={n}#L{n}\">{n}</a>{line}</span>"
+            )
+        })
+        .collect()
+}
+
+fn markdown(value: &Value, _: &HashMap<String, Value>) -> Result<Value> {
+    let content = value
+        .as_str()
+        .ok_or_else(|| Error::msg("Filter `markdown` was used on a value that isn't a string."))?;
+
+    // Empty for pastes too large to render, so templates show their source instead
+    match tera::to_value(markdown::render(content).unwrap_or_default()) {
+        Ok(v) => Ok(v),
+        Err(e) => Err(Error::msg(format!(
+            "Failed to serialize rendered Markdown: {}",
+            e
+        ))),
+    }
+}
+
+// Display name of a format slug, unknown slugs are shown as they are
+fn format_name(value: &Value, _: &HashMap<String, Value>) -> Result<Value> {
+    let slug = value.as_str().ok_or_else(|| {
+        Error::msg("Filter `format_name` was used on a value that isn't a string.")
+    })?;
+
+    let name = catalog::format_by_slug(slug).map_or(slug, |format| format.name);
+    Ok(Value::from(name))
+}
+
+fn formats(_: &HashMap<String, Value>) -> Result<Value> {
+    match tera::to_value(catalog::formats_by_name()) {
+        Ok(v) => Ok(v),
+        Err(e) => Err(Error::msg(format!("Failed to serialize formats: {}", e))),
+    }
+}
+
+fn categories(_: &HashMap<String, Value>) -> Result<Value> {
+    match tera::to_value(catalog::CATEGORIES) {
+        Ok(v) => Ok(v),
+        Err(e) => Err(Error::msg(format!("Failed to serialize categories: {}", e))),
+    }
+}
+
+fn highlight_css(_: &HashMap<String, Value>) -> Result<Value> {
+    match tera::to_value(highlight::CSS.as_str()) {
+        Ok(v) => Ok(v),
+        Err(e) => Err(Error::msg(format!("Failed to serialize stylesheet: {}", e))),
+    }
+}
+
 fn get_banner(_: &HashMap<String, Value>) -> Result<Value> {
     match tera::to_value(BANNER.clone()) {
         Ok(v) => Ok(v),
@@ -138,4 +239,70 @@ mod tests {
         assert_eq!(result.as_str().unwrap_or(""), "147.483647 MB");
         Ok(())
     }
+
+    #[test]
+    fn test_highlight_anchors_lines() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let value = tera::to_value("a\nb\n")?;
+        let args = HashMap::from([
+            ("anchors".to_string(), tera::to_value(true)?),
+            ("selected".to_string(), tera::to_value("2")?),
+        ]);
+        let result = highlight(&value, &args)?;
+        let html = result.as_str().unwrap_or("");
+
+        assert!(html.contains("<span class=\"line\" id=\"L1\">"));
+        assert!(html.contains("<span class=\"line selected\" id=\"L2\">"));
+        assert!(!html.contains("L3"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_highlight_escapes_unknown_formats()
+    -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let value = tera::to_value("<script>")?;
+        let args = HashMap::from([("format".to_string(), tera::to_value("text")?)]);
+        let result = highlight(&value, &args)?;
+        assert_eq!(result.as_str().unwrap_or(""), "&lt;script&gt;");
+        Ok(())
+    }
+
+    #[test]
+    fn test_large_markdown_is_shown_as_source()
+    -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let mut tera = TEMPLATES.clone();
+        tera.add_raw_template(
+            "markdown_test.html",
+            r#"{% import "container.html" as c %}{{ c::generate_container(container=container) }}"#,
+        )?;
+        let render = |content: String| {
+            let mut context = tera::Context::new();
+            context.insert(
+                "container",
+                &serde_json::json!({
+                    "format": "markdown",
+                    "format_name": "Markdown",
+                    "size": content.len(),
+                    "category": null,
+                    "likes": 0,
+                    "dislikes": 0,
+                    "id": null,
+                    "content": content,
+                }),
+            );
+            tera.render("markdown_test.html", &context)
+        };
+
+        let html = render("# Title".to_string())?;
+        assert!(html.contains("<h1>Title</h1>"));
+        assert!(html.contains("markdown-toggle"));
+
+        let html = render(format!(
+            "# Title\n{}",
+            "x".repeat(highlight::MAX_HIGHLIGHT_BYTES)
+        ))?;
+        assert!(!html.contains("<h1>"));
+        assert!(!html.contains("markdown-toggle"));
+        assert!(html.contains("# Title"));
+        Ok(())
+    }
 }